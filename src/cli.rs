@@ -0,0 +1,133 @@
+use crate::config::Config;
+use crate::crypto;
+use crate::dates;
+use crate::data::DataStore;
+use crate::import::import_task_js;
+use crate::lock::{DataLock, LockStatus};
+use color_eyre::eyre::{eyre, Result};
+use std::fs;
+
+const USAGE: &str = "Usage:
+  taskim                   Start the TUI
+  taskim import <file>     Merge a task-js export into the task data
+  taskim export [file]     Write the task data as plain JSON to a file or stdout
+  taskim list [date]       Print the tasks of a day (default today), e.g. `next friday`
+  taskim decrypt           Remove the encryption from the data file";
+
+/// Handle command line subcommands. Returns `None` when the TUI should start.
+pub fn run(args: &[String]) -> Option<Result<()>> {
+    let command = args.first()?;
+    Some(match command.as_str() {
+        "import" => match args.get(1) {
+            Some(path) => import(path),
+            None => Err(eyre!("Missing file to import\n\n{}", USAGE)),
+        },
+        "export" => export(args.get(1).map(String::as_str)),
+        "decrypt" => decrypt(),
+        "list" => list(&args[1..].join(" ")),
+        "help" | "-h" | "--help" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        other => Err(eyre!("Unknown command: {}\n\n{}", other, USAGE)),
+    })
+}
+
+/// Open the data store for writing, refusing while a TUI instance holds it
+fn open_store(action: &str) -> Result<(Config, DataStore, DataLock)> {
+    let config = Config::load("config.yml")?;
+    let store = DataStore::open(config.storage, &config.lists)?;
+    let lock = match DataLock::acquire(store.path(), config.lock_mode)? {
+        LockStatus::Acquired(lock) => lock,
+        LockStatus::HeldBy(pid) => {
+            return Err(eyre!(
+                "The data file is in use by taskim (pid {}); close it before {}",
+                pid,
+                action
+            ))
+        }
+    };
+    Ok((config, store, lock))
+}
+
+fn import(path: &str) -> Result<()> {
+    let (config, mut store, _lock) = open_store("importing")?;
+    if crypto::unlock(&mut store, config.encrypt)? && config.git.enabled {
+        println!("Encrypted {}, but {}", store.path().display(), crypto::PLAINTEXT_HISTORY_WARNING);
+    }
+    let mut data = store.load()?;
+    let report = import_task_js(path, &mut data)?;
+
+    for change in &report.changes {
+        println!("  - {}", change);
+    }
+    println!(
+        "Imported {} task(s), {} updated, {} already present, {} skipped.",
+        report.imported, report.updated, report.already_present, report.skipped
+    );
+
+    if report.imported > 0 || report.updated > 0 {
+        store.mark_dirty(data.events.iter().map(|t| t.id.as_str()));
+        store.save(&data)?;
+    }
+    Ok(())
+}
+
+fn export(path: Option<&str>) -> Result<()> {
+    let config = Config::load("config.yml")?;
+    let mut store = DataStore::open(config.storage, &config.lists)?;
+    crypto::unlock(&mut store, false)?;
+    let data = store.load()?;
+    let content = serde_json::to_string_pretty(&data)?;
+
+    match path {
+        Some(path) => {
+            fs::write(path, content)?;
+            eprintln!("Exported {} task(s) to {}", data.events.len(), path);
+        }
+        None => println!("{}", content),
+    }
+    Ok(())
+}
+
+fn list(expression: &str) -> Result<()> {
+    let today = chrono::Local::now().date_naive();
+    let date = if expression.trim().is_empty() {
+        today
+    } else {
+        dates::parse_date(expression, today)
+            .ok_or_else(|| eyre!("Unknown date: {}\n\n{}", expression, USAGE))?
+    };
+    let config = Config::load("config.yml")?;
+    let mut store = DataStore::open(config.storage, &config.lists)?;
+    crypto::unlock(&mut store, false)?;
+    let data = store.load()?;
+
+    let mut tasks: Vec<_> = data
+        .events
+        .iter()
+        .filter(|t| t.start.date_naive() == date)
+        .collect();
+    tasks.sort_by_key(|t| t.order);
+    println!("{}", date.format("%A %Y-%m-%d"));
+    for task in tasks {
+        let check = if task.completed { "x" } else { " " };
+        println!("  [{}] {}", check, task.display_title());
+    }
+    Ok(())
+}
+
+fn decrypt() -> Result<()> {
+    let (config, mut store, _lock) = open_store("decrypting")?;
+    crypto::unlock(&mut store, false)?;
+    if !store.is_encrypted() {
+        println!("{} is not encrypted", store.path().display());
+        return Ok(());
+    }
+    store.set_cipher(None)?;
+    println!("Decrypted {}", store.path().display());
+    if config.encrypt {
+        println!("Set `encrypt: false` in config.yml, or taskim will encrypt it again on startup");
+    }
+    Ok(())
+}
@@ -0,0 +1,225 @@
+use crate::task::Task;
+use base64::Engine;
+use std::io::Write;
+use std::process::Command;
+
+/// How yanked tasks are written to the system clipboard
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ClipboardFormat {
+    /// One title per line, notes indented below
+    #[default]
+    Plain,
+    /// A markdown checklist with notes as nested items
+    Markdown,
+}
+
+impl ClipboardFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "plain" | "text" => Some(Self::Plain),
+            "markdown" | "md" => Some(Self::Markdown),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Plain => "plain",
+            Self::Markdown => "markdown",
+        }
+    }
+}
+
+/// Commands tried in order to read the system clipboard
+const PASTE_COMMANDS: &[&[&str]] = &[
+    &["pbpaste"],
+    &["wl-paste", "--no-newline"],
+    &["xclip", "-selection", "clipboard", "-o"],
+    &["xsel", "--clipboard", "--output"],
+    &["powershell.exe", "-NoProfile", "-Command", "Get-Clipboard"],
+];
+
+pub fn format_tasks(tasks: &[Task], format: ClipboardFormat) -> String {
+    let mut text = String::new();
+    for task in tasks {
+        match format {
+            ClipboardFormat::Plain => {
+                text.push_str(&format!("{}\n", task.title));
+                for comment in &task.comments {
+                    text.push_str(&format!("    {}\n", comment.text));
+                }
+            }
+            ClipboardFormat::Markdown => {
+                let check = if task.completed { "x" } else { " " };
+                text.push_str(&format!("- [{}] {}\n", check, task.title));
+                for comment in &task.comments {
+                    text.push_str(&format!("  - {}\n", comment.text));
+                }
+            }
+        }
+    }
+    text
+}
+
+/// Ask the terminal to put `text` on the system clipboard with the OSC 52
+/// escape, which also works over SSH in terminals that support it
+pub fn copy_osc52(text: &str) -> std::io::Result<()> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let mut stdout = std::io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", encoded)?;
+    stdout.flush()
+}
+
+/// Read the system clipboard with `command`, or the first clipboard tool found
+pub fn read_system(command: Option<&str>) -> Result<String, String> {
+    if let Some(command) = command {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .output()
+            .map_err(|e| format!("Could not run {}: {}", command, e))?;
+        if !output.status.success() {
+            return Err(format!("{} failed", command));
+        }
+        return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+    }
+    for args in PASTE_COMMANDS {
+        if let Ok(output) = Command::new(args[0]).args(&args[1..]).output() {
+            if output.status.success() {
+                return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+            }
+        }
+    }
+    Err(String::from(
+        "No clipboard tool found; set clipboard.paste_command in config.yml",
+    ))
+}
+
+/// A task read from pasted text
+#[derive(Debug, Clone, PartialEq)]
+pub struct PastedTask {
+    pub title: String,
+    pub completed: bool,
+    pub notes: Vec<String>,
+}
+
+/// Tasks in pasted text, one per line. Indented lines are notes of the task
+/// above, and markdown list markers and checkboxes are stripped, so text in
+/// either clipboard format pastes back as the same tasks.
+pub fn parse_lines(text: &str) -> Vec<PastedTask> {
+    let mut tasks: Vec<PastedTask> = Vec::new();
+    for line in text.lines() {
+        let indented = line.starts_with([' ', '\t']);
+        let (title, completed) = strip_markers(line.trim());
+        if title.is_empty() {
+            continue;
+        }
+        match tasks.last_mut() {
+            Some(task) if indented => task.notes.push(title.to_string()),
+            _ => tasks.push(PastedTask {
+                title: title.to_string(),
+                completed,
+                notes: Vec::new(),
+            }),
+        }
+    }
+    tasks
+}
+
+fn strip_markers(line: &str) -> (&str, bool) {
+    let line = ["- ", "* ", "+ "]
+        .iter()
+        .find_map(|marker| line.strip_prefix(marker))
+        .unwrap_or(line);
+    for (checkbox, completed) in [("[ ] ", false), ("[x] ", true), ("[X] ", true)] {
+        if let Some(title) = line.strip_prefix(checkbox) {
+            return (title.trim(), completed);
+        }
+    }
+    (line, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn yanked() -> Vec<Task> {
+        let mut report = Task::new(String::from("Write report"), Utc::now());
+        report.add_comment(String::from("Figures from Q3"));
+        report.add_comment(String::from("Ask Dana to review"));
+        let mut call = Task::new(String::from("Call the bank"), Utc::now());
+        call.completed = true;
+        let plan = Task::new(String::from("Plan - [ ] nothing"), Utc::now());
+        vec![report, call, plan]
+    }
+
+    fn pasted(task: &Task, completed: bool) -> PastedTask {
+        PastedTask {
+            title: task.title.clone(),
+            completed,
+            notes: task.comments.iter().map(|c| c.text.clone()).collect(),
+        }
+    }
+
+    #[test]
+    fn both_formats_paste_back_as_the_same_tasks() {
+        let tasks = yanked();
+
+        // Plain text has no checkboxes, so completion does not survive it
+        let plain = format_tasks(&tasks, ClipboardFormat::Plain);
+        let expected: Vec<PastedTask> = tasks.iter().map(|t| pasted(t, false)).collect();
+        assert_eq!(parse_lines(&plain), expected);
+
+        let markdown = format_tasks(&tasks, ClipboardFormat::Markdown);
+        let expected: Vec<PastedTask> = tasks.iter().map(|t| pasted(t, t.completed)).collect();
+        assert_eq!(parse_lines(&markdown), expected);
+    }
+
+    #[test]
+    fn formats_write_notes_below_their_task() {
+        let tasks = yanked();
+        assert_eq!(
+            format_tasks(&tasks[..2], ClipboardFormat::Plain),
+            "Write report\n    Figures from Q3\n    Ask Dana to review\nCall the bank\n"
+        );
+        assert_eq!(
+            format_tasks(&tasks[..2], ClipboardFormat::Markdown),
+            "- [ ] Write report\n  - Figures from Q3\n  - Ask Dana to review\n- [x] Call the bank\n"
+        );
+    }
+
+    #[test]
+    fn pasting_text_from_elsewhere() {
+        let text = "* [X] Done elsewhere\n\n+ Buy milk\n\tsemi-skimmed\n   \n1. Numbered\n";
+        let titles: Vec<(String, bool, usize)> = parse_lines(text)
+            .into_iter()
+            .map(|t| (t.title, t.completed, t.notes.len()))
+            .collect();
+        assert_eq!(
+            titles,
+            [
+                (String::from("Done elsewhere"), true, 0),
+                (String::from("Buy milk"), false, 1),
+                (String::from("1. Numbered"), false, 0),
+            ]
+        );
+
+        // An indented first line has no task to belong to
+        assert_eq!(parse_lines("  - orphan")[0].title, "orphan");
+    }
+
+    #[test]
+    fn format_names() {
+        for (name, format) in [
+            ("plain", ClipboardFormat::Plain),
+            ("Text", ClipboardFormat::Plain),
+            ("markdown", ClipboardFormat::Markdown),
+            ("MD", ClipboardFormat::Markdown),
+        ] {
+            assert_eq!(ClipboardFormat::from_name(name), Some(format), "{}", name);
+        }
+        assert_eq!(ClipboardFormat::from_name("html"), None);
+        assert_eq!(ClipboardFormat::Markdown.name(), "markdown");
+    }
+}
@@ -0,0 +1,269 @@
+use chrono::NaiveDate;
+
+/// One end of a date range on the command line
+#[derive(Debug, Clone, PartialEq)]
+pub enum Address {
+    /// `.` is the selected day; `.+3` and `.-1` are days from it
+    Selected(i64),
+    /// `2025-06-15` or `06/15/2025`
+    Date(NaiveDate),
+    /// `15`, a day of the shown month
+    Day(u32),
+    /// `2025`, the selected day in another year
+    Year(i32),
+    /// `'a`, the day of a mark
+    Mark(char),
+}
+
+/// The dates a command applies to
+#[derive(Debug, Clone, PartialEq)]
+pub enum RangeSpec {
+    /// `%`, the whole shown month
+    Month,
+    /// `a` or `a,b`
+    Span(Address, Option<Address>),
+}
+
+/// A command line split into `[range]name[!] [args]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandLine {
+    pub range: Option<RangeSpec>,
+    pub name: String,
+    pub bang: bool,
+    pub args: String,
+}
+
+pub fn parse(input: &str) -> Result<CommandLine, String> {
+    let input = input.trim().trim_start_matches(':');
+    let (range, rest) = parse_range(input)?;
+    let rest = rest.trim_start();
+    let name_len = rest
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(rest.len());
+    let (name, rest) = rest.split_at(name_len);
+    let (bang, args) = match rest.strip_prefix('!') {
+        Some(args) => (true, args),
+        None => (false, rest),
+    };
+    if name.is_empty() && !args.trim().is_empty() {
+        return Err(format!(
+            "Unknown command: {}. Type ':help' for available commands.",
+            input
+        ));
+    }
+    Ok(CommandLine {
+        range,
+        name: name.to_string(),
+        bang,
+        args: args.trim().to_string(),
+    })
+}
+
+fn parse_range(input: &str) -> Result<(Option<RangeSpec>, &str), String> {
+    if let Some(rest) = input.strip_prefix('%') {
+        return Ok((Some(RangeSpec::Month), rest));
+    }
+    let Some((start, rest)) = parse_address(input)? else {
+        return Ok((None, input));
+    };
+    let Some(rest) = rest.strip_prefix(',') else {
+        return Ok((Some(RangeSpec::Span(start, None)), rest));
+    };
+    match parse_address(rest)? {
+        Some((end, rest)) => Ok((Some(RangeSpec::Span(start, Some(end))), rest)),
+        None => Err(format!("Missing the end of the range: {}", input)),
+    }
+}
+
+fn parse_address(input: &str) -> Result<Option<(Address, &str)>, String> {
+    if let Some(rest) = input.strip_prefix('.') {
+        let offset_len = match rest.strip_prefix(['+', '-']) {
+            Some(digits) => 1 + digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len()),
+            None => 0,
+        };
+        let (offset, rest) = rest.split_at(offset_len);
+        let offset = match offset {
+            "" => 0,
+            "+" => 1,
+            "-" => -1,
+            offset => offset
+                .parse()
+                .map_err(|_| format!("Invalid day offset: {}", offset))?,
+        };
+        return Ok(Some((Address::Selected(offset), rest)));
+    }
+    if let Some(rest) = input.strip_prefix('\'') {
+        let mut chars = rest.chars();
+        return match chars.next() {
+            Some(name) => Ok(Some((Address::Mark(name), chars.as_str()))),
+            None => Err(String::from("Missing the mark name after '")),
+        };
+    }
+    let len = input
+        .find(|c: char| !(c.is_ascii_digit() || c == '-' || c == '/'))
+        .unwrap_or(input.len());
+    if len == 0 {
+        return Ok(None);
+    }
+    let (text, rest) = input.split_at(len);
+    let address = if text.contains('-') {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").map(Address::Date)
+    } else if text.contains('/') {
+        NaiveDate::parse_from_str(text, "%m/%d/%Y").map(Address::Date)
+    } else {
+        match text.parse::<i32>() {
+            Ok(day @ 1..=31) => Ok(Address::Day(day as u32)),
+            Ok(year @ 1900..=2050) => Ok(Address::Year(year)),
+            _ => return Err(format!("Invalid date: {}", text)),
+        }
+    };
+    address
+        .map(|address| Some((address, rest)))
+        .map_err(|_| format!("Invalid date: {}", text))
+}
+
+/// Candidates offered by Tab for the word being typed, cycled through in place
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    /// The command line before the completed word
+    pub prefix: String,
+    pub candidates: Vec<String>,
+    pub index: usize,
+}
+
+impl Completion {
+    pub fn current(&self) -> String {
+        format!("{}{}", self.prefix, self.candidates[self.index])
+    }
+
+    pub fn cycle(&mut self, forward: bool) {
+        let len = self.candidates.len();
+        self.index = if forward {
+            (self.index + 1) % len
+        } else {
+            (self.index + len - 1) % len
+        };
+    }
+}
+
+/// What is being typed at the end of `input`: the start of the word, its
+/// text, and the command name when it is an argument
+pub fn word_at_end(input: &str) -> (usize, &str, Option<String>) {
+    let start = input
+        .rfind(char::is_whitespace)
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let word = &input[start..];
+    let first = input[..start].trim();
+    if first.is_empty() {
+        // The command name after any range, or an address of the range
+        let name_start = word
+            .rfind(|c: char| !c.is_ascii_alphabetic())
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let name_start = match word[..name_start].ends_with('\'') {
+            // The letter after ' names a mark
+            true => (name_start + 1).min(word.len()),
+            false => name_start,
+        };
+        if name_start == word.len() && !word.is_empty() {
+            let address_start = word.rfind([',', '%']).map(|i| i + 1).unwrap_or(0);
+            return (start + address_start, &word[address_start..], None);
+        }
+        return (start + name_start, &word[name_start..], None);
+    }
+    let command = parse(first).ok().map(|line| line.name);
+    (start, word, command)
+}
+
+/// The days a range covers, both included
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DateRange {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl DateRange {
+    /// The range between two days given in either order
+    pub fn new(a: NaiveDate, b: NaiveDate) -> Self {
+        Self {
+            start: a.min(b),
+            end: a.max(b),
+        }
+    }
+
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        (self.start..=self.end).contains(&date)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn span(start: Address, end: Option<Address>) -> Option<RangeSpec> {
+        Some(RangeSpec::Span(start, end))
+    }
+
+    #[test]
+    fn parses_name_bang_and_args() {
+        let line = parse(":done!").unwrap();
+        assert_eq!((line.range, line.name.as_str(), line.bang, line.args.as_str()), (None, "done", true, ""));
+        let line = parse("tag  work urgent ").unwrap();
+        assert_eq!((line.name.as_str(), line.args.as_str()), ("tag", "work urgent"));
+        assert_eq!(parse("s/a/b/g").unwrap().args, "/a/b/g");
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(parse("%d").unwrap().range, Some(RangeSpec::Month));
+        assert_eq!(parse(".,.+6y").unwrap().range, span(Address::Selected(0), Some(Address::Selected(6))));
+        assert_eq!(parse(".-d").unwrap().range, span(Address::Selected(-1), None));
+        assert_eq!(parse("15,20done").unwrap().range, span(Address::Day(15), Some(Address::Day(20))));
+        assert_eq!(parse("2025").unwrap().range, span(Address::Year(2025), None));
+        assert_eq!(
+            parse("2025-06-15,06/20/2025move +1d").unwrap().range,
+            span(Address::Date(date(2025, 6, 15)), Some(Address::Date(date(2025, 6, 20))))
+        );
+        assert_eq!(parse("'a,'bdelete").unwrap().range, span(Address::Mark('a'), Some(Address::Mark('b'))));
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        assert!(parse("2025-13-01").is_err());
+        assert!(parse("40").is_err());
+        assert!(parse("15,").is_err());
+        assert!(parse("'").is_err());
+        assert!(parse("+3d").is_err());
+        assert!(parse("ü").is_err());
+    }
+
+    #[test]
+    fn huge_offsets_are_errors_not_panics() {
+        assert!(parse(".+99999999999999999999999").is_err());
+        // Fits an i64; the app rejects it when turning it into a date
+        assert_eq!(
+            parse(".+999999999999999").unwrap().range,
+            span(Address::Selected(999_999_999_999_999), None)
+        );
+    }
+
+    #[test]
+    fn completes_the_word_being_typed() {
+        assert_eq!(word_at_end("del"), (0, "del", None));
+        assert_eq!(word_at_end("%del"), (1, "del", None));
+        assert_eq!(word_at_end("show Wo"), (5, "Wo", Some(String::from("show"))));
+    }
+
+    #[test]
+    fn ranges_include_both_ends_in_either_order() {
+        let range = DateRange::new(date(2025, 6, 20), date(2025, 6, 15));
+        assert_eq!(range.start, date(2025, 6, 15));
+        assert!(range.contains(date(2025, 6, 20)));
+        assert!(!range.contains(date(2025, 6, 21)));
+    }
+}
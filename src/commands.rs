@@ -1,100 +1,535 @@
-use std::collections::HashMap;
+use crate::cmdline::DateRange;
+use crate::App;
+
+/// What a command was invoked with
+pub struct Args<'a> {
+    /// The days of the range typed before the name, if any
+    pub range: Option<DateRange>,
+    /// Whether the name was followed by `!`
+    pub bang: bool,
+    pub text: &'a str,
+}
+
+/// What Tab completes in a command's arguments, besides dates and #tags
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Complete {
+    Nothing,
+    Commands,
+    Options,
+    Lists,
+}
 
 pub struct CommandInfo {
+    pub name: &'static str,
     pub description: &'static str,
-    pub exec: fn(&mut crate::App, &str) -> Result<(), String>,
+    /// Whether the command takes a date range, e.g. :2025-06-01,2025-06-07delete
+    pub range: bool,
+    pub complete: Complete,
+    pub exec: fn(&mut App, &Args) -> Result<(), String>,
 }
 
-pub fn get_command_registry() -> HashMap<&'static str, CommandInfo> {
-    let mut map = HashMap::new();
-    // Quit commands
-    map.insert("q", CommandInfo {
-        description: "Quit the application.",
-        exec: |app, _| { app.should_exit = true; Ok(()) },
-    });
-    map.insert("quit", CommandInfo {
-        description: "Quit the application.",
-        exec: |app, _| { app.should_exit = true; Ok(()) },
-    });
-    map.insert("wq", CommandInfo {
-        description: "Quit the application.",
-        exec: |app, _| { app.should_exit = true; Ok(()) },
-    });
-    map.insert("x", CommandInfo {
-        description: "Quit the application.",
-        exec: |app, _| { app.should_exit = true; Ok(()) },
-    });
-    // Help command is handled specially in main.rs
-    map.insert("seekeys", CommandInfo {
-        description: "Show keybindings bar.",
-        exec: |app, _| { app.show_keybinds = true; Ok(()) },
-    });
-    map.insert("set seekeys", CommandInfo {
-        description: "Show keybindings bar.",
-        exec: |app, _| { app.show_keybinds = true; Ok(()) },
-    });
-    map.insert("nokeys", CommandInfo {
-        description: "Hide keybindings bar.",
-        exec: |app, _| { app.show_keybinds = false; Ok(()) },
-    });
-    map.insert("set nokeys", CommandInfo {
-        description: "Hide keybindings bar.",
-        exec: |app, _| { app.show_keybinds = false; Ok(()) },
-    });
-    map.insert("wrap", CommandInfo {
-        description: "Enable UI text wrapping.",
-        exec: |app, _| { app.month_view.set_wrap(true); Ok(()) },
-    });
-    map.insert("set wrap", CommandInfo {
-        description: "Enable UI text wrapping.",
-        exec: |app, _| { app.month_view.set_wrap(true); Ok(()) },
-    });
-    map.insert("nowrap", CommandInfo {
-        description: "Disable UI text wrapping.",
-        exec: |app, _| { app.month_view.set_wrap(false); Ok(()) },
-    });
-    map.insert("set nowrap", CommandInfo {
-        description: "Disable UI text wrapping.",
-        exec: |app, _| { app.month_view.set_wrap(false); Ok(()) },
-    });
-    // Date navigation commands are handled in main.rs
-    map.insert(
-        "YYYY",
-        CommandInfo {
-            description: "Jump to a specific year (e.g., :2025).",
-            exec: |_, _| Ok(()), // Handled in main.rs parse_date_command
-        },
-    );
-    map.insert(
-        "MM/DD/YYYY",
-        CommandInfo {
-            description: "Jump to a specific date (e.g., :06/15/2025).",
-            exec: |_, _| Ok(()), // Handled in main.rs parse_date_command
-        },
-    );
-    map.insert(
-        "YYYY-MM-DD",
-        CommandInfo {
-            description: "Jump to a specific date (e.g., :2025-06-15).",
-            exec: |_, _| Ok(()), // Handled in main.rs parse_date_command
-        },
-    );
-    map.insert(
-        "DD",
-        CommandInfo {
-            description: "Jump to a specific day in the current month (e.g., :15).",
-            exec: |_, _| Ok(()), // Handled in main.rs parse_date_command
-        },
-    );
-     map.insert(
-        "today",
-        CommandInfo {
-            description: "Jump today",
+/// The commands in the order abbreviations are matched: `:d` is the first
+/// command starting with "d"
+pub fn get_command_registry() -> Vec<CommandInfo> {
+    vec![
+        CommandInfo {
+            name: "quit",
+            description: "Quit the application.",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, _| { app.should_exit = true; Ok(()) },
+        },
+        CommandInfo {
+            name: "help",
+            description: "Toggle the help panel, or describe a command or option: :help <name>.",
+            range: false,
+            complete: Complete::Commands,
+            exec: |_, args| Err(help_text(args.text)),
+        },
+        CommandInfo {
+            name: "substitute",
+            description: "Replace a pattern in the titles of the selected task or a range: :s/pattern/replacement/[g][i].",
+            range: true,
+            complete: Complete::Nothing,
+            exec: |app, args| app.substitute_command(args.range, args.text),
+        },
+        CommandInfo {
+            name: "set",
+            description: "Change options: :set wrap, :set nowrap, :set wrap!, :set undolevels=500, :set wrap?.",
+            range: false,
+            complete: Complete::Options,
+            exec: |app, args| set_options(app, args.text),
+        },
+        CommandInfo {
+            name: "add",
+            description: "Add a task written inline: :add Call dentist tomorrow 3pm #health !high.",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, args| app.add_command(args.text),
+        },
+        CommandInfo {
+            name: "edit",
+            description: "Edit the selected task, or change it inline: :edit friday #work !low.",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, args| app.edit_command(args.text),
+        },
+        CommandInfo {
+            name: "delete",
+            description: "Cut the tasks of a range, or the selected task, into a register: :[range]delete [x].",
+            range: true,
+            complete: Complete::Nothing,
+            exec: |app, args| app.delete_command(args.range, register_arg(args.text)?),
+        },
+        CommandInfo {
+            name: "yank",
+            description: "Yank the tasks of a range, or the selected task, into a register: :[range]yank [x].",
+            range: true,
+            complete: Complete::Nothing,
+            exec: |app, args| app.yank_command(args.range, register_arg(args.text)?),
+        },
+        CommandInfo {
+            name: "done",
+            description: "Mark the selected task or the tasks of a range done, or not done with :done!.",
+            range: true,
+            complete: Complete::Nothing,
+            exec: |app, args| app.done_command(args.range, !args.bang),
+        },
+        CommandInfo {
+            name: "registers",
+            description: "Show the contents of the yank and delete registers.",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, _| Err(app.registers.describe()),
+        },
+        CommandInfo {
+            name: "rename",
+            description: "Change the title of the selected task: :rename <title>.",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, args| app.rename_command(args.text),
+        },
+        CommandInfo {
+            name: "rollover",
+            description: "Move the incomplete tasks of past days to the end of today, tagged rolled:N.",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, _| app.rollover_command(),
+        },
+        CommandInfo {
+            name: "paste",
+            description: "Add each line of the system clipboard as a task below the selection.",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, _| app.paste_clipboard(),
+        },
+        CommandInfo {
+            name: "passwd",
+            description: "Set or change the passphrase encrypting the data file.",
+            range: false,
+            complete: Complete::Nothing,
             exec: |app, _| {
+                app.ensure_writable()?;
+                app.change_passphrase = true;
+                Ok(())
+            },
+        },
+        CommandInfo {
+            name: "move",
+            description: "Move the selected task or the tasks of a range to a date, e.g. :move +1d or :move 2025-07-01.",
+            range: true,
+            complete: Complete::Nothing,
+            exec: |app, args| app.move_command(args.range, args.text),
+        },
+        CommandInfo {
+            name: "marks",
+            description: "Show the marks set with m{a-z}.",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, _| Err(app.describe_marks()),
+        },
+        CommandInfo {
+            name: "delmarks",
+            description: "Delete marks: :delmarks <names>, or all of them with :delmarks!.",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, args| app.delete_marks(args.text, args.bang),
+        },
+        CommandInfo {
+            name: "lists",
+            description: "Show the task lists and whether they are shown.",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, _| Err(app.describe_lists()),
+        },
+        CommandInfo {
+            name: "show",
+            description: "Show a task list in the calendar: :show <list>.",
+            range: false,
+            complete: Complete::Lists,
+            exec: |app, args| app.set_list_visible(args.text, true),
+        },
+        CommandInfo {
+            name: "hide",
+            description: "Hide a task list from the calendar: :hide <list>.",
+            range: false,
+            complete: Complete::Lists,
+            exec: |app, args| app.set_list_visible(args.text, false),
+        },
+        CommandInfo {
+            name: "filter",
+            description: "Open the smart lists sidebar, on a list or saved filter by name, or on a filter: :filter todo #work.",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, args| app.filter_command(args.text),
+        },
+        CommandInfo {
+            name: "earlier",
+            description: "Go back in the undo history: :earlier 3, :earlier 10m (s/m/h/d).",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, args| {
+                let distance = crate::undo::UndoDistance::parse(args.text)?;
+                app.time_travel(distance, false)
+            },
+        },
+        CommandInfo {
+            name: "later",
+            description: "Go forward in the undo history: :later 3, :later 5m (s/m/h/d).",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, args| {
+                let distance = crate::undo::UndoDistance::parse(args.text)?;
+                app.time_travel(distance, true)
+            },
+        },
+        CommandInfo {
+            name: "undotree",
+            description: "Toggle the undo tree panel.",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, _| { app.show_undo_tree = !app.show_undo_tree; Ok(()) },
+        },
+        CommandInfo {
+            name: "sync",
+            description: "Pull and push the git history, merging tasks by id.",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, _| app.sync(),
+        },
+        CommandInfo {
+            name: "global",
+            description: "Run done, delete, move, tag or s on the tasks matching a pattern: :g/pattern/command (:g! for the others).",
+            range: true,
+            complete: Complete::Nothing,
+            exec: |app, args| app.global_command(args.range, args.bang, args.text),
+        },
+        CommandInfo {
+            name: "today",
+            description: "Jump to today.",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, _| {
+                app.remember_jump();
                 app.month_view.go_to_today();
                 Ok(())
             },
         },
-    );
-    map
+        CommandInfo {
+            name: "tag",
+            description: "Add tags to the selected task or the tasks of a range, or remove them with :tag!.",
+            range: true,
+            complete: Complete::Nothing,
+            exec: |app, args| app.tag_command(args.range, args.text, args.bang),
+        },
+        CommandInfo {
+            name: "wrap",
+            description: "Enable UI text wrapping (:set wrap).",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, _| { app.month_view.set_wrap(true); Ok(()) },
+        },
+        CommandInfo {
+            name: "nowrap",
+            description: "Disable UI text wrapping (:set nowrap).",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, _| { app.month_view.set_wrap(false); Ok(()) },
+        },
+        CommandInfo {
+            name: "seekeys",
+            description: "Show keybindings bar (:set keys).",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, _| { app.show_keybinds = true; Ok(()) },
+        },
+        CommandInfo {
+            name: "nokeys",
+            description: "Hide keybindings bar (:set nokeys).",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, _| { app.show_keybinds = false; Ok(()) },
+        },
+        CommandInfo {
+            name: "wq",
+            description: "Quit the application.",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, _| { app.should_exit = true; Ok(()) },
+        },
+        CommandInfo {
+            name: "xit",
+            description: "Quit the application.",
+            range: false,
+            complete: Complete::Nothing,
+            exec: |app, _| { app.should_exit = true; Ok(()) },
+        },
+    ]
+}
+
+/// The command `name` stands for: the command of that name, or else the
+/// first one it abbreviates
+pub fn find_command<'a>(registry: &'a [CommandInfo], name: &str) -> Result<&'a CommandInfo, String> {
+    registry
+        .iter()
+        .find(|c| c.name == name)
+        .or_else(|| registry.iter().find(|c| !name.is_empty() && c.name.starts_with(name)))
+        .ok_or_else(|| format!("Unknown command: {}. Type ':help' for available commands.", name))
+}
+
+fn help_text(topic: &str) -> String {
+    let topic = topic.trim_start_matches(':');
+    if topic.is_empty() {
+        let mut text = String::from("Available commands:\n");
+        for info in get_command_registry() {
+            text.push_str(&format!(":{:<15} - {}\n", info.name, info.description));
+        }
+        text.push_str("Ranges: 15, 2025, 2025-06-15, 06/15/2025, . (selected day), .+7, 'a (mark), % (shown month), a,b\n");
+        return text;
+    }
+    if let Ok(info) = find_command(&get_command_registry(), topic) {
+        return format!(":{} - {}", info.name, info.description);
+    }
+    match find_option(&get_option_registry(), topic) {
+        Ok(option) => format!("'{}' - {}", option.name, option.description),
+        Err(_) => format!("No help found for :{}", topic),
+    }
+}
+
+/// The register named by a command's argument, e.g. the `a` of `:delete a`
+pub fn register_arg(text: &str) -> Result<Option<char>, String> {
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (None, _) => Ok(None),
+        (Some(name), None) if crate::registers::Registers::is_valid(name) => Ok(Some(name)),
+        _ => Err(format!("Invalid register: {}", text)),
+    }
+}
+
+/// How an option is read and changed
+pub enum OptionKind {
+    Bool {
+        get: fn(&App) -> bool,
+        set: fn(&mut App, bool),
+    },
+    Number {
+        get: fn(&App) -> usize,
+        set: fn(&mut App, usize) -> Result<(), String>,
+    },
+    Text {
+        get: fn(&App) -> String,
+        set: fn(&mut App, &str) -> Result<(), String>,
+    },
+}
+
+pub struct OptionInfo {
+    pub name: &'static str,
+    /// Other names accepted for the option
+    pub aliases: &'static [&'static str],
+    pub description: &'static str,
+    pub kind: OptionKind,
+}
+
+impl OptionInfo {
+    /// The option as `:set` shows it: `wrap`, `nowrap` or `name=value`
+    fn show(&self, app: &App) -> String {
+        match self.kind {
+            OptionKind::Bool { get, .. } if get(app) => self.name.to_string(),
+            OptionKind::Bool { .. } => format!("no{}", self.name),
+            OptionKind::Number { get, .. } => format!("{}={}", self.name, get(app)),
+            OptionKind::Text { get, .. } => format!("{}={}", self.name, get(app)),
+        }
+    }
+
+    fn assign(&self, app: &mut App, value: &str) -> Result<(), String> {
+        match self.kind {
+            OptionKind::Bool { set, .. } => {
+                let value = match value.to_lowercase().as_str() {
+                    "true" | "yes" | "on" | "1" => true,
+                    "false" | "no" | "off" | "0" => false,
+                    _ => return Err(format!("Invalid value for {}: {}", self.name, value)),
+                };
+                set(app, value);
+                Ok(())
+            }
+            OptionKind::Number { set, .. } => {
+                let value = value
+                    .parse()
+                    .map_err(|_| format!("Invalid number for {}: {}", self.name, value))?;
+                set(app, value)
+            }
+            OptionKind::Text { set, .. } => set(app, value),
+        }
+    }
+}
+
+pub fn get_option_registry() -> Vec<OptionInfo> {
+    vec![
+        OptionInfo {
+            name: "wrap",
+            aliases: &[],
+            description: "Wrap task titles in the calendar.",
+            kind: OptionKind::Bool {
+                get: |app| app.month_view.wrap_enabled,
+                set: |app, value| app.month_view.set_wrap(value),
+            },
+        },
+        OptionInfo {
+            name: "keys",
+            aliases: &["seekeys"],
+            description: "Show the keybindings bar.",
+            kind: OptionKind::Bool {
+                get: |app| app.show_keybinds,
+                set: |app, value| app.show_keybinds = value,
+            },
+        },
+        OptionInfo {
+            name: "scramble",
+            aliases: &[],
+            description: "Hide task titles behind numbers.",
+            kind: OptionKind::Bool {
+                get: |app| app.scramble_mode,
+                set: |app, value| app.scramble_mode = value,
+            },
+        },
+        OptionInfo {
+            name: "undotree",
+            aliases: &[],
+            description: "Show the undo tree panel.",
+            kind: OptionKind::Bool {
+                get: |app| app.show_undo_tree,
+                set: |app, value| app.show_undo_tree = value,
+            },
+        },
+        OptionInfo {
+            name: "undolevels",
+            aliases: &["ul"],
+            description: "How many changes the undo history keeps.",
+            kind: OptionKind::Number {
+                get: |app| app.config.undo_history.levels,
+                set: |app, value| {
+                    if value == 0 {
+                        return Err(String::from("undolevels must be at least 1"));
+                    }
+                    app.config.undo_history.levels = value;
+                    app.undo_tree.set_max_size(value);
+                    Ok(())
+                },
+            },
+        },
+        OptionInfo {
+            name: "osc52",
+            aliases: &[],
+            description: "Copy yanked tasks to the system clipboard with the OSC 52 escape.",
+            kind: OptionKind::Bool {
+                get: |app| app.config.clipboard.osc52,
+                set: |app, value| app.config.clipboard.osc52 = value,
+            },
+        },
+        OptionInfo {
+            name: "clipformat",
+            aliases: &[],
+            description: "How yanked tasks are copied to the clipboard: plain or markdown.",
+            kind: OptionKind::Text {
+                get: |app| app.config.clipboard.format.name().to_string(),
+                set: |app, value| {
+                    app.config.clipboard.format = crate::clipboard::ClipboardFormat::from_name(value)
+                        .ok_or_else(|| format!("Unknown clipboard format: {} (plain or markdown)", value))?;
+                    Ok(())
+                },
+            },
+        },
+        OptionInfo {
+            name: "pastecommand",
+            aliases: &[],
+            description: "Shell command :paste reads the clipboard with; empty to detect one.",
+            kind: OptionKind::Text {
+                get: |app| app.config.clipboard.paste_command.clone().unwrap_or_default(),
+                set: |app, value| {
+                    app.config.clipboard.paste_command =
+                        Some(value.to_string()).filter(|command| !command.is_empty());
+                    Ok(())
+                },
+            },
+        },
+    ]
+}
+
+/// Names Tab offers after :set, including the `no` forms of toggles
+pub fn option_names() -> Vec<String> {
+    let options = get_option_registry();
+    let names = options.iter().map(|o| o.name.to_string());
+    let negated = options
+        .iter()
+        .filter(|o| matches!(o.kind, OptionKind::Bool { .. }))
+        .map(|o| format!("no{}", o.name));
+    names.chain(negated).collect()
+}
+
+fn lookup<'a>(options: &'a [OptionInfo], name: &str) -> Option<&'a OptionInfo> {
+    options
+        .iter()
+        .find(|o| o.name == name || o.aliases.contains(&name))
+}
+
+fn find_option<'a>(options: &'a [OptionInfo], name: &str) -> Result<&'a OptionInfo, String> {
+    lookup(options, name).ok_or_else(|| format!("Unknown option: {}", name))
+}
+
+/// `:set opt`, `noopt`, `invopt` or `opt!`, `opt=value` and `opt?`, any number
+/// at once; without arguments it shows every option
+fn set_options(app: &mut App, text: &str) -> Result<(), String> {
+    let options = get_option_registry();
+    if text.is_empty() || text == "all" {
+        let values: Vec<String> = options.iter().map(|o| o.show(app)).collect();
+        return Err(values.join("  "));
+    }
+    let mut shown = Vec::new();
+    for word in text.split_whitespace() {
+        if let Some(name) = word.strip_suffix('?') {
+            shown.push(find_option(&options, name)?.show(app));
+        } else if let Some((name, value)) = word.split_once(['=', ':']) {
+            find_option(&options, name)?.assign(app, value)?;
+        } else if let Some(option) = lookup(&options, word) {
+            match option.kind {
+                OptionKind::Bool { set, .. } => set(app, true),
+                _ => shown.push(option.show(app)),
+            }
+        } else {
+            let (name, toggle) = match (word.strip_suffix('!'), word.strip_prefix("inv")) {
+                (Some(name), _) | (None, Some(name)) => (name, true),
+                (None, None) => (word.strip_prefix("no").unwrap_or(word), false),
+            };
+            let option = find_option(&options, name)?;
+            let OptionKind::Bool { get, set } = option.kind else {
+                return Err(format!("Not a toggle option: {}", option.name));
+            };
+            set(app, toggle && !get(app));
+        }
+    }
+    if shown.is_empty() {
+        Ok(())
+    } else {
+        Err(shown.join("  "))
+    }
 }
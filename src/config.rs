@@ -1,18 +1,33 @@
 // Taskim Configuration
 // Edit this file to customize your keybindings
 
+use crate::clipboard::ClipboardFormat;
+use crate::data::StorageKind;
+use crate::git::GitSettings;
+use crate::lock::LockMode;
+use crate::task::Task;
+use color_eyre::eyre::{eyre, Result};
 use crossterm::event::{KeyCode, KeyModifiers};
 use ratatui::style::Color;
 use serde::Deserialize;
 use serde_yaml::Value;
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 // --- YAML config file struct ---
 #[derive(Debug, Clone, Deserialize)]
 pub struct ConfigFile {
     pub show_keybinds: Option<bool>,
+    pub storage: Option<String>,
+    pub lock_mode: Option<String>,
+    pub encrypt: Option<bool>,
+    pub rollover_on_startup: Option<bool>,
+    pub git: Option<HashMap<String, String>>,
+    pub lists: Option<Vec<HashMap<String, String>>>,
+    pub filters: Option<Vec<HashMap<String, String>>>,
+    pub undo: Option<HashMap<String, String>>,
+    pub clipboard: Option<HashMap<String, String>>,
     pub colors: Option<HashMap<String, String>>,
     pub task_edit_colors: Option<HashMap<String, String>>,
     pub keybindings: Option<HashMap<String, serde_yaml::Value>>,
@@ -63,6 +78,42 @@ pub struct TaskEditColors {
     pub instructions_key_fg: Color,
 }
 
+/// Settings from the `undo:` section
+#[derive(Debug, Clone)]
+pub struct UndoSettings {
+    /// Keep the undo history in a journal next to the data file
+    pub persist: bool,
+    pub levels: usize,
+    pub max_age_days: i64,
+}
+
+/// Settings from the `clipboard:` section
+#[derive(Debug, Clone)]
+pub struct ClipboardSettings {
+    /// Also copy yanked tasks to the system clipboard with the OSC 52 escape
+    pub osc52: bool,
+    pub format: ClipboardFormat,
+    /// Shell command printing the clipboard for `:paste`, detected when unset
+    pub paste_command: Option<String>,
+}
+
+/// A named task list from the `lists:` section
+#[derive(Debug, Clone)]
+pub struct TaskList {
+    pub name: String,
+    pub color: Option<Color>,
+    /// JSON file holding this list's tasks instead of the main data file
+    pub file: Option<PathBuf>,
+    pub visible: bool,
+}
+
+/// A named filter from the `filters:` section, shown with the smart lists
+#[derive(Debug, Clone)]
+pub struct SavedFilter {
+    pub name: String,
+    pub expression: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct Config {
     // Navigation
@@ -70,6 +121,14 @@ pub struct Config {
     pub move_down: KeyBinding,
     pub move_up: KeyBinding,
     pub move_right: KeyBinding,
+    // Reordering and rescheduling the selected task
+    pub reorder_down: KeyBinding,
+    pub reorder_up: KeyBinding,
+    pub snooze: KeyBinding,
+    pub reschedule_left: KeyBinding,
+    pub reschedule_down: KeyBinding,
+    pub reschedule_up: KeyBinding,
+    pub reschedule_right: KeyBinding,
     // Task operations
     pub insert_edit: KeyBinding,
     pub insert_above: KeyBinding,
@@ -80,6 +139,7 @@ pub struct Config {
     pub yank: KeyBinding,
     pub paste: KeyBinding,
     pub paste_above: KeyBinding,
+    pub repeat: KeyBinding,
     // Undo/Redo
     pub undo: KeyBinding,
     pub redo: KeyBinding,
@@ -96,6 +156,10 @@ pub struct Config {
     pub last_day_of_month: KeyBinding,
     // Go to today
     pub go_to_today: KeyBinding,
+    // Smart lists and saved filters sidebar
+    pub smart_lists: KeyBinding,
+    // Record a macro into a register, or stop recording
+    pub record_macro: KeyBinding,
     // Task editing
     pub save_task: KeyBinding,
     pub cancel_edit: KeyBinding,
@@ -107,17 +171,125 @@ pub struct Config {
     pub force_quit: KeyBinding,
     // New config fields
     pub show_keybinds: bool,
+    pub storage: StorageKind,
+    pub lock_mode: LockMode,
+    pub encrypt: bool,
+    /// Run `:rollover` when taskim starts
+    pub rollover_on_startup: bool,
+    pub git: GitSettings,
+    pub lists: Vec<TaskList>,
+    pub filters: Vec<SavedFilter>,
+    pub undo_history: UndoSettings,
+    pub clipboard: ClipboardSettings,
     pub ui_colors: UiColors,
     pub task_edit_colors: TaskEditColors,
 }
 
 impl Config {
-    pub fn from_file_or_default<P: AsRef<Path>>(path: P) -> Self {
+    /// Read config.yml, falling back to defaults for what it doesn't set. A
+    /// `storage:` taskim doesn't know is an error rather than a silent switch
+    /// to another data file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
         let file = ConfigFile::load_from_yaml(&path);
-        Self::from_config_file(file)
+        if let Some(name) = file.as_ref().and_then(|f| f.storage.as_deref()) {
+            if StorageKind::from_name(name).is_none() {
+                return Err(eyre!(
+                    "Unknown storage `{}` in {} (expected json or sqlite)",
+                    name,
+                    path.as_ref().display()
+                ));
+            }
+        }
+        Ok(Self::from_config_file(file))
     }
     pub fn from_config_file(file: Option<ConfigFile>) -> Self {
         let show_keybinds = file.as_ref().and_then(|f| f.show_keybinds).unwrap_or(true);
+        let storage = file
+            .as_ref()
+            .and_then(|f| f.storage.as_deref())
+            .and_then(StorageKind::from_name)
+            .unwrap_or_default();
+        let lock_mode = file
+            .as_ref()
+            .and_then(|f| f.lock_mode.as_deref())
+            .and_then(LockMode::from_name)
+            .unwrap_or_default();
+        let encrypt = file.as_ref().and_then(|f| f.encrypt).unwrap_or(false);
+        let rollover_on_startup = file
+            .as_ref()
+            .and_then(|f| f.rollover_on_startup)
+            .unwrap_or(false);
+        let git_map = file.as_ref().and_then(|f| f.git.as_ref()).cloned();
+        let git = GitSettings {
+            enabled: parse_bool(&&git_map, "enabled", false),
+            remote: git_map
+                .as_ref()
+                .and_then(|m| m.get("remote"))
+                .filter(|r| !r.is_empty())
+                .cloned(),
+            branch: git_map
+                .as_ref()
+                .and_then(|m| m.get("branch"))
+                .cloned()
+                .unwrap_or_else(|| String::from("main")),
+        };
+        let lists = file
+            .as_ref()
+            .and_then(|f| f.lists.as_ref())
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter_map(|entry| {
+                        let name = entry.get("name").filter(|n| !n.is_empty())?.clone();
+                        let map = Some(entry.clone());
+                        Some(TaskList {
+                            name,
+                            color: entry.get("color").map(|c| parse_color_name(c)),
+                            file: entry
+                                .get("file")
+                                .filter(|f| !f.is_empty())
+                                .map(PathBuf::from),
+                            visible: parse_bool(&&map, "visible", true),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let filters = file
+            .as_ref()
+            .and_then(|f| f.filters.as_ref())
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter_map(|entry| {
+                        Some(SavedFilter {
+                            name: entry.get("name").filter(|n| !n.is_empty())?.clone(),
+                            expression: entry.get("filter")?.clone(),
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let undo_map = file.as_ref().and_then(|f| f.undo.as_ref()).cloned();
+        let undo_history = UndoSettings {
+            persist: parse_bool(&&undo_map, "persist", true),
+            levels: parse_number(&undo_map, "levels", 200),
+            max_age_days: parse_number(&undo_map, "max_age_days", 30),
+        };
+        let clipboard_map = file.as_ref().and_then(|f| f.clipboard.as_ref()).cloned();
+        let clipboard = ClipboardSettings {
+            osc52: parse_bool(&&clipboard_map, "osc52", false),
+            format: clipboard_map
+                .as_ref()
+                .and_then(|m| m.get("format"))
+                .and_then(|f| ClipboardFormat::from_name(f))
+                .unwrap_or_default(),
+            paste_command: clipboard_map
+                .as_ref()
+                .and_then(|m| m.get("paste_command"))
+                .filter(|c| !c.is_empty())
+                .cloned(),
+        };
         let colors = file.as_ref().and_then(|f| f.colors.as_ref()).cloned();
         let task_edit_colors_map = file
             .as_ref()
@@ -180,6 +352,13 @@ impl Config {
             move_down: keybindings_map["move_down"].clone(),
             move_up: keybindings_map["move_up"].clone(),
             move_right: keybindings_map["move_right"].clone(),
+            reorder_down: keybindings_map["reorder_down"].clone(),
+            reorder_up: keybindings_map["reorder_up"].clone(),
+            snooze: keybindings_map["snooze"].clone(),
+            reschedule_left: keybindings_map["reschedule_left"].clone(),
+            reschedule_down: keybindings_map["reschedule_down"].clone(),
+            reschedule_up: keybindings_map["reschedule_up"].clone(),
+            reschedule_right: keybindings_map["reschedule_right"].clone(),
             insert_edit: keybindings_map["insert_edit"].clone(),
             insert_above: keybindings_map["insert_above"].clone(),
             insert_below: keybindings_map["insert_below"].clone(),
@@ -189,6 +368,7 @@ impl Config {
             yank: keybindings_map["yank"].clone(),
             paste: keybindings_map["paste"].clone(),
             paste_above: keybindings_map["paste_above"].clone(),
+            repeat: keybindings_map["repeat"].clone(),
             undo: keybindings_map["undo"].clone(),
             redo: keybindings_map["redo"].clone(),
             next_month: keybindings_map["next_month"].clone(),
@@ -200,6 +380,8 @@ impl Config {
             first_day_of_month: keybindings_map["first_day_of_month"].clone(),
             last_day_of_month: keybindings_map["last_day_of_month"].clone(),
             go_to_today: keybindings_map["go_to_today"].clone(),
+            smart_lists: keybindings_map["smart_lists"].clone(),
+            record_macro: keybindings_map["record_macro"].clone(),
             save_task: keybindings_map["save_task"].clone(),
             cancel_edit: keybindings_map["cancel_edit"].clone(),
             switch_field: keybindings_map["switch_field"].clone(),
@@ -208,6 +390,15 @@ impl Config {
             quit_alt: keybindings_map["quit_alt"].clone(),
             force_quit: keybindings_map["force_quit"].clone(),
             show_keybinds,
+            storage,
+            lock_mode,
+            encrypt,
+            rollover_on_startup,
+            git,
+            lists,
+            filters,
+            undo_history,
+            clipboard,
             ui_colors,
             task_edit_colors,
         }
@@ -216,6 +407,28 @@ impl Config {
 
 // Helper functions for UI
 impl Config {
+    /// The first configured list, which holds tasks that name no list
+    pub fn default_list_name(&self) -> &str {
+        self.lists.first().map_or("Default", |l| l.name.as_str())
+    }
+
+    /// Name of the list a task belongs to
+    pub fn list_name<'a>(&'a self, task: &'a Task) -> &'a str {
+        task.list
+            .as_deref()
+            .unwrap_or_else(|| self.default_list_name())
+    }
+
+    /// Grid color of a task that is neither selected nor completed
+    pub fn task_fg(&self, task: &Task) -> Color {
+        let name = self.list_name(task);
+        self.lists
+            .iter()
+            .find(|l| l.name == name)
+            .and_then(|l| l.color)
+            .unwrap_or(self.ui_colors.default_task_fg)
+    }
+
     pub fn get_normal_mode_help_spans(
         &self,
         can_undo: bool,
@@ -223,11 +436,11 @@ impl Config {
     ) -> Vec<ratatui::text::Span<'static>> {
         use ratatui::{style::Style, text::Span};
 
-        let mut spans = Vec::new();
-
         // Movement keys (show as combined)
-        spans.push(Span::styled("hjkl", Style::default().fg(Color::Green)));
-        spans.push(Span::raw(": Move | "));
+        let mut spans = vec![
+            Span::styled("hjkl", Style::default().fg(Color::Green)),
+            Span::raw(": Move | "),
+        ];
 
         // Task operations
         spans.push(Span::styled(
@@ -248,6 +461,8 @@ impl Config {
         spans.push(Span::raw(": Yank | "));
         spans.push(Span::styled("p", Style::default().fg(self.paste.color)));
         spans.push(Span::raw(": Paste | "));
+        spans.push(Span::styled(".", Style::default().fg(self.repeat.color)));
+        spans.push(Span::raw(": Repeat | "));
 
         // Undo/Redo (only show if available)
         if can_undo {
@@ -279,6 +494,19 @@ impl Config {
         ));
         spans.push(Span::raw(": Day | "));
 
+        // Reordering and rescheduling
+        spans.push(Span::styled("J/K", Style::default().fg(self.reorder_down.color)));
+        spans.push(Span::raw(": Reorder | "));
+        spans.push(Span::styled("z", Style::default().fg(self.snooze.color)));
+        spans.push(Span::raw(": Snooze | "));
+        spans.push(Span::styled(
+            "A-h/l",
+            Style::default().fg(self.reschedule_right.color),
+        ));
+        spans.push(Span::raw(": Move Task | "));
+        spans.push(Span::styled("F", Style::default().fg(self.smart_lists.color)));
+        spans.push(Span::raw(": Lists | "));
+
         // Quit
         spans.push(Span::styled("q", Style::default().fg(self.quit.color)));
         spans.push(Span::raw(": Quit"));
@@ -292,6 +520,7 @@ impl Config {
         vec![
             Span::styled("Tab", Style::default().fg(self.switch_field.color)),
             Span::raw(": Switch field | "),
+            Span::raw("Left/Right: Change list | "),
             Span::styled("Enter", Style::default().fg(self.save_task.color)),
             Span::raw(": Save | "),
             Span::styled("Esc", Style::default().fg(self.cancel_edit.color)),
@@ -341,6 +570,17 @@ fn parse_bool(map: &&Option<HashMap<String, String>>, key: &str, default: bool)
         .unwrap_or(default)
 }
 
+fn parse_number<T: std::str::FromStr>(
+    map: &Option<HashMap<String, String>>,
+    key: &str,
+    default: T,
+) -> T {
+    map.as_ref()
+        .and_then(|m| m.get(key))
+        .and_then(|s| s.parse::<T>().ok())
+        .unwrap_or(default)
+}
+
 fn parse_keybinding(yaml: Option<&Value>, default: &KeyBinding) -> KeyBinding {
     match yaml {
         Some(Value::String(s)) => {
@@ -352,7 +592,7 @@ fn parse_keybinding(yaml: Option<&Value>, default: &KeyBinding) -> KeyBinding {
         }
         Some(Value::Sequence(seq)) => {
             let key = seq
-                .get(0)
+                .first()
                 .and_then(|v| v.as_str())
                 .map(parse_key_code)
                 .unwrap_or(default.key);
@@ -369,7 +609,7 @@ fn parse_keybinding(yaml: Option<&Value>, default: &KeyBinding) -> KeyBinding {
             let color = seq
                 .get(3)
                 .and_then(|v| v.as_str())
-                .map(|c| parse_color_name(c))
+                .map(parse_color_name)
                 .unwrap_or(default.color);
             KeyBinding {
                 key,
@@ -441,6 +681,69 @@ fn default_keybindings() -> std::collections::HashMap<&'static str, KeyBinding>
             color: Color::Green,
         },
     );
+    map.insert(
+        "reorder_down",
+        KeyBinding {
+            key: KeyCode::Char('J'),
+            modifiers: KeyModifiers::SHIFT,
+            description: String::from("Reorder"),
+            color: Color::Green,
+        },
+    );
+    map.insert(
+        "reorder_up",
+        KeyBinding {
+            key: KeyCode::Char('K'),
+            modifiers: KeyModifiers::SHIFT,
+            description: String::from("Reorder"),
+            color: Color::Green,
+        },
+    );
+    map.insert(
+        "snooze",
+        KeyBinding {
+            key: KeyCode::Char('z'),
+            modifiers: KeyModifiers::NONE,
+            description: String::from("Snooze"),
+            color: Color::Green,
+        },
+    );
+    map.insert(
+        "reschedule_left",
+        KeyBinding {
+            key: KeyCode::Char('h'),
+            modifiers: KeyModifiers::ALT,
+            description: String::from("Move Task"),
+            color: Color::Green,
+        },
+    );
+    map.insert(
+        "reschedule_down",
+        KeyBinding {
+            key: KeyCode::Char('j'),
+            modifiers: KeyModifiers::ALT,
+            description: String::from("Move Task"),
+            color: Color::Green,
+        },
+    );
+    map.insert(
+        "reschedule_up",
+        KeyBinding {
+            key: KeyCode::Char('k'),
+            modifiers: KeyModifiers::ALT,
+            description: String::from("Move Task"),
+            color: Color::Green,
+        },
+    );
+    map.insert(
+        "reschedule_right",
+        KeyBinding {
+            key: KeyCode::Char('l'),
+            modifiers: KeyModifiers::ALT,
+            description: String::from("Move Task"),
+            color: Color::Green,
+        },
+    );
     map.insert(
         "insert_edit",
         KeyBinding {
@@ -522,6 +825,15 @@ fn default_keybindings() -> std::collections::HashMap<&'static str, KeyBinding>
             color: Color::Yellow,
         },
     );
+    map.insert(
+        "repeat",
+        KeyBinding {
+            key: KeyCode::Char('.'),
+            modifiers: KeyModifiers::NONE,
+            description: String::from("Repeat Last Change"),
+            color: Color::Yellow,
+        },
+    );
     map.insert(
         "undo",
         KeyBinding {
@@ -621,6 +933,24 @@ fn default_keybindings() -> std::collections::HashMap<&'static str, KeyBinding>
             color: Color::Magenta,
         },
     );
+    map.insert(
+        "smart_lists",
+        KeyBinding {
+            key: KeyCode::Char('F'),
+            modifiers: KeyModifiers::SHIFT,
+            description: String::from("Lists"),
+            color: Color::Magenta,
+        },
+    );
+    map.insert(
+        "record_macro",
+        KeyBinding {
+            key: KeyCode::Char('q'),
+            modifiers: KeyModifiers::NONE,
+            description: String::from("Record Macro"),
+            color: Color::Magenta,
+        },
+    );
     map.insert(
         "save_task",
         KeyBinding {
@@ -660,8 +990,8 @@ fn default_keybindings() -> std::collections::HashMap<&'static str, KeyBinding>
     map.insert(
         "quit",
         KeyBinding {
-            key: KeyCode::Char('q'),
-            modifiers: KeyModifiers::NONE,
+            key: KeyCode::Char('Q'),
+            modifiers: KeyModifiers::SHIFT,
             description: String::from("Quit"),
             color: Color::Red,
         },
@@ -0,0 +1,201 @@
+use crate::data::{DataStore, StorageKind};
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use color_eyre::eyre::{eyre, Result};
+use std::fs;
+
+/// Header of an encrypted data file, followed by salt, nonce and ciphertext
+const MAGIC: &[u8] = b"TASKIM-ENCRYPTED-1\n";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const PASSPHRASE_ATTEMPTS: usize = 3;
+/// Encrypting only protects the data file from now on
+pub const PLAINTEXT_HISTORY_WARNING: &str =
+    "versions committed before encryption stay readable in the git history (.taskim-history)";
+
+/// Authenticated encryption with a key derived from a passphrase
+#[derive(Clone)]
+pub struct Cipher {
+    salt: [u8; SALT_LEN],
+    key: Key,
+}
+
+impl Cipher {
+    /// Derive a key for a new passphrase with a fresh random salt
+    pub fn new(passphrase: &str) -> Result<Self> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self::with_salt(passphrase, salt)
+    }
+
+    /// Derive the key for an existing encrypted file, verifying the passphrase
+    pub fn for_content(passphrase: &str, content: &[u8]) -> Result<Self> {
+        let (salt, _, _) = split(content)?;
+        let cipher = Self::with_salt(passphrase, salt)?;
+        cipher.decrypt(content)?;
+        Ok(cipher)
+    }
+
+    fn with_salt(passphrase: &str, salt: [u8; SALT_LEN]) -> Result<Self> {
+        let mut key = Key::default();
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| eyre!("Key derivation failed: {}", e))?;
+        Ok(Self { salt, key })
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = ChaCha20Poly1305::new(&self.key)
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| eyre!("Encryption failed"))?;
+
+        let mut content = Vec::with_capacity(MAGIC.len() + SALT_LEN + NONCE_LEN + ciphertext.len());
+        content.extend_from_slice(MAGIC);
+        content.extend_from_slice(&self.salt);
+        content.extend_from_slice(&nonce);
+        content.extend_from_slice(&ciphertext);
+        Ok(content)
+    }
+
+    pub fn decrypt(&self, content: &[u8]) -> Result<Vec<u8>> {
+        let (salt, nonce, ciphertext) = split(content)?;
+        if salt != self.salt {
+            return Err(eyre!(
+                "The data file was encrypted with a different passphrase"
+            ));
+        }
+        ChaCha20Poly1305::new(&self.key)
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| eyre!("Wrong passphrase or corrupted data file"))
+    }
+}
+
+pub fn is_encrypted(content: &[u8]) -> bool {
+    content.starts_with(MAGIC)
+}
+
+/// Encrypt `plaintext` when a cipher is in use
+pub fn seal(cipher: Option<&Cipher>, plaintext: Vec<u8>) -> Result<Vec<u8>> {
+    match cipher {
+        Some(cipher) => cipher.encrypt(&plaintext),
+        None => Ok(plaintext),
+    }
+}
+
+/// Decrypt `content` if it is encrypted; plain content is passed through
+pub fn unseal(cipher: Option<&Cipher>, content: Vec<u8>) -> Result<Vec<u8>> {
+    if !is_encrypted(&content) {
+        return Ok(content);
+    }
+    match cipher {
+        Some(cipher) => cipher.decrypt(&content),
+        None => Err(eyre!("The data is encrypted and no passphrase was given")),
+    }
+}
+
+fn split(content: &[u8]) -> Result<([u8; SALT_LEN], &[u8], &[u8])> {
+    let body = content
+        .strip_prefix(MAGIC)
+        .filter(|body| body.len() > SALT_LEN + NONCE_LEN)
+        .ok_or_else(|| eyre!("Not an encrypted taskim data file"))?;
+    let (salt, rest) = body.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let mut salt_bytes = [0u8; SALT_LEN];
+    salt_bytes.copy_from_slice(salt);
+    Ok((salt_bytes, nonce, ciphertext))
+}
+
+/// Ask for the passphrase of an encrypted data file, or for a new one when
+/// `encrypt` is set and the file is not encrypted yet. Runs before the TUI starts.
+/// Returns whether a plain file was encrypted.
+pub fn unlock(store: &mut DataStore, encrypt: bool) -> Result<bool> {
+    if encrypt && store.kind() != StorageKind::Json {
+        return Err(eyre!("Encryption requires `storage: json` in config.yml"));
+    }
+    let content = fs::read(store.path()).unwrap_or_default();
+
+    if is_encrypted(&content) {
+        for _ in 0..PASSPHRASE_ATTEMPTS {
+            let passphrase =
+                rpassword::prompt_password(format!("Passphrase for {}: ", store.path().display()))?;
+            match Cipher::for_content(&passphrase, &content) {
+                Ok(cipher) => {
+                    store.use_cipher(cipher)?;
+                    return Ok(false);
+                }
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        return Err(eyre!("Could not unlock {}", store.path().display()));
+    }
+
+    if encrypt {
+        eprintln!(
+            "Encryption is enabled; choose a passphrase for {}",
+            store.path().display()
+        );
+        let passphrase = prompt_new_passphrase()?;
+        store.set_cipher(Some(Cipher::new(&passphrase)?))?;
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// Prompt twice for a new, non-empty passphrase. Gives up after a few tries,
+/// since a closed input reads as an empty answer every time.
+pub fn prompt_new_passphrase() -> Result<String> {
+    for _ in 0..PASSPHRASE_ATTEMPTS {
+        let passphrase = rpassword::prompt_password("New passphrase: ")?;
+        if passphrase.is_empty() {
+            eprintln!("The passphrase must not be empty");
+            continue;
+        }
+        let confirmation = rpassword::prompt_password("Repeat passphrase: ")?;
+        if passphrase == confirmation {
+            return Ok(passphrase);
+        }
+        eprintln!("Passphrases do not match, try again");
+    }
+    Err(eyre!("No passphrase chosen"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encrypted_data_reads_back_only_with_its_passphrase() {
+        let cipher = Cipher::new("correct horse").unwrap();
+        let content = seal(Some(&cipher), b"{\"events\": []}".to_vec()).unwrap();
+        assert!(is_encrypted(&content));
+        assert_eq!(unseal(Some(&cipher), content.clone()).unwrap(), b"{\"events\": []}");
+
+        assert!(Cipher::for_content("correct horse", &content).is_ok());
+        assert!(Cipher::for_content("wrong horse", &content).is_err());
+        assert!(unseal(None, content.clone()).is_err());
+        // Another passphrase means another salt
+        let other = Cipher::new("correct horse").unwrap();
+        assert!(other.decrypt(&content).is_err());
+    }
+
+    #[test]
+    fn tampered_or_truncated_files_are_rejected() {
+        let cipher = Cipher::new("pw").unwrap();
+        let mut content = cipher.encrypt(b"tasks").unwrap();
+        let last = content.len() - 1;
+        content[last] ^= 1;
+        assert!(cipher.decrypt(&content).is_err());
+        assert!(cipher.decrypt(&content[..MAGIC.len() + SALT_LEN]).is_err());
+        assert!(cipher.decrypt(b"{\"events\": []}").is_err());
+    }
+
+    #[test]
+    fn plain_content_passes_through() {
+        assert_eq!(seal(None, b"plain".to_vec()).unwrap(), b"plain");
+        assert_eq!(unseal(None, b"plain".to_vec()).unwrap(), b"plain");
+        assert!(!is_encrypted(b"TASKIM-ENCRYPTED"));
+    }
+}
@@ -1,33 +1,794 @@
-use crate::task::TaskData;
+use crate::config::TaskList;
+use crate::crypto::{self, Cipher};
+use crate::task::{Priority, Task, TaskData};
+use chrono::{DateTime, NaiveDate, Utc};
+use color_eyre::eyre::{eyre, Result};
+use rusqlite::{params, Connection};
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 const DATA_FILE: &str = "task_manager_data.json";
+const SQLITE_FILE: &str = "task_manager_data.db";
 
-pub fn load_data() -> TaskData {
-    if Path::new(DATA_FILE).exists() {
-        match fs::read_to_string(DATA_FILE) {
-            Ok(content) => {
-                match serde_json::from_str(&content) {
-                    Ok(data) => data,
-                    Err(e) => {
-                        eprintln!("Error parsing data file: {}", e);
-                        TaskData::default()
+/// Suffix of the undo journal kept next to the main data file
+pub const UNDO_JOURNAL: &str = ".undo";
+/// Suffix of the file keeping marks between sessions
+pub const MARKS: &str = ".marks";
+/// Suffix of the file keeping the command-line history between sessions
+pub const HISTORY: &str = ".history";
+/// Files next to the data file that are encrypted along with it
+const SIDECARS: &[&str] = &[UNDO_JOURNAL, MARKS, HISTORY];
+
+/// Which backend persists the task data, selected with `storage:` in config.yml
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum StorageKind {
+    #[default]
+    Json,
+    Sqlite,
+}
+
+impl StorageKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "json" => Some(StorageKind::Json),
+            "sqlite" => Some(StorageKind::Sqlite),
+            _ => None,
+        }
+    }
+}
+
+/// A single change to persist
+#[derive(Debug, Clone)]
+pub enum Change {
+    Upsert(Task),
+    Delete(String),
+    Reorder(Vec<(String, u32)>),
+}
+
+/// Persistence backend for tasks
+pub trait Storage {
+    /// Load the tasks whose start date lies within `from..=to`
+    fn load_range(&mut self, from: NaiveDate, to: NaiveDate) -> Result<Vec<Task>>;
+    fn upsert(&mut self, task: &Task) -> Result<()>;
+    fn delete(&mut self, task_id: &str) -> Result<()>;
+    /// Set the `order` of the given tasks
+    fn reorder(&mut self, orders: &[(String, u32)]) -> Result<()>;
+    /// The file this backend reads and writes
+    fn path(&self) -> &Path;
+
+    /// Encrypt the stored data with `cipher` from now on (`None` stores plain text),
+    /// rewriting what is already stored when `rewrite` is set
+    fn set_cipher(&mut self, _cipher: Option<Cipher>, _rewrite: bool) -> Result<()> {
+        Err(eyre!("Encryption requires `storage: json` in config.yml"))
+    }
+
+    fn load_all(&mut self) -> Result<Vec<Task>> {
+        self.load_range(NaiveDate::MIN, NaiveDate::MAX)
+    }
+
+    /// Persist a batch of changes. Backends override this to write them in one go.
+    fn apply(&mut self, changes: &[Change]) -> Result<()> {
+        for change in changes {
+            match change {
+                Change::Upsert(task) => self.upsert(task)?,
+                Change::Delete(task_id) => self.delete(task_id)?,
+                Change::Reorder(orders) => self.reorder(orders)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Modification time and size of a data file, used to notice external writes
+type Fingerprint = Option<(SystemTime, u64)>;
+
+/// Tracks what has been persisted so saving only writes the tasks marked as changed
+pub struct DataStore {
+    kind: StorageKind,
+    /// The main data file first, followed by the files of lists that have their own
+    backends: Vec<Box<dyn Storage>>,
+    list_backends: HashMap<String, usize>,
+    persisted: HashMap<String, Task>,
+    /// Which backend each persisted task is stored in
+    locations: HashMap<String, usize>,
+    /// Tasks changed since the last save, including deleted ones
+    dirty: HashSet<String>,
+    fingerprints: Vec<Fingerprint>,
+    cipher: Option<Cipher>,
+}
+
+impl DataStore {
+    pub fn open(kind: StorageKind, lists: &[TaskList]) -> Result<Self> {
+        let mut backends: Vec<Box<dyn Storage>> = vec![match kind {
+            StorageKind::Json => Box::new(JsonStorage::new(DATA_FILE)),
+            StorageKind::Sqlite => Box::new(SqliteStorage::open(SQLITE_FILE, DATA_FILE)?),
+        }];
+        let mut list_backends = HashMap::new();
+        for list in lists {
+            if let Some(file) = &list.file {
+                list_backends.insert(list.name.clone(), backends.len());
+                backends.push(Box::new(JsonStorage::new(file)));
+            }
+        }
+        Ok(Self {
+            kind,
+            backends,
+            list_backends,
+            persisted: HashMap::new(),
+            locations: HashMap::new(),
+            dirty: HashSet::new(),
+            fingerprints: vec![],
+            cipher: None,
+        })
+    }
+
+    /// A store of one plain JSON file at `path`
+    #[cfg(test)]
+    pub fn json_at(path: &Path) -> Self {
+        Self {
+            kind: StorageKind::Json,
+            backends: vec![Box::new(JsonStorage::new(path))],
+            list_backends: HashMap::new(),
+            persisted: HashMap::new(),
+            locations: HashMap::new(),
+            dirty: HashSet::new(),
+            fingerprints: vec![],
+            cipher: None,
+        }
+    }
+
+    /// Read and write the (already encrypted) data files with `cipher`
+    pub fn use_cipher(&mut self, cipher: Cipher) -> Result<()> {
+        for backend in &mut self.backends {
+            backend.set_cipher(Some(cipher.clone()), false)?;
+        }
+        self.cipher = Some(cipher);
+        Ok(())
+    }
+
+    /// Re-encrypt the data files with `cipher`, or store them in plain text for `None`
+    pub fn set_cipher(&mut self, cipher: Option<Cipher>) -> Result<()> {
+        for backend in &mut self.backends {
+            backend.set_cipher(cipher.clone(), true)?;
+        }
+        let sidecars = SIDECARS
+            .iter()
+            .map(|suffix| Ok((*suffix, self.read_sidecar(suffix)?)))
+            .collect::<Result<Vec<_>>>()?;
+        self.cipher = cipher;
+        for (suffix, content) in sidecars {
+            if let Some(content) = content {
+                self.write_sidecar(suffix, content)?;
+            }
+        }
+        self.fingerprints = self.current_fingerprints();
+        Ok(())
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// Read every data file. On error nothing is changed, so a file caught
+    /// mid-write by another program can simply be read again later. Tasks
+    /// marked as changed stay marked, to be saved after merging.
+    pub fn load(&mut self) -> Result<TaskData> {
+        let tasks = self.read(None)?;
+        self.locations = tasks.iter().map(|(index, t)| (t.id.clone(), *index)).collect();
+        self.persisted = tasks.iter().map(|(_, t)| (t.id.clone(), t.clone())).collect();
+        self.fingerprints = self.current_fingerprints();
+        Ok(TaskData {
+            events: tasks.into_iter().map(|(_, t)| t).collect(),
+        })
+    }
+
+    /// Read the tasks of the days `from..=to` from every data file, adding
+    /// them to the ones loaded before
+    pub fn load_range(&mut self, from: NaiveDate, to: NaiveDate) -> Result<Vec<Task>> {
+        let tasks = self.read(Some((from, to)))?;
+        for (index, task) in &tasks {
+            self.locations.insert(task.id.clone(), *index);
+            self.persisted.insert(task.id.clone(), task.clone());
+        }
+        self.fingerprints = self.current_fingerprints();
+        Ok(tasks.into_iter().map(|(_, t)| t).collect())
+    }
+
+    /// The tasks of every backend, within `range` if given, with the index
+    /// of the backend each came from
+    fn read(&mut self, range: Option<(NaiveDate, NaiveDate)>) -> Result<Vec<(usize, Task)>> {
+        let mut tasks = Vec::new();
+        for (index, backend) in self.backends.iter_mut().enumerate() {
+            let list_name = self
+                .list_backends
+                .iter()
+                .find(|(_, &i)| i == index)
+                .map(|(name, _)| name.clone());
+            let loaded = match range {
+                Some((from, to)) => backend.load_range(from, to)?,
+                None => backend.load_all()?,
+            };
+            for mut task in loaded {
+                // Tasks in a list's own file belong to that list
+                if task.list.is_none() {
+                    task.list = list_name.clone();
+                }
+                tasks.push((index, task));
+            }
+        }
+        Ok(tasks)
+    }
+
+    /// The main data file; list files are not locked or versioned
+    pub fn path(&self) -> &Path {
+        self.backends[0].path()
+    }
+
+    pub fn kind(&self) -> StorageKind {
+        self.kind
+    }
+
+    /// A file kept next to the main data file, e.g. `task_manager_data.json.undo`
+    pub fn sidecar_path(&self, suffix: &str) -> PathBuf {
+        let mut name = self.path().as_os_str().to_owned();
+        name.push(suffix);
+        PathBuf::from(name)
+    }
+
+    /// Read a sidecar file, decrypting it like the data file. `None` if it doesn't exist.
+    pub fn read_sidecar(&self, suffix: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.sidecar_path(suffix);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read(path)?;
+        Ok(Some(crypto::unseal(self.cipher.as_ref(), content)?))
+    }
+
+    /// Write a sidecar file, encrypted if the data file is
+    pub fn write_sidecar(&self, suffix: &str, content: Vec<u8>) -> Result<()> {
+        write_atomic(
+            &self.sidecar_path(suffix),
+            &crypto::seal(self.cipher.as_ref(), content)?,
+        )
+    }
+
+    /// Serialize tasks in the data file's format
+    pub fn encode(&self, data: &TaskData) -> Result<Vec<u8>> {
+        crypto::seal(self.cipher.as_ref(), serde_json::to_vec_pretty(data)?)
+    }
+
+    /// Parse tasks stored in the data file's format
+    pub fn decode(&self, content: &[u8]) -> Result<TaskData> {
+        let content = crypto::unseal(self.cipher.as_ref(), content.to_vec())?;
+        Ok(serde_json::from_slice(&content)?)
+    }
+
+    /// The tasks as they were last loaded or saved, i.e. the base for merges
+    pub fn persisted(&self) -> &HashMap<String, Task> {
+        &self.persisted
+    }
+
+    /// Whether something else wrote a data file since we last loaded or saved it
+    pub fn changed_on_disk(&self) -> bool {
+        self.current_fingerprints() != self.fingerprints
+    }
+
+    fn current_fingerprints(&self) -> Vec<Fingerprint> {
+        self.backends
+            .iter()
+            .map(|backend| {
+                let metadata = fs::metadata(backend.path()).ok()?;
+                Some((metadata.modified().ok()?, metadata.len()))
+            })
+            .collect()
+    }
+
+    /// The backend a task belongs in according to its list
+    fn backend_for(&self, task: &Task) -> usize {
+        task.list
+            .as_ref()
+            .and_then(|name| self.list_backends.get(name))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Remember that these tasks were changed, created or deleted, so the next
+    /// save writes them
+    pub fn mark_dirty<'a>(&mut self, task_ids: impl IntoIterator<Item = &'a str>) {
+        self.dirty.extend(task_ids.into_iter().map(str::to_string));
+    }
+
+    /// Write the tasks marked as changed since the last save. If writing
+    /// fails they stay marked.
+    pub fn save(&mut self, data: &TaskData) -> Result<()> {
+        if self.dirty.is_empty() {
+            return Ok(());
+        }
+        let current: HashMap<&str, &Task> = data
+            .events
+            .iter()
+            .filter(|t| self.dirty.contains(&t.id))
+            .map(|t| (t.id.as_str(), t))
+            .collect();
+
+        let mut changes: Vec<Vec<Change>> = vec![Vec::new(); self.backends.len()];
+        let mut reorders: Vec<Vec<(String, u32)>> = vec![Vec::new(); self.backends.len()];
+        for task_id in &self.dirty {
+            let location = self.locations.get(task_id).copied();
+            let Some(&task) = current.get(task_id.as_str()) else {
+                if self.persisted.contains_key(task_id) {
+                    changes[location.unwrap_or(0)].push(Change::Delete(task_id.clone()));
+                }
+                continue;
+            };
+            let target = self.backend_for(task);
+            match self.persisted.get(task_id) {
+                None => changes[target].push(Change::Upsert(task.clone())),
+                // Moved to a list stored in another file
+                Some(_) if location != Some(target) => {
+                    if let Some(location) = location {
+                        changes[location].push(Change::Delete(task_id.clone()));
                     }
+                    changes[target].push(Change::Upsert(task.clone()));
                 }
+                Some(old) if old == task => {}
+                Some(old)
+                    if Task {
+                        order: task.order,
+                        ..old.clone()
+                    } == *task =>
+                {
+                    reorders[target].push((task_id.clone(), task.order));
+                }
+                Some(_) => changes[target].push(Change::Upsert(task.clone())),
+            }
+        }
+        for (index, orders) in reorders.into_iter().enumerate() {
+            if !orders.is_empty() {
+                changes[index].push(Change::Reorder(orders));
             }
-            Err(e) => {
-                eprintln!("Error reading data file: {}", e);
-                TaskData::default()
+        }
+
+        for (backend, changes) in self.backends.iter_mut().zip(&changes) {
+            if !changes.is_empty() {
+                backend.apply(changes)?;
             }
         }
-    } else {
-        TaskData::default()
+        for task_id in std::mem::take(&mut self.dirty) {
+            match current.get(task_id.as_str()) {
+                Some(&task) => {
+                    self.locations.insert(task_id.clone(), self.backend_for(task));
+                    self.persisted.insert(task_id, task.clone());
+                }
+                None => {
+                    self.locations.remove(&task_id);
+                    self.persisted.remove(&task_id);
+                }
+            }
+        }
+        self.fingerprints = self.current_fingerprints();
+        Ok(())
     }
 }
 
-pub fn save_data(data: &TaskData) -> Result<(), color_eyre::eyre::Error> {
-    let content = serde_json::to_string_pretty(data)?;
-    fs::write(DATA_FILE, content)?;
-    Ok(())
+/// The original format: the whole `TaskData` as one pretty-printed JSON file
+pub struct JsonStorage {
+    path: PathBuf,
+    data: TaskData,
+    cipher: Option<Cipher>,
+}
+
+impl JsonStorage {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            data: TaskData::default(),
+            cipher: None,
+        }
+    }
+
+    fn write(&self) -> Result<()> {
+        let content = serde_json::to_vec_pretty(&self.data)?;
+        write_atomic(&self.path, &crypto::seal(self.cipher.as_ref(), content)?)
+    }
+}
+
+impl Storage for JsonStorage {
+    fn load_range(&mut self, from: NaiveDate, to: NaiveDate) -> Result<Vec<Task>> {
+        self.data = read_json(&self.path, self.cipher.as_ref())?;
+        Ok(self
+            .data
+            .events
+            .iter()
+            .filter(|t| (from..=to).contains(&t.start.date_naive()))
+            .cloned()
+            .collect())
+    }
+
+    fn upsert(&mut self, task: &Task) -> Result<()> {
+        self.apply(&[Change::Upsert(task.clone())])
+    }
+
+    fn delete(&mut self, task_id: &str) -> Result<()> {
+        self.apply(&[Change::Delete(task_id.to_string())])
+    }
+
+    fn reorder(&mut self, orders: &[(String, u32)]) -> Result<()> {
+        self.apply(&[Change::Reorder(orders.to_vec())])
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn set_cipher(&mut self, cipher: Option<Cipher>, rewrite: bool) -> Result<()> {
+        if rewrite && self.path.exists() {
+            // Read with the old cipher before switching so nothing is lost
+            self.data = read_json(&self.path, self.cipher.as_ref())?;
+            self.cipher = cipher;
+            self.write()
+        } else {
+            self.cipher = cipher;
+            Ok(())
+        }
+    }
+
+    fn apply(&mut self, changes: &[Change]) -> Result<()> {
+        // The file is rewritten as a whole, so apply everything first and write once
+        for change in changes {
+            match change {
+                Change::Upsert(task) => {
+                    match self.data.events.iter_mut().find(|t| t.id == task.id) {
+                        Some(existing) => *existing = task.clone(),
+                        None => self.data.events.push(task.clone()),
+                    }
+                }
+                Change::Delete(task_id) => self.data.events.retain(|t| &t.id != task_id),
+                Change::Reorder(orders) => {
+                    for (task_id, order) in orders {
+                        if let Some(task) = self.data.events.iter_mut().find(|t| &t.id == task_id) {
+                            task.order = *order;
+                        }
+                    }
+                }
+            }
+        }
+        self.write()
+    }
+}
+
+/// Embedded SQLite database that persists each change as its own row update
+pub struct SqliteStorage {
+    path: PathBuf,
+    conn: Connection,
+}
+
+impl SqliteStorage {
+    /// Open (or create) the database, seeding a new one from `json_path` if present
+    pub fn open<P: AsRef<Path>, J: AsRef<Path>>(path: P, json_path: J) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let is_new = !path.exists();
+        let conn = Connection::open(&path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS tasks (
+                id TEXT PRIMARY KEY,
+                title TEXT NOT NULL,
+                start TEXT NOT NULL,
+                end TEXT NOT NULL,
+                day TEXT NOT NULL,
+                comments TEXT NOT NULL,
+                completed INTEGER NOT NULL,
+                ord INTEGER NOT NULL,
+                list TEXT,
+                tags TEXT NOT NULL DEFAULT '[]',
+                priority TEXT
+            );
+            CREATE INDEX IF NOT EXISTS tasks_day ON tasks (day);",
+        )?;
+
+        let mut storage = Self { path, conn };
+        if is_new && json_path.as_ref().exists() {
+            let data = read_json(json_path, None)?;
+            let changes: Vec<_> = data.events.into_iter().map(Change::Upsert).collect();
+            storage.apply(&changes)?;
+        }
+        Ok(storage)
+    }
+
+    fn upsert_row(conn: &Connection, task: &Task) -> Result<()> {
+        conn.execute(
+            "INSERT INTO tasks (id, title, start, end, day, comments, completed, ord, list, tags, priority)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+             ON CONFLICT(id) DO UPDATE SET
+                title = excluded.title, start = excluded.start, end = excluded.end,
+                day = excluded.day, comments = excluded.comments,
+                completed = excluded.completed, ord = excluded.ord, list = excluded.list,
+                tags = excluded.tags, priority = excluded.priority",
+            params![
+                task.id,
+                task.title,
+                task.start.to_rfc3339(),
+                task.end.to_rfc3339(),
+                task.start.date_naive().to_string(),
+                serde_json::to_string(&task.comments)?,
+                task.completed,
+                task.order,
+                task.list,
+                serde_json::to_string(&task.tags)?,
+                task.priority.map(Priority::name),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Read the tasks selected by `sql`
+    fn query(&self, sql: &str, params: impl rusqlite::Params) -> Result<Vec<Task>> {
+        let mut statement = self.conn.prepare(sql)?;
+        let rows = statement.query_map(params, |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, bool>(5)?,
+                row.get::<_, u32>(6)?,
+                row.get::<_, Option<String>>(7)?,
+                row.get::<_, String>(8)?,
+                row.get::<_, Option<String>>(9)?,
+            ))
+        })?;
+
+        let mut tasks = Vec::new();
+        for row in rows {
+            let (id, title, start, end, comments, completed, order, list, tags, priority) = row?;
+            tasks.push(Task {
+                id,
+                title,
+                start: parse_timestamp(&start)?,
+                end: parse_timestamp(&end)?,
+                comments: serde_json::from_str(&comments)?,
+                completed,
+                order,
+                list,
+                tags: serde_json::from_str(&tags)?,
+                priority: priority.as_deref().and_then(Priority::from_name),
+            });
+        }
+        Ok(tasks)
+    }
+
+    fn reorder_rows(conn: &Connection, orders: &[(String, u32)]) -> Result<()> {
+        let mut statement = conn.prepare_cached("UPDATE tasks SET ord = ?2 WHERE id = ?1")?;
+        for (task_id, order) in orders {
+            statement.execute(params![task_id, order])?;
+        }
+        Ok(())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn load_range(&mut self, from: NaiveDate, to: NaiveDate) -> Result<Vec<Task>> {
+        // Days are stored as ISO strings, which only sort correctly for four digit years
+        let min = NaiveDate::from_ymd_opt(0, 1, 1).unwrap();
+        let max = NaiveDate::from_ymd_opt(9999, 12, 31).unwrap();
+        let (from, to) = (from.clamp(min, max), to.clamp(min, max));
+        self.query(
+            "SELECT id, title, start, end, comments, completed, ord, list, tags, priority FROM tasks
+             WHERE day BETWEEN ?1 AND ?2 ORDER BY day, ord",
+            params![from.to_string(), to.to_string()],
+        )
+    }
+
+    fn load_all(&mut self) -> Result<Vec<Task>> {
+        self.query(
+            "SELECT id, title, start, end, comments, completed, ord, list, tags, priority FROM tasks
+             ORDER BY day, ord",
+            [],
+        )
+    }
+
+    fn upsert(&mut self, task: &Task) -> Result<()> {
+        Self::upsert_row(&self.conn, task)
+    }
+
+    fn delete(&mut self, task_id: &str) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM tasks WHERE id = ?1", params![task_id])?;
+        Ok(())
+    }
+
+    fn reorder(&mut self, orders: &[(String, u32)]) -> Result<()> {
+        Self::reorder_rows(&self.conn, orders)
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn apply(&mut self, changes: &[Change]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        for change in changes {
+            match change {
+                Change::Upsert(task) => Self::upsert_row(&tx, task)?,
+                Change::Delete(task_id) => {
+                    tx.execute("DELETE FROM tasks WHERE id = ?1", params![task_id])?;
+                }
+                Change::Reorder(orders) => Self::reorder_rows(&tx, orders)?,
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+fn parse_timestamp(value: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.to_utc())
+        .map_err(|e| eyre!("Invalid timestamp {:?} in database: {}", value, e))
+}
+
+/// Read a JSON data file, decrypting it if needed. A missing file holds no
+/// tasks; one that cannot be read, decrypted or parsed is an error.
+fn read_json<P: AsRef<Path>>(path: P, cipher: Option<&Cipher>) -> Result<TaskData> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(TaskData::default());
+    }
+    let content = fs::read(path).map_err(|e| eyre!("Cannot read {}: {}", path.display(), e))?;
+    let content = crypto::unseal(cipher, content)?;
+    serde_json::from_slice(&content).map_err(|e| eyre!("Cannot parse {}: {}", path.display(), e))
+}
+
+/// Replace `path` with `content` so readers see either the old or the new
+/// file, never a partly written one: write a temporary file next to it, flush
+/// it to disk and rename it over the original.
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| eyre!("Invalid data file path {}", path.display()))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(name);
+    temp_name.push(".tmp");
+    let temp = path.with_file_name(temp_name);
+    let result = (|| -> Result<()> {
+        let mut file = fs::File::create(&temp)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        fs::rename(&temp, path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("taskim-data-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("tasks.json")
+    }
+
+    #[test]
+    fn missing_file_has_no_tasks() {
+        let path = temp_path("missing");
+        let _ = fs::remove_file(&path);
+        assert!(read_json(&path, None).unwrap().events.is_empty());
+    }
+
+    #[test]
+    fn unreadable_file_is_an_error_not_an_empty_list() {
+        let path = temp_path("partial");
+        fs::write(&path, b"{\"events\": [{\"id\": \"a\", \"tit").unwrap();
+        assert!(read_json(&path, None).is_err());
+    }
+
+    #[test]
+    fn failed_load_keeps_the_merge_base() {
+        let path = temp_path("base");
+        let mut storage = JsonStorage::new(&path);
+        let task = Task::new(String::from("keep"), Utc::now());
+        storage.upsert(&task).unwrap();
+        let mut store = DataStore::json_at(&path);
+        assert_eq!(store.load().unwrap().events.len(), 1);
+        fs::write(&path, b"{").unwrap();
+        assert!(store.load().is_err());
+        assert!(store.persisted().contains_key(&task.id));
+        assert!(store.locations.contains_key(&task.id));
+    }
+
+    #[test]
+    fn both_backends_load_a_range_of_days() {
+        let json_path = temp_path("range");
+        let sqlite_path = json_path.with_extension("db");
+        let _ = fs::remove_file(&json_path);
+        let _ = fs::remove_file(&sqlite_path);
+        let day = |d: u32| NaiveDate::from_ymd_opt(2025, 6, d).unwrap();
+        let backends: Vec<Box<dyn Storage>> = vec![
+            Box::new(JsonStorage::new(&json_path)),
+            Box::new(SqliteStorage::open(&sqlite_path, &json_path).unwrap()),
+        ];
+        for mut backend in backends {
+            for d in [1, 10, 11, 20] {
+                let start = day(d).and_hms_opt(23, 30, 0).unwrap().and_utc();
+                backend.upsert(&Task::new(format!("{}", d), start)).unwrap();
+            }
+            let titles = |tasks: Vec<Task>| tasks.into_iter().map(|t| t.title).collect::<Vec<_>>();
+            assert_eq!(titles(backend.load_range(day(10), day(20)).unwrap()), ["10", "11", "20"]);
+            assert_eq!(titles(backend.load_range(day(2), day(9)).unwrap()), Vec::<String>::new());
+            assert_eq!(backend.load_all().unwrap().len(), 4);
+        }
+    }
+
+    #[test]
+    fn loaded_ranges_add_up_to_the_merge_base() {
+        let path = temp_path("ranges");
+        let _ = fs::remove_file(&path);
+        let mut storage = JsonStorage::new(&path);
+        let early = Task::new(String::from("early"), Utc::now() - chrono::Duration::days(40));
+        let late = Task::new(String::from("late"), Utc::now());
+        storage.upsert(&early).unwrap();
+        storage.upsert(&late).unwrap();
+
+        let mut store = DataStore::json_at(&path);
+        let today = late.start.date_naive();
+        assert_eq!(store.load_range(today, today).unwrap().len(), 1);
+        assert!(!store.persisted().contains_key(&early.id));
+        let before = early.start.date_naive();
+        assert_eq!(store.load_range(before, before).unwrap().len(), 1);
+        assert!(store.persisted().contains_key(&early.id));
+        assert!(store.persisted().contains_key(&late.id));
+    }
+
+    #[test]
+    fn saving_writes_the_tasks_marked_dirty() {
+        let path = temp_path("dirty");
+        let _ = fs::remove_file(&path);
+        let mut store = DataStore::json_at(&path);
+        let mut data = store.load().unwrap();
+        data.events.push(Task::new(String::from("one"), Utc::now()));
+        data.events.push(Task::new(String::from("two"), Utc::now()));
+        store.save(&data).unwrap();
+        assert!(!path.exists());
+
+        store.mark_dirty(data.events.iter().map(|t| t.id.as_str()));
+        store.save(&data).unwrap();
+        assert_eq!(read_json(&path, None).unwrap().events.len(), 2);
+
+        // Only the marked edit is written; deletions are marked by id
+        data.events[0].title = String::from("one, edited");
+        data.events[1].title = String::from("two, unmarked");
+        store.mark_dirty([data.events[0].id.as_str()]);
+        store.save(&data).unwrap();
+        let removed = data.events.remove(1);
+        store.mark_dirty([removed.id.as_str()]);
+        store.save(&data).unwrap();
+        let saved = read_json(&path, None).unwrap().events;
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].title, "one, edited");
+        assert!(store.dirty.is_empty());
+    }
+
+    #[test]
+    fn atomic_write_replaces_the_file_and_leaves_no_temporary() {
+        let path = temp_path("atomic");
+        write_atomic(&path, b"old").unwrap();
+        write_atomic(&path, b"new").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        let leftovers: Vec<_> = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().ends_with(".tmp"))
+            .collect();
+        assert!(leftovers.is_empty());
+    }
 }
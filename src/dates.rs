@@ -0,0 +1,311 @@
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveTime, Weekday};
+
+/// The day a date expression means, seen from `today`:
+///
+/// - `2025-06-15`, `06/15/2025`
+/// - `today`, `tomorrow`, `yesterday`
+/// - `friday`, `next friday`: the next such day after today
+/// - `+3d`, `-2w`, `+1m`, `+1y`: days, weeks, months or years from today
+/// - `eow`, `eom`, `eoy`: the end of this week, month or year; `sow` its start
+/// - `next weekday`: the next Monday to Friday
+/// - `next week`: the next Monday; `next month`, `next year`: the first day of it
+/// - `first monday of july`, `last friday of 2025-08`: the nth weekday of a month
+pub fn parse_date(text: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let text = text.trim().to_lowercase();
+    let words: Vec<&str> = text.split_whitespace().collect();
+    match words.as_slice() {
+        [word] => parse_word(word, today),
+        ["next", "week"] => Some(next_weekday(today, Weekday::Mon)),
+        ["next", "weekday"] => Some(next_workday(today)),
+        ["next", "month"] => first_of_month(today).checked_add_months(Months::new(1)),
+        ["next", "year"] => NaiveDate::from_ymd_opt(today.year() + 1, 1, 1),
+        ["next", name] => parse_weekday(name).map(|weekday| next_weekday(today, weekday)),
+        [nth, name, "of", month @ ..] => {
+            let weekday = parse_weekday(name)?;
+            let month = parse_month(&month.join(" "), today)?;
+            nth_weekday(month, weekday, parse_nth(nth)?)
+        }
+        _ => None,
+    }
+}
+
+fn parse_word(word: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match word {
+        "today" => return Some(today),
+        "tomorrow" | "tmr" => return Some(today + Duration::days(1)),
+        "yesterday" => return Some(today - Duration::days(1)),
+        // Weeks run from Sunday to Saturday, as in the calendar
+        "sow" => return Some(today - Duration::days(today.weekday().num_days_from_sunday() as i64)),
+        "eow" => return Some(today + Duration::days(6 - today.weekday().num_days_from_sunday() as i64)),
+        "eom" => return last_of_month(today),
+        "eoy" => return NaiveDate::from_ymd_opt(today.year(), 12, 31),
+        _ => {}
+    }
+    if let Some(date) = parse_offset(word, today) {
+        return Some(date);
+    }
+    if let Some(weekday) = parse_weekday(word) {
+        return Some(next_weekday(today, weekday));
+    }
+    NaiveDate::parse_from_str(word, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(word, "%m/%d/%Y"))
+        .ok()
+}
+
+/// `+3d`, `-2w`, `+1m`, `+1y`; the sign is required so a title word such as
+/// "3d" is not taken for a date
+fn parse_offset(word: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let (forward, rest) = match word.as_bytes().first()? {
+        b'+' => (true, &word[1..]),
+        b'-' => (false, &word[1..]),
+        _ => return None,
+    };
+    let unit = rest.chars().last()?;
+    let amount: u32 = rest[..rest.len() - unit.len_utf8()].parse().ok()?;
+    let (days, months) = match unit {
+        'd' => (amount as i64, 0),
+        'w' => (amount as i64 * 7, 0),
+        'm' => (0, amount),
+        'y' => (0, amount.checked_mul(12)?),
+        _ => return None,
+    };
+    if forward {
+        today.checked_add_months(Months::new(months))?
+            .checked_add_signed(Duration::days(days))
+    } else {
+        today.checked_sub_months(Months::new(months))?
+            .checked_sub_signed(Duration::days(days))
+    }
+}
+
+/// A time of day: `15:00`, `3pm`, `3:30pm`, `9am`
+pub fn parse_time(text: &str) -> Option<NaiveTime> {
+    let text = text.trim().to_lowercase();
+    let (clock, offset) = match (text.strip_suffix("am"), text.strip_suffix("pm")) {
+        (Some(clock), _) => (clock, Some(0)),
+        (_, Some(clock)) => (clock, Some(12)),
+        _ => (text.as_str(), None),
+    };
+    let (hour, minute) = match clock.split_once(':') {
+        Some((hour, minute)) => (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?),
+        // A bare number is only a time with am/pm
+        None if offset.is_some() => (clock.parse::<u32>().ok()?, 0),
+        None => return None,
+    };
+    let hour = match offset {
+        Some(_) if !(1..=12).contains(&hour) => return None,
+        Some(offset) => hour % 12 + offset,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+/// Day names and the abbreviations that aren't also words ("sun", "sat",
+/// "mon" and "wed" would turn titles into dates)
+fn parse_weekday(name: &str) -> Option<Weekday> {
+    match name {
+        "monday" => Some(Weekday::Mon),
+        "tuesday" | "tue" | "tues" => Some(Weekday::Tue),
+        "wednesday" => Some(Weekday::Wed),
+        "thursday" | "thu" | "thurs" => Some(Weekday::Thu),
+        "friday" | "fri" => Some(Weekday::Fri),
+        "saturday" => Some(Weekday::Sat),
+        "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// `first` to `fifth` (or `1st` to `5th`) as 1 to 5, `last` as 0
+fn parse_nth(word: &str) -> Option<u32> {
+    match word {
+        "first" | "1st" => Some(1),
+        "second" | "2nd" => Some(2),
+        "third" | "3rd" => Some(3),
+        "fourth" | "4th" => Some(4),
+        "fifth" | "5th" => Some(5),
+        "last" => Some(0),
+        _ => None,
+    }
+}
+
+/// The first day of a month written `july`, `july 2026`, `2026-07` or
+/// `next month`; a month name alone is its next start from `today` on
+fn parse_month(text: &str, today: NaiveDate) -> Option<NaiveDate> {
+    if text == "next month" {
+        return first_of_month(today).checked_add_months(Months::new(1));
+    }
+    if text == "this month" {
+        return Some(first_of_month(today));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(&format!("{}-01", text), "%Y-%m-%d") {
+        return Some(date);
+    }
+    let mut words = text.split_whitespace();
+    let name = words.next()?;
+    let month = MONTHS
+        .iter()
+        .position(|m| *m == name || (name.len() >= 3 && m.starts_with(name)))? as u32
+        + 1;
+    match words.next() {
+        Some(year) => NaiveDate::from_ymd_opt(year.parse().ok()?, month, 1),
+        None => {
+            let this_year = NaiveDate::from_ymd_opt(today.year(), month, 1)?;
+            if month < today.month() {
+                this_year.checked_add_months(Months::new(12))
+            } else {
+                Some(this_year)
+            }
+        }
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "january", "february", "march", "april", "may", "june", "july", "august", "september",
+    "october", "november", "december",
+];
+
+/// The `nth` `weekday` of the month starting on `first`, the last one for 0
+fn nth_weekday(first: NaiveDate, weekday: Weekday, nth: u32) -> Option<NaiveDate> {
+    if nth == 0 {
+        let last = last_of_month(first)?;
+        let back = (last.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+        return Some(last - Duration::days(back as i64));
+    }
+    let ahead = (weekday.num_days_from_monday() + 7 - first.weekday().num_days_from_monday()) % 7;
+    let date = first + Duration::days((ahead + (nth - 1) * 7) as i64);
+    (date.month() == first.month()).then_some(date)
+}
+
+fn first_of_month(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap()
+}
+
+fn last_of_month(date: NaiveDate) -> Option<NaiveDate> {
+    first_of_month(date).checked_add_months(Months::new(1))?.pred_opt()
+}
+
+/// The first Monday to Friday after `date`
+fn next_workday(date: NaiveDate) -> NaiveDate {
+    let mut next = date + Duration::days(1);
+    while matches!(next.weekday(), Weekday::Sat | Weekday::Sun) {
+        next += Duration::days(1);
+    }
+    next
+}
+
+/// The first `weekday` after `date`
+fn next_weekday(date: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let days = (weekday.num_days_from_monday() + 7 - date.weekday().num_days_from_monday()) % 7;
+    date + Duration::days(if days == 0 { 7 } else { days as i64 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn time(h: u32, m: u32) -> Option<NaiveTime> {
+        NaiveTime::from_hms_opt(h, m, 0)
+    }
+
+    // A Wednesday
+    fn today() -> NaiveDate {
+        date(2025, 6, 11)
+    }
+
+    #[test]
+    fn reads_fixed_dates_and_day_names() {
+        let cases = [
+            ("2025-06-15", date(2025, 6, 15)),
+            ("06/15/2025", date(2025, 6, 15)),
+            ("today", today()),
+            (" Tomorrow ", date(2025, 6, 12)),
+            ("yesterday", date(2025, 6, 10)),
+            ("friday", date(2025, 6, 13)),
+            ("wednesday", date(2025, 6, 18)),
+            ("thu", date(2025, 6, 12)),
+            ("next friday", date(2025, 6, 13)),
+            ("sow", date(2025, 6, 8)),
+            ("eow", date(2025, 6, 14)),
+            ("eom", date(2025, 6, 30)),
+            ("eoy", date(2025, 12, 31)),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse_date(text, today()), Some(expected), "{}", text);
+        }
+    }
+
+    #[test]
+    fn reads_offsets_and_relative_periods() {
+        let cases = [
+            ("+3d", date(2025, 6, 14)),
+            ("-2w", date(2025, 5, 28)),
+            ("+1m", date(2025, 7, 11)),
+            ("+1y", date(2026, 6, 11)),
+            ("next week", date(2025, 6, 16)),
+            ("next weekday", date(2025, 6, 12)),
+            ("next month", date(2025, 7, 1)),
+            ("next year", date(2026, 1, 1)),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse_date(text, today()), Some(expected), "{}", text);
+        }
+        // Friday's next weekday is the Monday after
+        assert_eq!(parse_date("next weekday", date(2025, 6, 13)), Some(date(2025, 6, 16)));
+        // A month on from the 31st ends at the end of the shorter month
+        assert_eq!(parse_date("+1m", date(2025, 1, 31)), Some(date(2025, 2, 28)));
+    }
+
+    #[test]
+    fn reads_the_nth_weekday_of_a_month() {
+        let cases = [
+            ("first monday of july", date(2025, 7, 7)),
+            ("2nd tuesday of july 2026", date(2026, 7, 14)),
+            ("last friday of 2025-08", date(2025, 8, 29)),
+            ("last sunday of next month", date(2025, 7, 27)),
+            ("first wednesday of this month", date(2025, 6, 4)),
+            // A month already past this year means next year's
+            ("first monday of jan", date(2026, 1, 5)),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse_date(text, today()), Some(expected), "{}", text);
+        }
+        // There is no fifth Monday in July 2025
+        assert_eq!(parse_date("fifth monday of july", today()), None);
+    }
+
+    #[test]
+    fn rejects_other_words_without_panicking() {
+        let inputs = [
+            "", "tom", "tod", "sun", "sat", "mon", "wed", "first sat of july", "3d", "+", "+d", "+3x", "+3é", "é", "+é", "friyay", "next", "next fortnight",
+            "2025-02-30", "13/01/2025", "first of july", "sixth monday of july",
+            "first monday of smarch", "first monday of july 99999999999",
+            "+99999999999d", "+4000000000d", "+4000000000m", "+400000000y", "-4000000000w",
+        ];
+        for input in inputs {
+            assert_eq!(parse_date(input, today()), None, "{}", input);
+        }
+    }
+
+    #[test]
+    fn reads_times_of_day() {
+        let cases = [
+            ("15:00", time(15, 0)),
+            ("3pm", time(15, 0)),
+            ("3:30PM", time(15, 30)),
+            ("9am", time(9, 0)),
+            ("12am", time(0, 0)),
+            ("12pm", time(12, 0)),
+            ("0:05", time(0, 5)),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse_time(text), expected, "{}", text);
+        }
+        for input in ["15", "13pm", "0am", "25:00", "9:60", "3 pm", "pm", "é", "9éam", "99999999999am"] {
+            assert_eq!(parse_time(input), None, "{}", input);
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use crate::dates;
+use crate::task::{Priority, Task};
+use chrono::NaiveDate;
+
+/// The built-in smart lists as (name, filter expression)
+pub const SMART_LISTS: [(&str, &str); 4] = [
+    ("Overdue", "todo before:today"),
+    ("Today", "on:today"),
+    ("Next 7 days", "todo after:today until:+7d"),
+    ("Completed this week", "done from:sow until:eow"),
+];
+
+/// A parsed filter expression such as `todo before:today #work or !high`:
+/// a task matches when every term of one of the `or` alternatives does
+#[derive(Debug, Clone)]
+pub struct Filter {
+    alternatives: Vec<Vec<Term>>,
+}
+
+#[derive(Debug, Clone)]
+struct Term {
+    /// Written with a leading `-`
+    negated: bool,
+    test: Test,
+}
+
+#[derive(Debug, Clone)]
+enum Test {
+    Completed(bool),
+    On(NaiveDate),
+    From(NaiveDate),
+    Until(NaiveDate),
+    Title(String),
+    Comment(String),
+    /// Title or comments
+    Text(String),
+    Tag(String),
+    List(String),
+    Priority(Priority),
+}
+
+impl Filter {
+    /// Parse an expression, reading its dates relative to `today`:
+    ///
+    /// - `done`, `todo`: completed or not
+    /// - `on:`, `before:`, `after:`, `from:`, `until:` a date expression,
+    ///   quoted when it has spaces (`after:"next week"`)
+    /// - `title:word`, `comment:word`, or a bare word for either
+    /// - `#tag`, `list:name`, `!high`
+    /// - `-term` for tasks not matching it, and `or` between alternatives
+    pub fn parse(text: &str, today: NaiveDate) -> Result<Self, String> {
+        let mut alternatives = vec![Vec::new()];
+        for word in split_words(text)? {
+            if word.eq_ignore_ascii_case("or") {
+                alternatives.push(Vec::new());
+                continue;
+            }
+            let term = parse_term(&word, today)?;
+            alternatives.last_mut().unwrap().push(term);
+        }
+        if alternatives.iter().any(Vec::is_empty) {
+            return Err(String::from("Empty filter, e.g. todo before:today"));
+        }
+        Ok(Self { alternatives })
+    }
+
+    /// Whether `task`, which belongs to the list named `list`, matches
+    pub fn matches(&self, task: &Task, list: &str) -> bool {
+        self.alternatives
+            .iter()
+            .any(|terms| terms.iter().all(|term| term.test.matches(task, list) != term.negated))
+    }
+}
+
+impl Test {
+    fn matches(&self, task: &Task, list: &str) -> bool {
+        let date = task.start.date_naive();
+        match self {
+            Test::Completed(done) => task.completed == *done,
+            Test::On(day) => date == *day,
+            Test::From(day) => date >= *day,
+            Test::Until(day) => date <= *day,
+            Test::Title(text) => contains(&task.title, text),
+            Test::Comment(text) => task.comments.iter().any(|c| contains(&c.text, text)),
+            Test::Text(text) => {
+                contains(&task.title, text) || task.comments.iter().any(|c| contains(&c.text, text))
+            }
+            Test::Tag(tag) => {
+                task.tags.iter().any(|t| t.to_lowercase() == *tag)
+                    || task
+                        .title
+                        .split_whitespace()
+                        .any(|w| w.strip_prefix('#').is_some_and(|t| t.to_lowercase() == *tag))
+            }
+            Test::List(name) => list.to_lowercase() == *name,
+            Test::Priority(priority) => task.priority == Some(*priority),
+        }
+    }
+}
+
+fn parse_term(word: &str, today: NaiveDate) -> Result<Term, String> {
+    let (negated, word) = match word.strip_prefix('-') {
+        Some(rest) if !rest.is_empty() => (true, rest),
+        _ => (false, word),
+    };
+    let date = |value: &str| {
+        dates::parse_date(value, today).ok_or_else(|| format!("Unknown date: {}", value))
+    };
+    let test = match word.split_once(':') {
+        Some(("on", value)) => Test::On(date(value)?),
+        Some(("before", value)) => {
+            Test::Until(date(value)?.pred_opt().ok_or_else(|| out_of_range(value))?)
+        }
+        Some(("after", value)) => {
+            Test::From(date(value)?.succ_opt().ok_or_else(|| out_of_range(value))?)
+        }
+        Some(("from", value)) => Test::From(date(value)?),
+        Some(("until", value)) => Test::Until(date(value)?),
+        Some(("title", value)) => Test::Title(value.to_lowercase()),
+        Some(("comment", value)) => Test::Comment(value.to_lowercase()),
+        Some(("list", value)) => Test::List(value.to_lowercase()),
+        Some(("priority", value)) => Test::Priority(priority(value)?),
+        _ => match word {
+            "done" => Test::Completed(true),
+            "todo" => Test::Completed(false),
+            _ => {
+                if let Some(tag) = word.strip_prefix('#').filter(|t| !t.is_empty()) {
+                    Test::Tag(tag.to_lowercase())
+                } else if let Some(name) = word.strip_prefix('!').filter(|p| !p.is_empty()) {
+                    Test::Priority(priority(name)?)
+                } else {
+                    Test::Text(word.to_lowercase())
+                }
+            }
+        },
+    };
+    Ok(Term { negated, test })
+}
+
+/// For a day before the first or after the last one chrono can hold
+fn out_of_range(value: &str) -> String {
+    format!("Date out of range: {}", value)
+}
+
+fn priority(name: &str) -> Result<Priority, String> {
+    Priority::from_name(name).ok_or_else(|| format!("Unknown priority: {}", name))
+}
+
+/// Case-insensitive substring search; `needle` is already lowercase
+fn contains(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(needle)
+}
+
+/// Split at whitespace outside double quotes, dropping the quotes
+fn split_words(text: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    for c in text.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if quoted {
+        return Err(String::from("Unclosed quote"));
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::TaskComment;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    // A Wednesday
+    fn today() -> NaiveDate {
+        date(2025, 6, 11)
+    }
+
+    fn task(title: &str, day: NaiveDate) -> Task {
+        Task::new(title.to_string(), day.and_hms_opt(9, 0, 0).unwrap().and_utc())
+    }
+
+    fn matches(filter: &str, task: &Task) -> bool {
+        Filter::parse(filter, today()).unwrap().matches(task, "Work")
+    }
+
+    #[test]
+    fn dates_bound_the_day_inclusively_or_not() {
+        let task = task("Report", today());
+        for filter in ["on:today", "from:today", "until:today", "before:tomorrow", "after:yesterday"] {
+            assert!(matches(filter, &task), "{}", filter);
+        }
+        for filter in ["on:tomorrow", "before:today", "after:today", "from:+1d", "until:-1d"] {
+            assert!(!matches(filter, &task), "{}", filter);
+        }
+        assert!(matches("after:\"last monday of 2025-05\" before:\"next week\"", &task));
+        // A month name alone is its next start, so May is next year's here
+        assert!(!matches("after:\"last monday of may\"", &task));
+    }
+
+    #[test]
+    fn matches_completion_text_tags_lists_and_priority() {
+        let mut task = task("Send Q2 report #finance", today());
+        task.tags = vec![String::from("urgent")];
+        task.priority = Some(Priority::High);
+        task.comments.push(TaskComment {
+            id: String::from("c"),
+            text: String::from("Ask Ana for the numbers"),
+        });
+
+        for filter in [
+            "todo", "REPORT", "title:q2", "comment:ana", "ana", "#finance", "#Urgent",
+            "list:work", "!high", "priority:h", "-done",
+        ] {
+            assert!(matches(filter, &task), "{}", filter);
+        }
+        for filter in ["done", "title:ana", "comment:report", "#report", "list:home", "!low", "-todo"] {
+            assert!(!matches(filter, &task), "{}", filter);
+        }
+        task.completed = true;
+        assert!(matches("done", &task));
+    }
+
+    #[test]
+    fn every_term_of_one_alternative_must_match() {
+        let mut task = task("Report", today());
+        task.priority = Some(Priority::Low);
+        assert!(matches("todo report", &task));
+        assert!(!matches("todo report !high", &task));
+        assert!(matches("todo report !high or !low", &task));
+        assert!(matches("done OR report", &task));
+        assert!(!matches("done or !high", &task));
+    }
+
+    #[test]
+    fn smart_lists_parse() {
+        for (name, filter) in SMART_LISTS {
+            assert!(Filter::parse(filter, today()).is_ok(), "{}", name);
+        }
+        let overdue = Filter::parse(SMART_LISTS[0].1, today()).unwrap();
+        assert!(overdue.matches(&task("Old", date(2025, 6, 10)), "Work"));
+        assert!(!overdue.matches(&task("Now", today()), "Work"));
+    }
+
+    #[test]
+    fn works_on_multibyte_text() {
+        let mut task = task("Café crème #日本", today());
+        task.tags = vec![String::from("Éte")];
+        for filter in ["café", "CRÈME", "title:\"é cr\"", "#日本", "#éte", "-ünknown"] {
+            assert!(matches(filter, &task), "{}", filter);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        let inputs = [
+            "", "   ", "or", "todo or", "or todo", "todo or or done", "on:", "on:someday",
+            "before:\"next week", "!urgent", "priority:", "after:+99999999999d",
+            "before:\"first monday of july 99999999999\"",
+        ];
+        for input in inputs {
+            assert!(Filter::parse(input, today()).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn days_past_the_calendar_are_errors() {
+        let last = (NaiveDate::MAX - today()).num_days();
+        let first = (today() - NaiveDate::MIN).num_days();
+        assert!(Filter::parse(&format!("until:+{}d", last), today()).is_ok());
+        assert!(Filter::parse(&format!("after:+{}d", last), today()).is_err());
+        assert!(Filter::parse(&format!("from:-{}d", first), today()).is_ok());
+        assert!(Filter::parse(&format!("before:-{}d", first), today()).is_err());
+    }
+}
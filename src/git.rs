@@ -0,0 +1,334 @@
+use crate::data::{write_atomic, DataStore};
+use crate::merge::merge_by_id;
+use crate::task::TaskData;
+use color_eyre::eyre::{eyre, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Remote that `:sync` pulls from and pushes to, named so it can't clash with
+/// a remote the user set up
+const REMOTE: &str = "taskim";
+/// Git directory holding the history, next to the data file. It is separate
+/// from any repository the data directory may already be part of.
+const GIT_DIR: &str = ".taskim-history";
+
+/// Settings from the `git:` section of config.yml
+#[derive(Debug, Clone, Default)]
+pub struct GitSettings {
+    pub enabled: bool,
+    pub remote: Option<String>,
+    pub branch: String,
+}
+
+/// Result of a `:sync`
+#[derive(Debug, Default)]
+pub struct SyncOutcome {
+    pub pulled: bool,
+    pub conflicts: usize,
+}
+
+/// Git repository in the data directory that versions the data file
+pub struct GitHistory {
+    dir: PathBuf,
+    git_dir: PathBuf,
+    file: String,
+    settings: GitSettings,
+    identity: Vec<String>,
+}
+
+impl GitHistory {
+    /// Open the history of `data_path`, initializing it if needed
+    pub fn open(data_path: &Path, settings: &GitSettings) -> Result<Self> {
+        let dir = match data_path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let file = data_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| eyre!("Invalid data file path {}", data_path.display()))?
+            .to_string();
+
+        let git_dir = dir.join(GIT_DIR);
+        let mut history = Self {
+            dir,
+            git_dir,
+            file,
+            settings: settings.clone(),
+            identity: vec![],
+        };
+
+        if !history.git_dir.exists() {
+            // `init -b` needs git 2.28, so name the branch with symbolic-ref
+            history.git(&["init", "-q"])?;
+            let head = format!("refs/heads/{}", history.settings.branch);
+            history.git(&["symbolic-ref", "HEAD", &head])?;
+        }
+
+        // Commits must not fail just because no identity is configured
+        if history.git(&["config", "user.email"]).is_err() {
+            history.identity = vec![
+                String::from("-c"),
+                String::from("user.name=taskim"),
+                String::from("-c"),
+                String::from("user.email=taskim@localhost"),
+            ];
+        }
+
+        if let Some(remote) = &history.settings.remote {
+            match history.git(&["remote", "get-url", REMOTE]) {
+                Ok(url) if url == *remote => {}
+                Ok(_) => {
+                    history.git(&["remote", "set-url", REMOTE, remote])?;
+                }
+                Err(_) => {
+                    history.git(&["remote", "add", REMOTE, remote])?;
+                }
+            }
+        }
+
+        if history.dir.join(&history.file).exists() {
+            history.commit("Track task data")?;
+        }
+        Ok(history)
+    }
+
+    /// Commit the data file if it changed
+    pub fn commit(&self, message: &str) -> Result<()> {
+        // Forced, since an enclosing project may well ignore the data file
+        self.git(&["add", "-f", "--", &self.file])?;
+        if self.git(&["diff", "--cached", "--quiet", "--", &self.file]).is_ok() {
+            return Ok(());
+        }
+        self.git(&["commit", "-q", "-m", message, "--", &self.file])?;
+        Ok(())
+    }
+
+    /// Pull from and push to the configured remote, merging task lists by id.
+    /// The data file on disk is updated in place; the caller reloads it.
+    pub fn sync(&self, store: &DataStore) -> Result<SyncOutcome> {
+        if self.settings.remote.is_none() {
+            return Err(eyre!("No git remote configured (set git.remote in config.yml)"));
+        }
+        let branch = self.settings.branch.as_str();
+        let remote_ref = format!("{}/{}", REMOTE, branch);
+        let mut outcome = SyncOutcome::default();
+
+        // An empty remote has nothing to pull yet
+        let has_remote_branch = self.git(&["fetch", "-q", REMOTE, branch]).is_ok();
+        if has_remote_branch && !self.is_ancestor(&remote_ref, "HEAD") {
+            outcome.pulled = true;
+            if self.is_ancestor("HEAD", &remote_ref) {
+                self.git(&["merge", "-q", "--ff-only", &remote_ref])?;
+            } else {
+                outcome.conflicts = self.merge_diverged(store, &remote_ref)?;
+            }
+        }
+
+        self.git(&["push", "-q", REMOTE, &format!("HEAD:{}", branch)])?;
+        Ok(outcome)
+    }
+
+    /// Both sides have new commits: merge the task lists and record a merge commit
+    fn merge_diverged(&self, store: &DataStore, remote_ref: &str) -> Result<usize> {
+        let base = match self.git(&["merge-base", "HEAD", remote_ref]) {
+            Ok(base) => self.read_at(store, &base)?,
+            Err(_) => TaskData::default(),
+        };
+        let ours = self.read_at(store, "HEAD")?;
+        let theirs = self.read_at(store, remote_ref)?;
+
+        let base: HashMap<_, _> = base
+            .events
+            .into_iter()
+            .map(|t| (t.id.clone(), t))
+            .collect();
+        let merged = merge_by_id(&base, &ours.events, &theirs.events);
+
+        // Record both parents, then replace the file with the merged task list
+        self.git(&[
+            "merge",
+            "-q",
+            "--no-commit",
+            "--allow-unrelated-histories",
+            "-s",
+            "ours",
+            remote_ref,
+        ])?;
+        let mut data = TaskData {
+            events: merged.tasks,
+        };
+        // Both sides may have appended to the same day
+        data.normalize_orders();
+        let content = store.encode(&data)?;
+        write_atomic(&self.dir.join(&self.file), &content)?;
+        self.git(&["add", "-f", "--", &self.file])?;
+        self.git(&["commit", "-q", "-m", &format!("Merge tasks from {}", remote_ref)])?;
+        Ok(merged.conflicts.len())
+    }
+
+    fn read_at(&self, store: &DataStore, rev: &str) -> Result<TaskData> {
+        match self.git_bytes(&["show", &format!("{}:{}", rev, self.file)]) {
+            Ok(content) => store.decode(&content),
+            Err(_) => Ok(TaskData::default()),
+        }
+    }
+
+    fn is_ancestor(&self, ancestor: &str, descendant: &str) -> bool {
+        self.git(&["merge-base", "--is-ancestor", ancestor, descendant])
+            .is_ok()
+    }
+
+    fn git(&self, args: &[&str]) -> Result<String> {
+        let stdout = self.git_bytes(args)?;
+        Ok(String::from_utf8_lossy(&stdout).trim().to_string())
+    }
+
+    fn git_bytes(&self, args: &[&str]) -> Result<Vec<u8>> {
+        let output = Command::new("git")
+            .current_dir(&self.dir)
+            .arg(format!("--git-dir={}", GIT_DIR))
+            .arg("--work-tree=.")
+            .env_remove("GIT_INDEX_FILE")
+            .args(&self.identity)
+            .args(args)
+            .output()
+            .map_err(|e| eyre!("Failed to run git: {}", e))?;
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            Err(eyre!(
+                "git {} failed: {}",
+                args.first().unwrap_or(&""),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::Task;
+    use crate::undo::Operation;
+    use chrono::Utc;
+    use std::fs;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("taskim-git-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn settings(remote: Option<&Path>) -> GitSettings {
+        GitSettings {
+            enabled: true,
+            remote: remote.map(|r| r.display().to_string()),
+            branch: String::from("main"),
+        }
+    }
+
+    /// A data directory with its history, like the app opens it
+    fn open(dir: &Path, remote: Option<&Path>) -> (GitHistory, DataStore) {
+        let path = dir.join("tasks.json");
+        let history = GitHistory::open(&path, &settings(remote)).unwrap();
+        (history, DataStore::json_at(&path))
+    }
+
+    /// Make a change and save it the way the app does: write the tasks it
+    /// touched, then commit with its description
+    fn save(
+        history: &GitHistory,
+        store: &mut DataStore,
+        change: impl FnOnce(&mut TaskData) -> Operation,
+    ) {
+        let mut data = store.load().unwrap();
+        let operation = change(&mut data);
+        store.mark_dirty(operation.task_ids());
+        store.save(&data).unwrap();
+        history.commit(&operation.describe()).unwrap();
+    }
+
+    fn add(title: &str) -> impl FnOnce(&mut TaskData) -> Operation + '_ {
+        move |data| {
+            let mut task = Task::new(title.to_string(), Utc::now());
+            task.id = title.to_string();
+            data.events.push(task.clone());
+            Operation::CreateTask { task }
+        }
+    }
+
+    fn titles(store: &mut DataStore) -> Vec<String> {
+        let data = store.load().unwrap();
+        let mut titles: Vec<_> = data.events.into_iter().map(|t| t.title).collect();
+        titles.sort();
+        titles
+    }
+
+    #[test]
+    fn saving_commits_with_the_description_of_the_change() {
+        let dir = temp_dir("commit");
+        let (history, mut store) = open(&dir, None);
+        assert!(dir.join(GIT_DIR).is_dir());
+
+        let mut described = String::new();
+        save(&history, &mut store, |data| {
+            let operation = add("Water plants")(data);
+            described = operation.describe();
+            operation
+        });
+        assert_eq!(history.git(&["log", "-1", "--format=%s"]).unwrap(), described);
+        assert_eq!(history.git(&["rev-list", "--count", "HEAD"]).unwrap(), "1");
+
+        // Nothing changed, nothing to commit
+        history.commit("Update tasks").unwrap();
+        assert_eq!(history.git(&["rev-list", "--count", "HEAD"]).unwrap(), "1");
+    }
+
+    #[test]
+    fn sync_merges_task_lists_by_id_between_clones() {
+        let root = temp_dir("sync");
+        let remote = root.join("remote.git");
+        let status = Command::new("git")
+            .args(["init", "-q", "--bare"])
+            .arg(&remote)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let (one, two) = (root.join("one"), root.join("two"));
+        fs::create_dir_all(&one).unwrap();
+        fs::create_dir_all(&two).unwrap();
+        let (history_one, mut store_one) = open(&one, Some(&remote));
+        let (history_two, mut store_two) = open(&two, Some(&remote));
+
+        save(&history_one, &mut store_one, add("a"));
+        assert!(!history_one.sync(&store_one).unwrap().pulled);
+
+        // The second clone has its own history and merges the first's in
+        save(&history_two, &mut store_two, add("b"));
+        let outcome = history_two.sync(&store_two).unwrap();
+        assert!(outcome.pulled);
+        assert_eq!(outcome.conflicts, 0);
+        assert_eq!(titles(&mut store_two), ["a", "b"]);
+
+        // Changes to different tasks on both sides are kept
+        save(&history_one, &mut store_one, |data| {
+            let task = data.events.iter_mut().find(|t| t.id == "a").unwrap();
+            let old_task = task.clone();
+            task.title = String::from("a, renamed");
+            Operation::EditTask {
+                task_id: task.id.clone(),
+                old_task,
+                new_task: task.clone(),
+            }
+        });
+        save(&history_two, &mut store_two, add("c"));
+        history_two.sync(&store_two).unwrap();
+        assert!(history_one.sync(&store_one).unwrap().pulled);
+        assert_eq!(titles(&mut store_one), ["a, renamed", "b", "c"]);
+        history_two.sync(&store_two).unwrap();
+        assert_eq!(titles(&mut store_two), ["a, renamed", "b", "c"]);
+    }
+}
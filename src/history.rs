@@ -0,0 +1,153 @@
+/// Most commands the history keeps
+const HISTORY_SIZE: usize = 500;
+
+/// Commands run from the `:` line, oldest first, kept between sessions
+#[derive(Debug, Default)]
+pub struct CommandHistory {
+    entries: Vec<String>,
+}
+
+impl CommandHistory {
+    /// Remember a command as the newest, moving it there if it was run before
+    pub fn push(&mut self, command: &str) {
+        let command = command.trim();
+        if command.is_empty() {
+            return;
+        }
+        self.entries.retain(|c| c != command);
+        self.entries.push(command.to_string());
+        if self.entries.len() > HISTORY_SIZE {
+            self.entries.remove(0);
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.entries.len() {
+            self.entries.remove(index);
+        }
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The newest command before `index` starting with `prefix` (Up)
+    pub fn previous(&self, index: usize, prefix: &str) -> Option<usize> {
+        self.entries[..index.min(self.entries.len())]
+            .iter()
+            .rposition(|c| c.starts_with(prefix))
+    }
+
+    /// The oldest command after `index` starting with `prefix` (Down)
+    pub fn next(&self, index: usize, prefix: &str) -> Option<usize> {
+        self.entries
+            .iter()
+            .enumerate()
+            .skip(index + 1)
+            .find(|(_, c)| c.starts_with(prefix))
+            .map(|(i, _)| i)
+    }
+
+    /// The newest command before `index` containing `pattern` (Ctrl-r)
+    pub fn search(&self, index: usize, pattern: &str) -> Option<usize> {
+        self.entries[..index.min(self.entries.len())]
+            .iter()
+            .rposition(|c| c.contains(pattern))
+    }
+
+    /// One command per line, oldest first
+    pub fn to_text(&self) -> Vec<u8> {
+        let mut text = self.entries.join("\n");
+        text.push('\n');
+        text.into_bytes()
+    }
+
+    pub fn from_text(content: &[u8]) -> Self {
+        let mut history = Self::default();
+        for line in String::from_utf8_lossy(content).lines() {
+            history.push(line);
+        }
+        history
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(commands: &[&str]) -> CommandHistory {
+        let mut history = CommandHistory::default();
+        for command in commands {
+            history.push(command);
+        }
+        history
+    }
+
+    #[test]
+    fn running_a_command_again_makes_it_the_newest() {
+        let history = history(&["w", " filter #work ", "", "  ", "sort", "w"]);
+        assert_eq!(history.entries(), ["filter #work", "sort", "w"]);
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.get(1), Some("sort"));
+        assert_eq!(history.get(3), None);
+    }
+
+    #[test]
+    fn up_and_down_walk_commands_with_the_typed_prefix() {
+        let history = history(&["sort", "filter a", "w", "filter b", "q"]);
+        let len = history.len();
+        assert_eq!(history.previous(len, ""), Some(4));
+        assert_eq!(history.previous(len, "fil"), Some(3));
+        assert_eq!(history.previous(3, "fil"), Some(1));
+        assert_eq!(history.previous(1, "fil"), None);
+        assert_eq!(history.previous(len + 10, "s"), Some(0));
+
+        assert_eq!(history.next(1, "fil"), Some(3));
+        assert_eq!(history.next(3, "fil"), None);
+        assert_eq!(history.next(0, ""), Some(1));
+        assert_eq!(history.next(len, ""), None);
+    }
+
+    #[test]
+    fn ctrl_r_finds_commands_containing_the_pattern() {
+        let history = history(&["filter #work", "sort", "filter #home", "w"]);
+        assert_eq!(history.search(history.len(), "#"), Some(2));
+        assert_eq!(history.search(2, "#"), Some(0));
+        assert_eq!(history.search(2, "work"), Some(0));
+        assert_eq!(history.search(0, "#"), None);
+        assert_eq!(history.search(history.len(), "nothing"), None);
+    }
+
+    #[test]
+    fn the_oldest_commands_are_forgotten() {
+        let mut history = CommandHistory::default();
+        for n in 0..=HISTORY_SIZE {
+            history.push(&format!("echo {}", n));
+        }
+        assert_eq!(history.len(), HISTORY_SIZE);
+        assert_eq!(history.get(0), Some("echo 1"));
+    }
+
+    #[test]
+    fn removing_and_saving_as_text() {
+        let mut history = history(&["sort", "w", "q"]);
+        history.remove(1);
+        history.remove(7);
+        assert_eq!(history.to_text(), b"sort\nq\n");
+        let loaded = CommandHistory::from_text(b"sort\nq\n\nsort\n");
+        assert_eq!(loaded.entries(), ["q", "sort"]);
+        assert!(CommandHistory::from_text(b"").is_empty());
+    }
+}
@@ -0,0 +1,390 @@
+use crate::task::{Task, TaskComment, TaskData};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use color_eyre::eyre::{eyre, Result};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use uuid::Uuid;
+
+/// Summary of what an import did, including every repair made to the input
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub updated: usize,
+    pub already_present: usize,
+    pub skipped: usize,
+    pub changes: Vec<String>,
+}
+
+impl ImportReport {
+    fn note(&mut self, message: String) {
+        self.changes.push(message);
+    }
+}
+
+/// Read a task-js export from `path`, repair it and merge it into `data`
+pub fn import_task_js<P: AsRef<Path>>(path: P, data: &mut TaskData) -> Result<ImportReport> {
+    let content = fs::read_to_string(&path)?;
+    let mut report = ImportReport::default();
+    let tasks = parse_task_js(&content, &mut report)?;
+    merge_tasks(data, tasks, &mut report);
+    Ok(report)
+}
+
+/// Parse and validate a task-js export, repairing what can be repaired
+pub fn parse_task_js(content: &str, report: &mut ImportReport) -> Result<Vec<Task>> {
+    let root: Value = serde_json::from_str(content)?;
+
+    // task-js saved `{ "events": [...] }`, but accept a bare array as well
+    let events = match &root {
+        Value::Object(map) => map
+            .get("events")
+            .and_then(Value::as_array)
+            .ok_or_else(|| eyre!("Expected an \"events\" array in the task-js export"))?,
+        Value::Array(events) => events,
+        _ => return Err(eyre!("Unrecognized task-js export format")),
+    };
+
+    let mut tasks = Vec::new();
+    let mut seen_ids = HashSet::new();
+    // Renamed duplicates must not take an id used elsewhere in the file
+    let file_ids: HashSet<String> = events.iter().filter_map(event_id).collect();
+    let mut missing_order = Vec::new();
+
+    for (index, event) in events.iter().enumerate() {
+        let Some(fields) = event.as_object() else {
+            report.note(format!("Event #{} is not an object, skipped", index + 1));
+            report.skipped += 1;
+            continue;
+        };
+
+        let title = match fields.get("title").and_then(Value::as_str).map(str::trim) {
+            Some(title) if !title.is_empty() => title.to_string(),
+            _ => {
+                report.note(format!("Event #{} has no title, skipped", index + 1));
+                report.skipped += 1;
+                continue;
+            }
+        };
+
+        let Some(start) = fields.get("start").and_then(parse_datetime) else {
+            report.note(format!("\"{}\" has no valid start date, skipped", title));
+            report.skipped += 1;
+            continue;
+        };
+
+        let end = match fields.get("end").and_then(parse_datetime) {
+            Some(end) if end >= start => end,
+            Some(_) => {
+                report.note(format!("\"{}\" ends before it starts, end reset", title));
+                start + chrono::Duration::hours(1)
+            }
+            None => start + chrono::Duration::hours(1),
+        };
+
+        let mut id = match event_id(event) {
+            Some(id) => id,
+            None => {
+                report.note(format!("\"{}\" has no id, generated a new one", title));
+                Uuid::new_v4().to_string()
+            }
+        };
+        if !seen_ids.insert(id.clone()) {
+            // Derive the replacement from the position so re-importing is idempotent
+            let replacement = (index + 1..)
+                .map(|n| format!("{}-{}", id, n))
+                .find(|candidate| !file_ids.contains(candidate) && !seen_ids.contains(candidate))
+                .unwrap_or_default();
+            report.note(format!(
+                "\"{}\" reuses id {}, renamed to {}",
+                title, id, replacement
+            ));
+            id = replacement;
+            seen_ids.insert(id.clone());
+        }
+
+        let comments = parse_comments(fields.get("comments"));
+        let comments = if comments.len() > 1 {
+            // The task editor only shows a single content field
+            report.note(format!(
+                "\"{}\" had {} comments, merged into one",
+                title,
+                comments.len()
+            ));
+            let text = comments
+                .iter()
+                .map(|c| c.text.as_str())
+                .collect::<Vec<_>>()
+                .join("\n");
+            vec![TaskComment {
+                id: comments[0].id.clone(),
+                text,
+            }]
+        } else {
+            comments
+        };
+
+        let completed = fields
+            .get("completed")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+
+        let order = fields
+            .get("order")
+            .and_then(Value::as_u64)
+            .and_then(|o| u32::try_from(o).ok());
+        if order.is_none() {
+            missing_order.push(tasks.len());
+        }
+
+        tasks.push(Task {
+            id,
+            title,
+            start,
+            end,
+            comments,
+            completed,
+            order: order.unwrap_or(0),
+            list: None,
+            tags: Vec::new(),
+            priority: None,
+        });
+    }
+
+    if !missing_order.is_empty() {
+        report.note(format!(
+            "{} task(s) had no order, ordered by start time",
+            missing_order.len()
+        ));
+        assign_missing_orders(&mut tasks, &missing_order);
+    }
+
+    Ok(tasks)
+}
+
+/// Merge imported tasks into `data`. A task whose id is already there updates
+/// that task, so importing the same export again changes nothing.
+pub fn merge_tasks(data: &mut TaskData, mut tasks: Vec<Task>, report: &mut ImportReport) {
+    tasks.sort_by_key(|t| (t.start.date_naive(), t.order));
+
+    let mut existing: HashMap<String, usize> = data
+        .events
+        .iter()
+        .enumerate()
+        .map(|(index, t)| (t.id.clone(), index))
+        .collect();
+
+    for mut task in tasks {
+        if let Some(&index) = existing.get(&task.id) {
+            let current = &data.events[index];
+            if same_content(current, &task) {
+                report.already_present += 1;
+                continue;
+            }
+            report.note(format!("\"{}\" was changed in the export, updated", task.title));
+            let moved = current.start.date_naive() != task.start.date_naive();
+            let order = match moved {
+                true => data.next_order_for_date(task.start.date_naive()),
+                false => current.order,
+            };
+            // Keep what task-js doesn't know about
+            let current = &mut data.events[index];
+            current.title = task.title;
+            current.start = task.start;
+            current.end = task.end;
+            current.comments = task.comments;
+            current.completed = task.completed;
+            current.order = order;
+            report.updated += 1;
+            continue;
+        }
+
+        // Imported tasks go after the tasks already on that day
+        task.order = data.next_order_for_date(task.start.date_naive());
+        existing.insert(task.id.clone(), data.events.len());
+        data.events.push(task);
+        report.imported += 1;
+    }
+}
+
+fn same_content(a: &Task, b: &Task) -> bool {
+    a.title == b.title
+        && a.start == b.start
+        && a.completed == b.completed
+        && a.comments.iter().map(|c| &c.text).eq(b.comments.iter().map(|c| &c.text))
+}
+
+/// The id of a task-js event, which may be a string or a number
+fn event_id(event: &Value) -> Option<String> {
+    match event.get("id")? {
+        Value::String(id) if !id.is_empty() => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+fn parse_comments(value: Option<&Value>) -> Vec<TaskComment> {
+    let Some(Value::Array(items)) = value else {
+        return vec![];
+    };
+
+    items
+        .iter()
+        .filter_map(|item| match item {
+            Value::String(text) => Some((None, text.clone())),
+            Value::Object(fields) => fields
+                .get("text")
+                .and_then(Value::as_str)
+                .map(|text| (fields.get("id").and_then(Value::as_str), text.to_string())),
+            _ => None,
+        })
+        .filter(|(_, text)| !text.trim().is_empty())
+        .map(|(id, text)| TaskComment {
+            id: id
+                .map(str::to_string)
+                .unwrap_or_else(|| Uuid::new_v4().to_string()),
+            text,
+        })
+        .collect()
+}
+
+fn parse_datetime(value: &Value) -> Option<DateTime<Utc>> {
+    match value {
+        Value::String(s) => {
+            if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+                return Some(dt.to_utc());
+            }
+            if let Ok(dt) = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S") {
+                return Some(dt.and_utc());
+            }
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(9, 0, 0))
+                .map(|dt| dt.and_utc())
+        }
+        // JavaScript timestamps are milliseconds since the epoch
+        Value::Number(n) => n.as_i64().and_then(DateTime::from_timestamp_millis),
+        _ => None,
+    }
+}
+
+fn assign_missing_orders(tasks: &mut [Task], missing: &[usize]) {
+    let mut by_date: HashMap<NaiveDate, Vec<usize>> = HashMap::new();
+    for &index in missing {
+        by_date
+            .entry(tasks[index].start.date_naive())
+            .or_default()
+            .push(index);
+    }
+
+    for (date, mut indices) in by_date {
+        let first_order = tasks
+            .iter()
+            .enumerate()
+            .filter(|(i, t)| t.is_on_date(date) && !missing.contains(i))
+            .map(|(_, t)| t.order + 1)
+            .max()
+            .unwrap_or(0);
+
+        indices.sort_by_key(|&i| tasks[i].start);
+        for (offset, index) in indices.into_iter().enumerate() {
+            tasks[index].order = first_order + offset as u32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> (Vec<Task>, ImportReport) {
+        let mut report = ImportReport::default();
+        let tasks = parse_task_js(content, &mut report).unwrap();
+        (tasks, report)
+    }
+
+    fn import(data: &mut TaskData, content: &str) -> ImportReport {
+        let (tasks, mut report) = parse(content);
+        merge_tasks(data, tasks, &mut report);
+        report
+    }
+
+    const EXPORT: &str = r#"{"events": [
+        {"id": "a", "title": "Dentist", "start": "2025-06-15T15:00:00Z", "order": 0,
+         "comments": [{"id": "c1", "text": "Bring card"}, "Ask about teeth"]},
+        {"id": 7, "title": " Gym ", "start": "2025-06-15", "completed": true},
+        {"id": "b", "title": "Trip", "start": 1749981600000, "end": "2025-06-01T00:00:00Z"}
+    ]}"#;
+
+    #[test]
+    fn reads_task_js_exports() {
+        let (tasks, report) = parse(EXPORT);
+        assert_eq!(tasks.len(), 3);
+        assert_eq!(tasks[0].comments.len(), 1);
+        assert_eq!(tasks[0].comments[0].text, "Bring card\nAsk about teeth");
+        assert_eq!((tasks[1].id.as_str(), tasks[1].title.as_str()), ("7", "Gym"));
+        assert!(tasks[1].completed);
+        assert_eq!(tasks[1].start.format("%F %R").to_string(), "2025-06-15 09:00");
+        // Without an order it goes after the ordered task of its day
+        assert_eq!(tasks[1].order, 1);
+        assert_eq!(tasks[2].end, tasks[2].start + chrono::Duration::hours(1));
+        assert_eq!(report.changes.len(), 3);
+
+        let (tasks, _) = parse(r#"[{"title": "Bare", "start": "2025-06-15"}]"#);
+        assert_eq!(tasks[0].title, "Bare");
+    }
+
+    #[test]
+    fn rejects_or_skips_invalid_input() {
+        let mut report = ImportReport::default();
+        assert!(parse_task_js("not json", &mut report).is_err());
+        assert!(parse_task_js(r#"{"tasks": []}"#, &mut report).is_err());
+        assert!(parse_task_js("42", &mut report).is_err());
+
+        let (tasks, report) = parse(
+            r#"[1, {"title": "  ", "start": "2025-06-15"}, {"title": "When?", "start": "soon"},
+                {"title": "Ok", "start": "2025-06-15"}]"#,
+        );
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(report.skipped, 3);
+    }
+
+    #[test]
+    fn renamed_duplicate_ids_do_not_clash() {
+        let (tasks, _) = parse(
+            r#"[{"id": "x", "title": "One", "start": "2025-06-15"},
+                {"id": "x", "title": "Two", "start": "2025-06-15"},
+                {"id": "x-2", "title": "Three", "start": "2025-06-15"}]"#,
+        );
+        let ids: HashSet<_> = tasks.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids.len(), 3);
+        assert_eq!(tasks[2].id, "x-2");
+    }
+
+    #[test]
+    fn importing_again_changes_nothing() {
+        let mut data = TaskData::default();
+        let first = import(&mut data, EXPORT);
+        assert_eq!(first.imported, 3);
+        let before = serde_json::to_string(&data).unwrap();
+
+        let again = import(&mut data, EXPORT);
+        assert_eq!((again.imported, again.updated, again.already_present), (0, 0, 3));
+        assert_eq!(serde_json::to_string(&data).unwrap(), before);
+    }
+
+    #[test]
+    fn a_changed_task_updates_the_one_with_its_id() {
+        let mut data = TaskData::default();
+        import(&mut data, EXPORT);
+        data.events[0].tags = vec![String::from("health")];
+
+        let changed = EXPORT.replace("\"Dentist\"", "\"Dentist at 4\"");
+        let report = import(&mut data, &changed);
+        assert_eq!((report.imported, report.updated), (0, 1));
+        assert_eq!(data.events.len(), 3);
+        assert_eq!(data.events[0].title, "Dentist at 4");
+        assert_eq!(data.events[0].tags, ["health"]);
+    }
+}
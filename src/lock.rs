@@ -0,0 +1,127 @@
+use color_eyre::eyre::Result;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// What to do when another taskim already holds the data file, set with `lock_mode:`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LockMode {
+    #[default]
+    ReadOnly,
+    Wait,
+}
+
+impl LockMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "readonly" | "read-only" | "read_only" => Some(LockMode::ReadOnly),
+            "wait" => Some(LockMode::Wait),
+            _ => None,
+        }
+    }
+}
+
+/// Advisory lock on the data file, held on `<data_path>.lock` until dropped.
+/// The lock file records the holder's PID for messages only; the lock itself
+/// is an `flock`, which the system releases when the holder exits.
+#[derive(Debug)]
+pub struct DataLock {
+    _file: File,
+}
+
+pub enum LockStatus {
+    Acquired(DataLock),
+    /// Another process holds the lock
+    HeldBy(u32),
+}
+
+impl DataLock {
+    /// Lock `data_path`, either giving up right away or waiting for the holder
+    /// to exit depending on `mode`
+    pub fn acquire(data_path: &Path, mode: LockMode) -> Result<LockStatus> {
+        Self::lock(data_path, mode == LockMode::Wait)
+    }
+
+    /// Try to lock `data_path` without waiting
+    pub fn try_acquire(data_path: &Path) -> Result<LockStatus> {
+        Self::lock(data_path, false)
+    }
+
+    fn lock(data_path: &Path, wait: bool) -> Result<LockStatus> {
+        let path = lock_path(data_path);
+        // Never truncated before locking, so the holder's PID stays readable
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        if !lock_file(&file, wait)? {
+            return Ok(LockStatus::HeldBy(read_pid(&path).unwrap_or_default()));
+        }
+        file.set_len(0)?;
+        write!(file, "{}", std::process::id())?;
+        Ok(LockStatus::Acquired(DataLock { _file: file }))
+    }
+}
+
+fn lock_path(data_path: &Path) -> PathBuf {
+    let mut name = data_path.as_os_str().to_owned();
+    name.push(".lock");
+    PathBuf::from(name)
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Take an exclusive lock on `file`. Returns false if it is held elsewhere
+/// and `wait` is not set.
+#[cfg(unix)]
+fn lock_file(file: &File, wait: bool) -> std::io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    let operation = match wait {
+        true => libc::LOCK_EX,
+        false => libc::LOCK_EX | libc::LOCK_NB,
+    };
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+            return Ok(true);
+        }
+        let error = std::io::Error::last_os_error();
+        match error.raw_os_error() {
+            Some(libc::EINTR) => continue,
+            Some(libc::EWOULDBLOCK) => return Ok(false),
+            _ => return Err(error),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn lock_file(_file: &File, _wait: bool) -> std::io::Result<bool> {
+    // No advisory locking without libc; every instance is a writer
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_second_lock_is_refused_until_the_first_is_dropped() {
+        let dir = std::env::temp_dir().join(format!("taskim-lock-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let data = dir.join("tasks.json");
+
+        let first = DataLock::try_acquire(&data).unwrap();
+        assert!(matches!(first, LockStatus::Acquired(_)));
+        match DataLock::try_acquire(&data).unwrap() {
+            LockStatus::HeldBy(pid) => assert_eq!(pid, std::process::id()),
+            LockStatus::Acquired(_) => panic!("locked twice"),
+        }
+        drop(first);
+        assert!(matches!(DataLock::try_acquire(&data).unwrap(), LockStatus::Acquired(_)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
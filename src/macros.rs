@@ -0,0 +1,149 @@
+use crossterm::event::KeyEvent;
+use std::collections::HashMap;
+
+/// How deep macros may play other macros before playback is stopped
+pub const MAX_DEPTH: usize = 20;
+
+/// Keyboard macros recorded with `q{reg}` and played back with `@{reg}`
+#[derive(Debug, Default)]
+pub struct Macros {
+    registers: HashMap<char, Vec<KeyEvent>>,
+    recording: Option<(char, Vec<KeyEvent>)>,
+    last_played: Option<char>,
+}
+
+impl Macros {
+    pub fn is_valid(name: char) -> bool {
+        name.is_ascii_alphanumeric()
+    }
+
+    /// Start recording into `name`; an uppercase name appends to the lowercase one
+    pub fn start(&mut self, name: char) {
+        let register = name.to_ascii_lowercase();
+        let keys = if name.is_ascii_uppercase() {
+            self.registers.get(&register).cloned().unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        self.recording = Some((register, keys));
+    }
+
+    /// Stop recording, returning the register the keys went to
+    pub fn stop(&mut self) -> Option<char> {
+        let (name, keys) = self.recording.take()?;
+        self.registers.insert(name, keys);
+        Some(name)
+    }
+
+    /// The register being recorded into, if any
+    pub fn recording(&self) -> Option<char> {
+        self.recording.as_ref().map(|(name, _)| *name)
+    }
+
+    pub fn record(&mut self, key: KeyEvent) {
+        if let Some((_, keys)) = &mut self.recording {
+            keys.push(key);
+        }
+    }
+
+    /// Keys of the macro in `name` for playback; `@` is the last one played
+    pub fn keys(&mut self, name: char) -> Option<Vec<KeyEvent>> {
+        let name = match name {
+            '@' => self.last_played?,
+            name => name.to_ascii_lowercase(),
+        };
+        let keys = self.registers.get(&name).filter(|keys| !keys.is_empty())?;
+        self.last_played = Some(name);
+        Some(keys.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyModifiers};
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    fn record(macros: &mut Macros, name: char, keys: &str) {
+        macros.start(name);
+        for c in keys.chars() {
+            macros.record(key(c));
+        }
+        assert_eq!(macros.stop(), Some(name.to_ascii_lowercase()));
+    }
+
+    fn played(macros: &mut Macros, name: char) -> Option<String> {
+        let keys = macros.keys(name)?;
+        Some(
+            keys.iter()
+                .map(|k| match k.code {
+                    KeyCode::Char(c) => c,
+                    _ => '?',
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn recorded_keys_play_back_until_replaced() {
+        let mut macros = Macros::default();
+        assert_eq!(macros.recording(), None);
+        macros.start('a');
+        assert_eq!(macros.recording(), Some('a'));
+        macros.record(key('j'));
+        macros.record(key('x'));
+        assert_eq!(macros.stop(), Some('a'));
+        assert_eq!(macros.recording(), None);
+        assert_eq!(macros.stop(), None);
+
+        // A count plays the same keys again each time
+        for _ in 0..3 {
+            assert_eq!(played(&mut macros, 'a').as_deref(), Some("jx"));
+        }
+        record(&mut macros, 'a', "dd");
+        assert_eq!(played(&mut macros, 'a').as_deref(), Some("dd"));
+    }
+
+    #[test]
+    fn uppercase_names_append() {
+        let mut macros = Macros::default();
+        record(&mut macros, 'b', "j");
+        record(&mut macros, 'B', "x");
+        assert_eq!(played(&mut macros, 'b').as_deref(), Some("jx"));
+        assert_eq!(played(&mut macros, 'B').as_deref(), Some("jx"));
+        record(&mut macros, 'C', "k");
+        assert_eq!(played(&mut macros, 'c').as_deref(), Some("k"));
+    }
+
+    #[test]
+    fn at_sign_replays_the_last_macro_played() {
+        let mut macros = Macros::default();
+        assert_eq!(played(&mut macros, '@'), None);
+        record(&mut macros, 'a', "j");
+        record(&mut macros, 'b', "k");
+        assert_eq!(played(&mut macros, '@'), None);
+        played(&mut macros, 'b');
+        assert_eq!(played(&mut macros, '@').as_deref(), Some("k"));
+        played(&mut macros, 'a');
+        assert_eq!(played(&mut macros, '@').as_deref(), Some("j"));
+
+        // Missing and empty macros leave the last one played alone
+        record(&mut macros, 'e', "");
+        assert_eq!(played(&mut macros, 'e'), None);
+        assert_eq!(played(&mut macros, 'z'), None);
+        assert_eq!(played(&mut macros, '@').as_deref(), Some("j"));
+    }
+
+    #[test]
+    fn register_names() {
+        for name in ['a', 'Z', '0'] {
+            assert!(Macros::is_valid(name), "{}", name);
+        }
+        for name in ['@', ':', '"', ' '] {
+            assert!(!Macros::is_valid(name), "{}", name);
+        }
+    }
+}
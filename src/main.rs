@@ -1,36 +1,76 @@
+mod cli;
+mod clipboard;
+mod cmdline;
 mod commands;
 mod config;
+mod crypto;
 mod data;
+mod dates;
+mod filter;
+mod git;
+mod history;
+mod import;
+mod lock;
+mod macros;
+mod marks;
+mod merge;
 mod month_view;
+mod quick_add;
+mod registers;
+mod repeat;
+mod smart_lists;
+mod snooze;
+mod substitute;
 mod task;
 mod task_edit;
 mod undo;
 mod utils;
 
-use crate::data::{load_data, save_data};
+use crate::cmdline::{Address, Completion, DateRange, RangeSpec};
+use crate::data::{DataStore, StorageKind, HISTORY, MARKS, UNDO_JOURNAL};
+use crate::git::GitHistory;
+use crate::history::CommandHistory;
+use crate::lock::{DataLock, LockStatus};
+use crate::macros::Macros;
+use crate::marks::{JumpList, Marks, Position};
 use crate::month_view::{render_month_view, MonthView, SelectionType};
-use crate::task::TaskData;
-use crate::task_edit::{render_task_edit_popup, TaskEditState};
-use crate::undo::{Operation, UndoStack};
+use crate::registers::Registers;
+use crate::repeat::{Change, Count, InsertPosition, LastChange};
+use crate::smart_lists::{
+    render_smart_lists, FilteredList, SmartListsAction, SmartListsState, SIDEBAR_WIDTH,
+};
+use crate::snooze::{render_snooze_popup, SnoozeAction, SnoozeState};
+use crate::substitute::{Global, Substitution};
+use crate::task::{Task, TaskData};
+use crate::task_edit::{render_task_edit_popup, EditingField, TaskEditState};
+use crate::undo::{Operation, UndoDistance, UndoTree};
 use crate::utils::days_in_month;
-use commands::get_command_registry;
+use commands::{get_command_registry, Complete};
 
-use chrono::{Datelike, Local, Timelike};
+use chrono::{Datelike, Local, NaiveDate};
 use color_eyre::Result;
 use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
 use ratatui::{
     layout::{Constraint, Layout, Rect},
     style::Style,
     text::{Line, Span},
-    widgets::Paragraph,
+    widgets::{Block, Borders, Clear, Paragraph},
     DefaultTerminal, Frame,
 };
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+const FILE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const UNDO_TREE_WIDTH: u16 = 60;
 
 #[derive(Debug, Clone, PartialEq)]
 enum AppMode {
     Normal,
     TaskEdit(TaskEditState),
     Command(CommandState),
+    CommandWindow(CommandWindowState),
+    Snooze(SnoozeState),
+    SmartLists(SmartListsState),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -39,6 +79,10 @@ struct CommandState {
     cursor_position: usize,
     show_help: bool,
     last_error: Option<String>,
+    completion: Option<Completion>, // Candidates Tab is cycling through
+    history_index: Option<usize>,   // History entry recalled with Up/Down or Ctrl-r
+    history_prefix: String,         // What was typed before recalling history
+    search: Option<String>,         // Pattern of a Ctrl-r search in progress
 }
 
 impl CommandState {
@@ -48,6 +92,10 @@ impl CommandState {
             cursor_position: 0,
             show_help: false,
             last_error: None,
+            completion: None,
+            history_index: None,
+            history_prefix: String::new(),
+            search: None,
         }
     }
 
@@ -72,59 +120,1580 @@ impl CommandState {
             self.cursor_position += 1;
         }
     }
+
+    /// Replace the input with a recalled command, the cursor at its end
+    fn recall(&mut self, command: &str) {
+        self.input = command.to_string();
+        self.cursor_position = self.input.len();
+    }
+}
+
+/// The q: window listing the command history
+#[derive(Debug, Clone, PartialEq)]
+struct CommandWindowState {
+    selected: usize,
 }
 
 struct App {
     mode: AppMode,
     data: TaskData,
+    store: DataStore,
     month_view: MonthView,
     should_exit: bool,
-    undo_stack: UndoStack,
-    yanked_task: Option<crate::task::Task>, // Store yanked task for paste operation
+    undo_tree: UndoTree,
+    registers: Registers,                   // Yanked and cut tasks for pasting
+    register: Option<char>,                 // Register chosen with '"' for the next key
     pending_key: Option<char>,              // For handling multi-key sequences like 'gg'
-    pending_insert_order: Option<u32>,      // For tracking task insertion order
+    pending_insert: Option<(InsertPosition, usize)>, // Where and how often the task being typed goes
+    count: Count,                           // Count typed before a normal-mode key
+    last_change: Option<LastChange>,        // What `.` repeats
     scramble_mode: bool,                    // Toggle for scrambling task names with numbers
     config: crate::config::Config,          // <-- add config field
     show_keybinds: bool,                    // runtime toggle for keybind help
+    status_message: Option<String>,         // Warning shown in the footer until the next key
+    lock: Option<DataLock>,                 // Held while we are the writer of the data file
+    locked_by: Option<u32>,                 // PID of the other instance when opened read-only
+    history: Option<GitHistory>,            // Git repository versioning the data file
+    pending_change: Option<String>,         // Description of the change the next save commits
+    change_passphrase: bool,                // Prompt for a new passphrase outside the TUI
+    hidden_lists: HashSet<String>,          // Lists whose tasks are left out of the grid
+    show_undo_tree: bool,                   // Show the undo tree panel next to the calendar
+    marks: Marks,                           // Positions saved with 'm{a-z}'
+    jumps: JumpList,                        // Positions left by large moves, for Ctrl-o/Ctrl-i
+    macros: Macros,                         // Key sequences recorded with 'q{reg}'
+    macro_depth: usize,                     // Macros being played, to stop runaway recursion
+    command_history: CommandHistory,        // Commands run from the ':' line
+    smart_list: usize,                      // List the smart lists sidebar last showed
+    loaded: Option<(NaiveDate, NaiveDate)>, // Days read while only browsing, None once all are
 }
 
 impl App {
-    fn new() -> Self {
-        let data = load_data();
+    fn new() -> Result<Self> {
+        let config = crate::config::Config::load("config.yml")?;
+        let mut store = DataStore::open(config.storage, &config.lists)?;
+        let (lock, locked_by) = match DataLock::acquire(store.path(), config.lock_mode)? {
+            LockStatus::Acquired(lock) => (Some(lock), None),
+            LockStatus::HeldBy(pid) => (None, Some(pid)),
+        };
+        // A read-only instance leaves encrypting a plain file to the writer
+        let encrypted_now = crypto::unlock(&mut store, config.encrypt && locked_by.is_none())?;
+        // Only the days on screen are read until something needs the rest
         let current_date = Local::now().date_naive();
         let month_view = MonthView::new(current_date);
-        let config = crate::config::Config::from_file_or_default("config.yml");
+        let (from, to) = month_view.shown_days();
+        let data = TaskData {
+            events: store.load_range(from, to)?,
+        };
+        let history = if config.git.enabled {
+            if store.kind() != StorageKind::Json {
+                return Err(color_eyre::eyre::eyre!(
+                    "Git history requires `storage: json` in config.yml"
+                ));
+            }
+            Some(GitHistory::open(store.path(), &config.git)?)
+        } else {
+            None
+        };
+        let mut undo_tree = UndoTree::new(config.undo_history.levels);
+        let mut status_message = None;
+        if config.undo_history.persist {
+            let journal = store
+                .read_sidecar(UNDO_JOURNAL)
+                .and_then(|content| match content {
+                    Some(content) => Ok(Some(UndoTree::from_journal(
+                        &content,
+                        config.undo_history.levels,
+                        chrono::Duration::days(config.undo_history.max_age_days),
+                    )?)),
+                    None => Ok(None),
+                });
+            match journal {
+                // Checked against the tasks once they are all loaded
+                Ok(Some(stack)) => undo_tree = stack,
+                Ok(None) => {}
+                Err(e) => {
+                    status_message = Some(format!("Ignoring unreadable undo journal: {}", e));
+                }
+            }
+        }
+        let marks = match store.read_sidecar(MARKS) {
+            Ok(Some(content)) => Marks::from_json(&content).unwrap_or_else(|e| {
+                status_message = Some(format!("Ignoring unreadable marks file: {}", e));
+                Marks::default()
+            }),
+            Ok(None) => Marks::default(),
+            Err(e) => {
+                status_message = Some(format!("Ignoring unreadable marks file: {}", e));
+                Marks::default()
+            }
+        };
+        let command_history = match store.read_sidecar(HISTORY) {
+            Ok(content) => content
+                .map(|content| CommandHistory::from_text(&content))
+                .unwrap_or_default(),
+            Err(e) => {
+                status_message = Some(format!("Ignoring unreadable command history: {}", e));
+                CommandHistory::default()
+            }
+        };
+        if encrypted_now && history.is_some() {
+            status_message = Some(format!(
+                "The data file is now encrypted, but {}",
+                crypto::PLAINTEXT_HISTORY_WARNING
+            ));
+        }
         let show_keybinds = config.show_keybinds;
-        Self {
+        let hidden_lists = config
+            .lists
+            .iter()
+            .filter(|l| !l.visible)
+            .map(|l| l.name.clone())
+            .collect();
+        let mut app = Self {
             mode: AppMode::Normal,
             data,
+            store,
             month_view,
             should_exit: false,
-            undo_stack: UndoStack::new(50), // Allow up to 50 undo operations
-            yanked_task: None,
+            undo_tree,
+            registers: Registers::default(),
+            register: None,
             pending_key: None,
-            pending_insert_order: None,
+            pending_insert: None,
+            count: Count::default(),
+            last_change: None,
             scramble_mode: false,
             config,
             show_keybinds,
+            status_message,
+            lock,
+            locked_by,
+            history,
+            pending_change: None,
+            change_passphrase: false,
+            hidden_lists,
+            show_undo_tree: false,
+            marks,
+            jumps: JumpList::default(),
+            macros: Macros::default(),
+            macro_depth: 0,
+            command_history,
+            smart_list: 0,
+            loaded: Some((from, to)),
+        };
+        if app.config.rollover_on_startup && app.locked_by.is_none() {
+            app.load_everything()?;
+            match app.rollover() {
+                Ok(0) => {}
+                Ok(count) => {
+                    app.status_message = Some(format!("Rolled over {} task(s) to today", count))
+                }
+                Err(e) => app.status_message = Some(e),
+            }
+        }
+        Ok(app)
+    }
+
+    /// Read the days the calendar shows that weren't read yet
+    fn load_shown_days(&mut self) -> Result<()> {
+        let Some((loaded_from, loaded_to)) = self.loaded else {
+            return Ok(());
+        };
+        // Days read now must not mix with the file as it was before
+        if self.store.changed_on_disk() {
+            return self.load_everything();
+        }
+        let (from, to) = self.month_view.shown_days();
+        if from < loaded_from {
+            let tasks = self.store.load_range(from, loaded_from - chrono::Duration::days(1))?;
+            self.data.events.extend(tasks);
+        }
+        if to > loaded_to {
+            let tasks = self.store.load_range(loaded_to + chrono::Duration::days(1), to)?;
+            self.data.events.extend(tasks);
+        }
+        self.loaded = Some((from.min(loaded_from), to.max(loaded_to)));
+        Ok(())
+    }
+
+    /// Read every task, for anything besides browsing the calendar
+    fn load_everything(&mut self) -> Result<()> {
+        if self.loaded.is_none() {
+            return Ok(());
+        }
+        // Nothing can have changed while browsing, so the file's tasks are ours
+        self.data = self.store.load()?;
+        self.loaded = None;
+        if self.undo_tree.retain_consistent(&self.data) {
+            self.status_message = Some(String::from(
+                "The data file changed since the last session; older undo history was dropped",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether `key` only moves around the calendar, which needs no more
+    /// than the days on screen
+    fn browses_calendar(&self, key: crossterm::event::KeyEvent) -> bool {
+        if !matches!(self.mode, AppMode::Normal) || self.pending_key.is_some() {
+            return false;
+        }
+        if matches!(key.code, KeyCode::Char('1'..='9')) && key.modifiers == KeyModifiers::NONE {
+            return true;
+        }
+        [
+            &self.config.move_left,
+            &self.config.move_down,
+            &self.config.move_up,
+            &self.config.move_right,
+            &self.config.next_month,
+            &self.config.prev_month,
+            &self.config.next_year,
+            &self.config.prev_year,
+            &self.config.next_week,
+            &self.config.prev_week,
+            &self.config.first_day_of_month,
+            &self.config.last_day_of_month,
+            &self.config.go_to_today,
+            &self.config.quit,
+            &self.config.quit_alt,
+            &self.config.force_quit,
+        ]
+        .iter()
+        .any(|binding| binding.matches(key.code, key.modifiers))
+    }
+
+    /// Tasks of the lists currently shown in the grid
+    fn visible_tasks(&self) -> Vec<&Task> {
+        shown_tasks(&self.data, &self.hidden_lists, &self.config)
+    }
+
+    /// Move the selection `times` times among the tasks shown in the grid
+    fn navigate(&mut self, times: usize, step: fn(&mut MonthView, &[&Task])) {
+        let tasks = shown_tasks(&self.data, &self.hidden_lists, &self.config);
+        for _ in 0..times {
+            step(&mut self.month_view, &tasks);
+        }
+    }
+
+    /// Names of the configured lists followed by any others tasks refer to
+    fn list_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.config.lists.iter().map(|l| l.name.clone()).collect();
+        if names.is_empty() {
+            names.push(self.config.default_list_name().to_string());
+        }
+        for task in &self.data.events {
+            let name = self.config.list_name(task);
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+        }
+        names
+    }
+
+    /// Show or hide a list in the grid
+    fn set_list_visible(&mut self, name: &str, visible: bool) -> Result<(), String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(String::from("Usage: :show <list> or :hide <list>"));
+        }
+        let Some(name) = self
+            .list_names()
+            .into_iter()
+            .find(|n| n.eq_ignore_ascii_case(name))
+        else {
+            return Err(format!("No list named {}. Type ':lists' to see them.", name));
+        };
+
+        if visible {
+            self.hidden_lists.remove(&name);
+        } else {
+            // Don't leave a hidden task selected
+            let selected_date = self.month_view.get_selected_date(&self.data.events);
+            if let Some(task_id) = self.month_view.get_selected_task_id() {
+                if let Some(task) = self.data.events.iter().find(|t| t.id == task_id) {
+                    if self.config.list_name(task) == name {
+                        self.month_view.selection = month_view::Selection {
+                            selection_type: SelectionType::Day(selected_date),
+                        };
+                    }
+                }
+            }
+            self.hidden_lists.insert(name);
+        }
+        Ok(())
+    }
+
+    /// Summary of the lists for `:lists`
+    fn describe_lists(&self) -> String {
+        let mut text = String::from("Lists:\n");
+        for name in self.list_names() {
+            let count = self
+                .data
+                .events
+                .iter()
+                .filter(|t| self.config.list_name(t) == name)
+                .count();
+            let file = self
+                .config
+                .lists
+                .iter()
+                .find(|l| l.name == name)
+                .and_then(|l| l.file.as_ref())
+                .map(|f| format!(", {}", f.display()))
+                .unwrap_or_default();
+            let state = if self.hidden_lists.contains(&name) {
+                "hidden"
+            } else {
+                "shown"
+            };
+            text.push_str(&format!("  {:<15} {} task(s), {}{}\n", name, count, state, file));
+        }
+        text
+    }
+
+    fn save(&mut self) -> Result<()> {
+        // Never overwrite changes someone else made to the file since we loaded it.
+        // If it can't be read right now, keep our changes and retry on the next poll.
+        if !self.merge_external_changes() || self.locked_by.is_some() {
+            return Ok(());
+        }
+        self.store.save(&self.data)?;
+        self.save_undo_journal();
+
+        if let Some(history) = &self.history {
+            let message = self
+                .pending_change
+                .take()
+                .unwrap_or_else(|| String::from("Update tasks"));
+            if let Err(e) = history.commit(&message) {
+                self.status_message = Some(e.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Persist the undo history next to the data file
+    fn save_undo_journal(&mut self) {
+        if !self.config.undo_history.persist || self.locked_by.is_some() {
+            return;
+        }
+        let result = self
+            .undo_tree
+            .to_journal()
+            .map_err(color_eyre::eyre::Report::from)
+            .and_then(|content| self.store.write_sidecar(UNDO_JOURNAL, content));
+        if let Err(e) = result {
+            self.status_message = Some(format!("Could not save the undo journal: {}", e));
+        }
+    }
+
+    /// Track an operation for undo and describe it in the next history commit
+    fn record(&mut self, operation: Operation) {
+        self.store.mark_dirty(operation.task_ids());
+        self.pending_change = Some(operation.describe());
+        self.undo_tree.push(operation);
+    }
+
+    /// Run a change on the data and return its undo operation, bundled with
+    /// the order shifts it caused among the shown lists
+    fn tracked(
+        &mut self,
+        change: impl FnOnce(&mut TaskData) -> Option<Operation>,
+    ) -> Option<Operation> {
+        let before = self.data.clone();
+        let operation = change(&mut self.data)?;
+
+        // Tasks of hidden lists keep their order unless the change is theirs
+        let own: HashSet<&str> = operation.task_ids().into_iter().collect();
+        for task in self.data.events.iter_mut() {
+            if own.contains(task.id.as_str())
+                || !self.hidden_lists.contains(self.config.list_name(task))
+            {
+                continue;
+            }
+            if let Some(old) = before.events.iter().find(|t| t.id == task.id) {
+                task.order = old.order;
+            }
+        }
+
+        let operation = operation.with_reorder(&before, &self.data);
+        self.store.mark_dirty(operation.task_ids());
+        Some(operation)
+    }
+
+    /// Make a change from a normal-mode key and remember it for `.`
+    fn make_change(&mut self, change: Change, count: usize) -> Result<()> {
+        if self.apply_change(&change, count)? {
+            self.last_change = Some(LastChange { change, count });
+        }
+        Ok(())
+    }
+
+    /// Make a change `count` times as a single undo step; false if nothing changed
+    fn apply_change(&mut self, change: &Change, count: usize) -> Result<bool> {
+        let operations = match change {
+            Change::ToggleComplete => self.toggle_tasks(count),
+            Change::Delete { register } => self.delete_tasks(count, *register),
+            Change::Paste { above, register } => {
+                let Some(yanked) = self.registers.get(*register).map(<[Task]>::to_vec) else {
+                    return Ok(false);
+                };
+                let position = if *above {
+                    InsertPosition::Above
+                } else {
+                    InsertPosition::Below
+                };
+                self.insert_tasks(position, count * yanked.len(), |date, i| {
+                    // A copy with a new id at the same time on the selected day
+                    let mut task = yanked[i % yanked.len()].clone();
+                    task.id = uuid::Uuid::new_v4().to_string();
+                    let duration = task.end - task.start;
+                    task.start = date.and_time(task.start.time()).and_utc();
+                    task.end = task.start + duration;
+                    task
+                })
+            }
+            Change::Insert {
+                position,
+                title,
+                content,
+                list,
+            } => self.insert_tasks(*position, count, |date, _| {
+                let mut state = TaskEditState::new_task(date);
+                state.title = title.clone();
+                state.content = content.clone();
+                state.list = list.clone();
+                state.to_task()
+            }),
+            Change::Reorder { down } => (0..count)
+                .map_while(|_| self.reorder_task(*down))
+                .collect(),
+            Change::Move { days } => {
+                let days = days * count as i64;
+                let tasks = self.selected_task().into_iter().collect();
+                self.move_tasks(tasks, |date| date + chrono::Duration::days(days))
+            }
+        };
+        let Some(operation) = Operation::batch(operations) else {
+            return Ok(false);
+        };
+        self.record(operation);
+        self.save()?;
+        Ok(true)
+    }
+
+    /// The selected task and the visible tasks below it on its day, `count` in all
+    fn selected_tasks(&self, count: usize) -> Vec<String> {
+        let Some(task_id) = self.month_view.get_selected_task_id() else {
+            return Vec::new();
+        };
+        let mut day = self.visible_tasks();
+        let Some(date) = day
+            .iter()
+            .find(|t| t.id == task_id)
+            .map(|t| t.start.date_naive())
+        else {
+            return Vec::new();
+        };
+        day.retain(|t| t.is_on_date(date));
+        day.sort_by_key(|t| t.order);
+        day.into_iter()
+            .map(|t| t.id.clone())
+            .skip_while(|id| *id != task_id)
+            .take(count)
+            .collect()
+    }
+
+    fn toggle_tasks(&mut self, count: usize) -> Vec<Operation> {
+        let mut operations = Vec::new();
+        for task_id in self.selected_tasks(count) {
+            operations.extend(self.tracked(|data| {
+                let task = data.events.iter_mut().find(|t| t.id == task_id)?;
+                task.completed = !task.completed;
+                Some(Operation::ToggleComplete {
+                    task_id: task.id.clone(),
+                    title: task.title.clone(),
+                    completed: task.completed,
+                })
+            }));
+        }
+        operations
+    }
+
+    /// Swap the selected task with its neighbour below or above, or carry it
+    /// to the start of the next day or the end of the previous one, keeping
+    /// it selected
+    fn reorder_task(&mut self, down: bool) -> Option<Operation> {
+        let task = self.selected_task().ok()?;
+        let date = task.start.date_naive();
+        let mut day: Vec<&Task> = self
+            .visible_tasks()
+            .into_iter()
+            .filter(|t| t.is_on_date(date))
+            .collect();
+        day.sort_by_key(|t| t.order);
+        let position = day.iter().position(|t| t.id == task.id)?;
+        let neighbour = if down {
+            day.get(position + 1)
+        } else {
+            position.checked_sub(1).map(|i| &day[i])
+        };
+
+        let (operation, date) = match neighbour {
+            Some(neighbour) => {
+                // Number the shown day 0, 1, 2, ... before swapping, as tasks
+                // sharing an order would not move; hidden lists keep theirs
+                let mut orders: HashMap<&str, u32> = day
+                    .iter()
+                    .enumerate()
+                    .map(|(index, t)| (t.id.as_str(), index as u32))
+                    .collect();
+                let (ours, theirs) = (orders[task.id.as_str()], orders[neighbour.id.as_str()]);
+                orders.insert(&task.id, theirs);
+                orders.insert(&neighbour.id, ours);
+                let changes = day
+                    .iter()
+                    .filter(|t| orders[t.id.as_str()] != t.order)
+                    .map(|t| (t.id.clone(), t.order, orders[t.id.as_str()]))
+                    .collect();
+                let operation = Operation::Reorder { changes };
+                operation.apply(&mut self.data);
+                (operation, date)
+            }
+            None => {
+                let offset = chrono::Duration::days(if down { 1 } else { -1 });
+                let new_date = date + offset;
+                let operation = self.tracked(|data| {
+                    let mut moved = data.remove_task_and_reorder(&task.id)?;
+                    moved.start = task.start + offset;
+                    moved.end = task.end + offset;
+                    let order = if down {
+                        data.events
+                            .iter()
+                            .filter(|t| t.is_on_date(new_date))
+                            .map(|t| t.order)
+                            .min()
+                            .unwrap_or(0)
+                    } else {
+                        data.next_order_for_date(new_date)
+                    };
+                    data.insert_task_at_order(moved.clone(), order);
+                    Some(Operation::MoveTask {
+                        task_id: task.id.clone(),
+                        title: task.title.clone(),
+                        old_start: task.start,
+                        old_end: task.end,
+                        new_start: moved.start,
+                        new_end: moved.end,
+                    })
+                })?;
+                (operation, new_date)
+            }
+        };
+        self.month_view.jump_to(date, Some(task.id));
+        Some(operation)
+    }
+
+    /// The selected task and the ones below it, `count` in all, for yanking
+    fn selected_task_copies(&self, count: usize) -> Vec<Task> {
+        self.selected_tasks(count)
+            .iter()
+            .filter_map(|id| self.data.events.iter().find(|t| &t.id == id).cloned())
+            .collect()
+    }
+
+    /// Cut the selected tasks into `register` or the delete ring
+    fn delete_tasks(&mut self, count: usize, register: Option<char>) -> Vec<Operation> {
+        let task_ids = self.selected_tasks(count);
+        self.cut_tasks(task_ids, register)
+    }
+
+    /// Cut tasks into `register` or the delete ring, then select what is
+    /// left of the day of the first
+    fn cut_tasks(&mut self, task_ids: Vec<String>, register: Option<char>) -> Vec<Operation> {
+        let mut operations = Vec::new();
+        let mut deleted = Vec::new();
+        for task_id in task_ids {
+            operations.extend(self.tracked(|data| {
+                let task = data.remove_task_and_reorder(&task_id)?;
+                deleted.push(task.clone());
+                Some(Operation::DeleteTask { task })
+            }));
         }
+        let Some(task_date) = deleted.first().map(|t| t.start.date_naive()) else {
+            return operations;
+        };
+
+        // Store the cut tasks for pasting
+        self.registers.delete(register, deleted);
+
+        // Check if there are any remaining tasks on the same date
+        let remaining_tasks = self.data.get_tasks_for_date(task_date);
+        self.month_view.selection = match remaining_tasks.first() {
+            // Select the first remaining task (ordered)
+            Some(task) => month_view::Selection {
+                selection_type: month_view::SelectionType::Task(task.id.clone()),
+            },
+            // No more tasks on this day, select the day itself
+            None => month_view::Selection {
+                selection_type: month_view::SelectionType::Day(task_date),
+            },
+        };
+        operations
+    }
+
+    /// Store copies of tasks in `register`, and on the system clipboard if enabled
+    fn yank_tasks(&mut self, tasks: Vec<Task>, register: Option<char>) {
+        if tasks.len() > 1 {
+            self.status_message = Some(format!("{} tasks yanked", tasks.len()));
+        }
+        if tasks.is_empty() {
+            return;
+        }
+        if self.config.clipboard.osc52 {
+            let text = clipboard::format_tasks(&tasks, self.config.clipboard.format);
+            if let Err(e) = clipboard::copy_osc52(&text) {
+                self.status_message = Some(format!("Could not copy to the clipboard: {}", e));
+            }
+        }
+        self.registers.yank(register, tasks);
+    }
+
+    /// Visible tasks in `range` by day and order, or the selected task without one
+    fn command_targets(&self, range: Option<DateRange>) -> Result<Vec<Task>, String> {
+        let Some(range) = range else {
+            return self.selected_task().map(|task| vec![task]);
+        };
+        let mut tasks: Vec<Task> = self
+            .visible_tasks()
+            .into_iter()
+            .filter(|t| range.contains(t.start.date_naive()))
+            .cloned()
+            .collect();
+        tasks.sort_by_key(|t| (t.start.date_naive(), t.order));
+        if tasks.is_empty() {
+            return Err(format!("No tasks from {} to {}", range.start, range.end));
+        }
+        Ok(tasks)
     }
 
-    fn save(&self) -> Result<()> {
-        save_data(&self.data).map_err(|e| color_eyre::eyre::eyre!(e))?;
+    /// :[range]delete [x]
+    fn delete_command(&mut self, range: Option<DateRange>, register: Option<char>) -> Result<(), String> {
+        self.ensure_writable()?;
+        let task_ids = self.command_targets(range)?.into_iter().map(|t| t.id).collect();
+        let operations = self.cut_tasks(task_ids, register);
+        if let Some(operation) = Operation::batch(operations) {
+            self.record(operation);
+        }
+        self.save().map_err(|e| e.to_string())
+    }
+
+    /// The selected task, for commands acting on it
+    fn selected_task(&self) -> Result<Task, String> {
+        self.selected_task_copies(1)
+            .pop()
+            .ok_or_else(|| String::from("No task selected"))
+    }
+
+    /// :add <text> - a task written inline, on its date or the selected day
+    fn add_command(&mut self, text: &str) -> Result<(), String> {
+        self.ensure_writable()?;
+        let parsed = quick_add::parse(text, Local::now().date_naive());
+        if parsed.title.is_empty() {
+            return Err(String::from(
+                "Usage: :add <title> [date] [time] [#tag] [!priority]",
+            ));
+        }
+        let selected_date = self.month_view.get_selected_date(&self.data.events);
+        let date = parsed.date.unwrap_or(selected_date);
+        let mut task = Task::new(parsed.title, quick_add::start_time(date, parsed.time));
+        task.tags = parsed.tags;
+        task.priority = parsed.priority;
+        task.order = self.data.next_order_for_date(date);
+        let task_id = task.id.clone();
+        let operation = self.tracked(|data| {
+            data.insert_task_at_order(task.clone(), task.order);
+            Some(Operation::CreateTask { task })
+        });
+        if let Some(operation) = operation {
+            self.record(operation);
+        }
+        self.save().map_err(|e| e.to_string())?;
+        if date != selected_date {
+            self.remember_jump();
+        }
+        self.month_view.jump_to(date, Some(task_id));
         Ok(())
     }
 
+    /// :edit [text] - open the editor on the selected task, or change it with
+    /// the inline syntax of :add: new words replace the title, tags are added
+    fn edit_command(&mut self, text: &str) -> Result<(), String> {
+        let task = self.selected_task()?;
+        if text.is_empty() {
+            self.mode = AppMode::TaskEdit(TaskEditState::edit_task(&task));
+            return Ok(());
+        }
+        self.ensure_writable()?;
+        let parsed = quick_add::parse(text, Local::now().date_naive());
+        let mut edited = task.clone();
+        if !parsed.title.is_empty() {
+            edited.title = parsed.title;
+        }
+        if parsed.date.is_some() || parsed.time.is_some() {
+            let date = parsed.date.unwrap_or(task.start.date_naive());
+            edited.start = match parsed.time {
+                Some(time) => quick_add::start_time(date, Some(time)),
+                None => date.and_time(task.start.time()).and_utc(),
+            };
+            edited.end = edited.start + (task.end - task.start);
+        }
+        for tag in &parsed.tags {
+            edited.add_tag(tag);
+        }
+        if parsed.priority.is_some() {
+            edited.priority = parsed.priority;
+        }
+        self.replace_task(task, edited)
+    }
+
+    /// :rename <title>
+    fn rename_command(&mut self, title: &str) -> Result<(), String> {
+        if title.is_empty() {
+            return Err(String::from("Usage: :rename <title>"));
+        }
+        self.ensure_writable()?;
+        let task = self.selected_task()?;
+        let mut renamed = task.clone();
+        renamed.title = title.to_string();
+        self.replace_task(task, renamed)
+    }
+
+    /// :[range]done[!] - mark the selected task or the tasks of a range done,
+    /// or not done with !
+    fn done_command(&mut self, range: Option<DateRange>, done: bool) -> Result<(), String> {
+        self.ensure_writable()?;
+        let tasks = self.command_targets(range)?;
+        let operations = self.complete_tasks(&tasks, done);
+        if let Some(operation) = Operation::batch(operations) {
+            self.record(operation);
+        }
+        self.save().map_err(|e| e.to_string())
+    }
+
+    /// Mark tasks done, or not done, skipping those that already are
+    fn complete_tasks(&mut self, tasks: &[Task], done: bool) -> Vec<Operation> {
+        let mut operations = Vec::new();
+        for task in tasks.iter().filter(|t| t.completed != done) {
+            operations.extend(self.tracked(|data| {
+                let task = data.events.iter_mut().find(|t| t.id == task.id)?;
+                task.completed = done;
+                Some(Operation::ToggleComplete {
+                    task_id: task.id.clone(),
+                    title: task.title.clone(),
+                    completed: done,
+                })
+            }));
+        }
+        operations
+    }
+
+    /// :[range]tag <tags>, or :[range]tag! <tags> to remove them
+    fn tag_command(&mut self, range: Option<DateRange>, text: &str, remove: bool) -> Result<(), String> {
+        self.ensure_writable()?;
+        let tags = tag_names(text)?;
+        let tasks = self.command_targets(range)?;
+        let operations = self.tag_tasks(&tasks, &tags, remove);
+        if let Some(operation) = Operation::batch(operations) {
+            self.record(operation);
+        }
+        self.save().map_err(|e| e.to_string())
+    }
+
+    fn tag_tasks(&mut self, tasks: &[Task], tags: &[String], remove: bool) -> Vec<Operation> {
+        self.edit_tasks(tasks, |task| {
+            if remove {
+                task.tags.retain(|t| !tags.contains(t));
+            } else {
+                for tag in tags {
+                    task.add_tag(tag);
+                }
+            }
+        })
+    }
+
+    /// :[range]s/pattern/replacement/flags on the titles of the selected task
+    /// or the tasks of a range
+    fn substitute_command(&mut self, range: Option<DateRange>, text: &str) -> Result<(), String> {
+        self.ensure_writable()?;
+        let substitution = Substitution::parse(text)?;
+        let tasks = self.command_targets(range)?;
+        let operations = self.substitute_tasks(&tasks, &substitution);
+        let count = operations.len();
+        if count == 0 {
+            return Err(format!("Pattern not found: {}", substitution.pattern()));
+        }
+        self.record(Operation::Batch {
+            description: format!("Substitute /{}/ in {} task(s)", substitution.pattern(), count),
+            operations,
+        });
+        self.save().map_err(|e| e.to_string())?;
+        self.status_message = Some(format!("Changed {} title(s)", count));
+        Ok(())
+    }
+
+    fn substitute_tasks(&mut self, tasks: &[Task], substitution: &Substitution) -> Vec<Operation> {
+        self.edit_tasks(tasks, |task| {
+            if let Some(title) = substitution.apply(&task.title) {
+                task.title = title;
+            }
+        })
+    }
+
+    /// :[range]g/pattern/command - run done, delete, move, tag or s on the
+    /// tasks of the range (the shown month without one) whose title or a
+    /// #tag matches, or with :g! on those that don't, as one undo step
+    fn global_command(&mut self, range: Option<DateRange>, inverse: bool, text: &str) -> Result<(), String> {
+        self.ensure_writable()?;
+        let global = Global::parse(text)?;
+        let range = match range {
+            Some(range) => range,
+            None => self.resolve_range(&RangeSpec::Month)?,
+        };
+        let tasks: Vec<Task> = self
+            .command_targets(Some(range))?
+            .into_iter()
+            .filter(|t| global.matches(t) != inverse)
+            .collect();
+        if tasks.is_empty() {
+            return Err(format!("Pattern not found: {}", global.regex.as_str()));
+        }
+        let line = cmdline::parse(&global.command)?;
+        if line.range.is_some() {
+            return Err(String::from("The command of :g takes no range"));
+        }
+        let registry = get_command_registry();
+        let info = commands::find_command(&registry, &line.name)?;
+        let operations = match info.name {
+            "done" => self.complete_tasks(&tasks, !line.bang),
+            "delete" => {
+                let register = commands::register_arg(&line.args)?;
+                self.cut_tasks(tasks.iter().map(|t| t.id.clone()).collect(), register)
+            }
+            "move" => {
+                let date_for = move_date(&line.args)?;
+                self.move_tasks(tasks.clone(), date_for)
+            }
+            "tag" => {
+                let tags = tag_names(&line.args)?;
+                self.tag_tasks(&tasks, &tags, line.bang)
+            }
+            "substitute" => {
+                let substitution = Substitution::parse(&line.args)?;
+                self.substitute_tasks(&tasks, &substitution)
+            }
+            other => {
+                return Err(format!(
+                    ":{} doesn't work with :g; use done, delete, move, tag or s",
+                    other
+                ))
+            }
+        };
+        if operations.is_empty() {
+            return Ok(());
+        }
+        self.record(Operation::Batch {
+            description: format!(
+                "Global /{}/ {} on {} task(s)",
+                global.regex.as_str(),
+                info.name,
+                tasks.len()
+            ),
+            operations,
+        });
+        self.save().map_err(|e| e.to_string())?;
+        self.status_message = Some(format!(":{} on {} task(s)", info.name, tasks.len()));
+        Ok(())
+    }
+
+    /// Apply `edit` to a copy of each task and record the ones it changed
+    fn edit_tasks(&mut self, tasks: &[Task], edit: impl Fn(&mut Task)) -> Vec<Operation> {
+        let mut operations = Vec::new();
+        for task in tasks {
+            let mut new_task = task.clone();
+            edit(&mut new_task);
+            if new_task == *task {
+                continue;
+            }
+            operations.extend(self.tracked(|data| {
+                let existing = data.events.iter_mut().find(|t| t.id == task.id)?;
+                *existing = new_task.clone();
+                Some(Operation::EditTask {
+                    task_id: task.id.clone(),
+                    old_task: task.clone(),
+                    new_task,
+                })
+            }));
+        }
+        operations
+    }
+
+    /// :[range]move <date> - reschedule the selected task or the tasks of a
+    /// range; `+N`/`-N` offsets count from each task's own day
+    fn move_command(&mut self, range: Option<DateRange>, text: &str) -> Result<(), String> {
+        self.ensure_writable()?;
+        let date_for = move_date(text)?;
+        let tasks = self.command_targets(range)?;
+        let operations = self.move_tasks(tasks, date_for);
+        if let Some(operation) = Operation::batch(operations) {
+            self.record(operation);
+        }
+        self.save().map_err(|e| e.to_string())
+    }
+
+    /// Move a task chosen in the snooze popup to `date`, keeping it selected
+    fn snooze_task(&mut self, task_id: &str, date: chrono::NaiveDate) -> Result<()> {
+        let tasks = self
+            .data
+            .events
+            .iter()
+            .filter(|t| t.id == task_id)
+            .cloned()
+            .collect();
+        let operations = self.move_tasks(tasks, |_| date);
+        if let Some(operation) = Operation::batch(operations) {
+            self.record(operation);
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Move each task to the day `date_for` gives for its current one, at the
+    /// end of that day with its id and time kept, and select the last one moved
+    fn move_tasks(
+        &mut self,
+        tasks: Vec<Task>,
+        date_for: impl Fn(chrono::NaiveDate) -> chrono::NaiveDate,
+    ) -> Vec<Operation> {
+        let mut operations = Vec::new();
+        let mut moved_to = None;
+        for task in tasks {
+            let date = date_for(task.start.date_naive());
+            let offset = date - task.start.date_naive();
+            if offset.is_zero() {
+                continue;
+            }
+            operations.extend(self.tracked(|data| {
+                let mut moved = data.remove_task_and_reorder(&task.id)?;
+                moved.start = task.start + offset;
+                moved.end = task.end + offset;
+                moved.order = data.next_order_for_date(date);
+                data.insert_task_at_order(moved.clone(), moved.order);
+                Some(Operation::MoveTask {
+                    task_id: task.id.clone(),
+                    title: task.title.clone(),
+                    old_start: task.start,
+                    old_end: task.end,
+                    new_start: moved.start,
+                    new_end: moved.end,
+                })
+            }));
+            moved_to = Some((date, task.id));
+        }
+        if let Some((date, task_id)) = moved_to {
+            self.month_view.jump_to(date, Some(task_id));
+        }
+        operations
+    }
+
+    /// :rollover
+    fn rollover_command(&mut self) -> Result<(), String> {
+        let count = self.rollover()?;
+        self.remember_jump();
+        self.month_view.go_to_today();
+        self.status_message = Some(format!("Rolled over {} task(s) to today", count));
+        Ok(())
+    }
+
+    /// Move the incomplete tasks of past days to the end of today, oldest
+    /// first, counting the rollovers in a `rolled:N` tag, as one undo step
+    fn rollover(&mut self) -> Result<usize, String> {
+        self.ensure_writable()?;
+        let today = Local::now().date_naive();
+        let mut operations = Vec::new();
+        for task in self.data.overdue_tasks(today) {
+            operations.extend(self.tracked(|data| {
+                let rolled = data.roll_over(&task.id, today)?;
+                Some(Operation::EditTask {
+                    task_id: task.id.clone(),
+                    old_task: task,
+                    new_task: rolled,
+                })
+            }));
+        }
+        let count = operations.len();
+        if count == 0 {
+            return Ok(0);
+        }
+        self.record(Operation::Batch {
+            description: format!("Roll over {} task(s) to {}", count, today),
+            operations,
+        });
+        self.save().map_err(|e| e.to_string())?;
+        Ok(count)
+    }
+
+    /// :filter, :filter <name>, :filter <expression>: open the smart lists
+    /// sidebar on the last list shown, a list by name, or a new filter
+    fn filter_command(&mut self, text: &str) -> Result<(), String> {
+        let text = text.trim();
+        let state = if text.is_empty() {
+            SmartListsState::new(self.smart_list, None)
+        } else if let Some(index) = self
+            .filtered_lists(None)
+            .iter()
+            .position(|l| l.name.eq_ignore_ascii_case(text))
+        {
+            SmartListsState::new(index, None)
+        } else {
+            filter::Filter::parse(text, Local::now().date_naive())?;
+            let index = self.filtered_lists(None).len();
+            SmartListsState::new(index, Some(text.to_string()))
+        };
+        self.smart_list = state.list;
+        self.mode = AppMode::SmartLists(state);
+        Ok(())
+    }
+
+    /// The smart lists, then the saved filters and `query`, each with the
+    /// visible tasks it matches by date and order
+    fn filtered_lists(&self, query: Option<&str>) -> Vec<FilteredList> {
+        let today = Local::now().date_naive();
+        let mut tasks = self.visible_tasks();
+        tasks.sort_by_key(|t| (t.start.date_naive(), t.order));
+        let saved = self
+            .config
+            .filters
+            .iter()
+            .map(|f| (f.name.as_str(), f.expression.as_str()));
+        filter::SMART_LISTS
+            .into_iter()
+            .chain(saved)
+            .chain(query.map(|q| (q, q)))
+            .map(|(name, expression)| FilteredList {
+                name: name.to_string(),
+                tasks: filter::Filter::parse(expression, today).map(|filter| {
+                    tasks
+                        .iter()
+                        .filter(|t| filter.matches(t, self.config.list_name(t)))
+                        .map(|t| (*t).clone())
+                        .collect()
+                }),
+            })
+            .collect()
+    }
+
+    /// Replace a task with an edited copy as one undo step, moving it to the
+    /// end of its new day if the date changed, and keep it selected
+    fn replace_task(&mut self, old_task: Task, mut new_task: Task) -> Result<(), String> {
+        if new_task == old_task {
+            return Ok(());
+        }
+        let task_id = old_task.id.clone();
+        let date = new_task.start.date_naive();
+        let operation = self.tracked(|data| {
+            if date != old_task.start.date_naive() {
+                data.remove_task_and_reorder(&old_task.id)?;
+                new_task.order = data.next_order_for_date(date);
+                data.insert_task_at_order(new_task.clone(), new_task.order);
+            } else {
+                let existing = data.events.iter_mut().find(|t| t.id == old_task.id)?;
+                *existing = new_task.clone();
+            }
+            Some(Operation::EditTask {
+                task_id: old_task.id.clone(),
+                old_task,
+                new_task,
+            })
+        });
+        if let Some(operation) = operation {
+            self.record(operation);
+        }
+        self.save().map_err(|e| e.to_string())?;
+        self.month_view.jump_to(date, Some(task_id));
+        Ok(())
+    }
+
+    /// :[range]yank [x]
+    fn yank_command(&mut self, range: Option<DateRange>, register: Option<char>) -> Result<(), String> {
+        let tasks = self.command_targets(range)?;
+        self.yank_tasks(tasks, register);
+        Ok(())
+    }
+
+    /// Insert `count` tasks made by `make` one below the other at `position`,
+    /// selecting the last unless they were added at the end of the day
+    fn insert_tasks(
+        &mut self,
+        position: InsertPosition,
+        count: usize,
+        make: impl Fn(chrono::NaiveDate, usize) -> Task,
+    ) -> Vec<Operation> {
+        let date = self.month_view.get_selected_date(&self.data.events);
+        let current_order = self.month_view.get_current_task_order(&self.data.events);
+        let first_order = match (position, current_order) {
+            (InsertPosition::Above, order) => order.unwrap_or_default(),
+            (InsertPosition::Below, Some(order)) => order + 1,
+            _ => self.data.next_order_for_date(date),
+        };
+        let mut operations = Vec::new();
+        let mut order = first_order;
+        for i in 0..count {
+            let mut task = make(date, i);
+            task.order = order;
+            operations.extend(self.tracked(|data| {
+                data.insert_task_at_order(task.clone(), order);
+                Some(Operation::CreateTask { task })
+            }));
+            order += 1;
+        }
+        if position != InsertPosition::End {
+            self.month_view
+                .select_task_by_order(date, order - 1, &self.data.events);
+        }
+        operations
+    }
+
+    /// Add the lines of the system clipboard as tasks below the selection
+    fn paste_clipboard(&mut self) -> Result<(), String> {
+        self.ensure_writable()?;
+        let text = clipboard::read_system(self.config.clipboard.paste_command.as_deref())?;
+        let pasted = clipboard::parse_lines(&text);
+        if pasted.is_empty() {
+            return Err(String::from("The clipboard holds no text to paste"));
+        }
+        let operations = self.insert_tasks(InsertPosition::Below, pasted.len(), |date, i| {
+            let mut state = TaskEditState::new_task(date);
+            state.title = pasted[i].title.clone();
+            let mut task = state.to_task();
+            task.completed = pasted[i].completed;
+            for note in &pasted[i].notes {
+                task.add_comment(note.clone());
+            }
+            task
+        });
+        if let Some(operation) = Operation::batch(operations) {
+            self.record(operation);
+        }
+        self.save().map_err(|e| e.to_string())
+    }
+
+    fn current_position(&self) -> Position {
+        Position {
+            date: self.month_view.get_selected_date(&self.data.events),
+            task_id: self.month_view.get_selected_task_id(),
+        }
+    }
+
+    /// Select a position, following a task to its current day, or falling
+    /// back to the day once the task is gone or hidden
+    fn go_to_position(&mut self, position: Position) {
+        let task = position.task_id.and_then(|id| {
+            self.visible_tasks()
+                .into_iter()
+                .find(|t| t.id == id)
+                .map(|t| (t.start.date_naive(), t.id.clone()))
+        });
+        match task {
+            Some((date, id)) => self.month_view.jump_to(date, Some(id)),
+            None => self.month_view.jump_to(position.date, None),
+        }
+    }
+
+    /// Remember the current position in the jump list before a large move
+    fn remember_jump(&mut self) {
+        let position = self.current_position();
+        self.jumps.push(position);
+    }
+
+    /// Persist marks next to the data file
+    fn save_marks(&mut self) {
+        if self.locked_by.is_some() {
+            return;
+        }
+        let result = self
+            .marks
+            .to_json()
+            .map_err(color_eyre::eyre::Report::from)
+            .and_then(|content| self.store.write_sidecar(MARKS, content));
+        if let Err(e) = result {
+            self.status_message = Some(format!("Could not save marks: {}", e));
+        }
+    }
+
+    /// Persist the command history next to the data file
+    fn save_command_history(&mut self) {
+        if self.locked_by.is_some() {
+            return;
+        }
+        let content = self.command_history.to_text();
+        if let Err(e) = self.store.write_sidecar(HISTORY, content) {
+            self.status_message = Some(format!("Could not save the command history: {}", e));
+        }
+    }
+
+    fn describe_marks(&self) -> String {
+        self.marks.describe(|id| {
+            self.data
+                .events
+                .iter()
+                .find(|t| t.id == id)
+                .map(|t| t.title.clone())
+        })
+    }
+
+    /// Delete marks: :delmarks <names>, or every mark with :delmarks!
+    fn delete_marks(&mut self, args: &str, all: bool) -> Result<(), String> {
+        let names: String = match all {
+            true => ('a'..='z').chain('A'..='Z').collect(),
+            false => args.chars().filter(|c| !c.is_whitespace()).collect(),
+        };
+        if names.is_empty() {
+            return Err(String::from("Usage: :delmarks <names>, e.g. :delmarks ab"));
+        }
+        if self.marks.remove(&names) == 0 {
+            return Err(format!("No marks named {}", names));
+        }
+        self.save_marks();
+        Ok(())
+    }
+
+    /// Commands that change the data file are refused while read-only
+    fn ensure_writable(&self) -> Result<(), String> {
+        match self.locked_by {
+            Some(pid) => Err(format!(
+                "Read-only: the data file is in use by taskim (pid {})",
+                pid
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Pull and push the git history, merging task lists by id
+    fn sync(&mut self) -> Result<(), String> {
+        self.ensure_writable()?;
+        if self.history.is_none() {
+            return Err(String::from(
+                "Git history is not enabled (set git.enabled in config.yml)",
+            ));
+        }
+
+        self.save().map_err(|e| e.to_string())?;
+        let outcome = match &self.history {
+            Some(history) => history.sync(&self.store).map_err(|e| e.to_string())?,
+            None => return Ok(()),
+        };
+        if !self.merge_external_changes() {
+            return Err(String::from("Synced, but the merged data file could not be read"));
+        }
+
+        self.status_message = Some(match (outcome.pulled, outcome.conflicts) {
+            (false, _) => String::from("Synced: pushed local history"),
+            (true, 0) => String::from("Synced: merged remote changes"),
+            (true, n) => format!(
+                "Synced: merged remote changes, {} conflicting task(s) kept the remote version",
+                n
+            ),
+        });
+        Ok(())
+    }
+
+    /// Ask for a new passphrase on the plain terminal and re-encrypt the data file with it
+    fn change_passphrase(&mut self) -> Result<()> {
+        self.change_passphrase = false;
+        self.save()?;
+        let passphrase = crypto::prompt_new_passphrase()?;
+        let was_encrypted = self.store.is_encrypted();
+        self.store
+            .set_cipher(Some(crypto::Cipher::new(&passphrase)?))?;
+
+        if let Some(history) = &self.history {
+            let message = if was_encrypted {
+                "Change passphrase"
+            } else {
+                "Encrypt task data"
+            };
+            if let Err(e) = history.commit(message) {
+                self.status_message = Some(e.to_string());
+                return Ok(());
+            }
+        }
+        self.status_message = Some(match (was_encrypted, &self.history) {
+            (true, _) => String::from("Passphrase changed"),
+            (false, None) => String::from(
+                "The data file is now encrypted (set `encrypt: true` in config.yml to keep it that way)",
+            ),
+            (false, Some(_)) => format!(
+                "The data file is now encrypted (set `encrypt: true` in config.yml to keep it that way), but {}",
+                crypto::PLAINTEXT_HISTORY_WARNING
+            ),
+        });
+        Ok(())
+    }
+
+    /// While read-only, take over the lock as soon as the other instance lets go
+    fn retry_lock(&mut self) -> Result<()> {
+        if self.locked_by.is_none() {
+            return Ok(());
+        }
+        if let LockStatus::Acquired(lock) = DataLock::try_acquire(self.store.path())? {
+            self.lock = Some(lock);
+            self.locked_by = None;
+            self.status_message = Some(String::from(
+                "The other taskim instance exited; editing is enabled again",
+            ));
+        }
+        Ok(())
+    }
+
+    /// How far a reschedule key moves the selected task, in days
+    fn reschedule_days(&self, key: crossterm::event::KeyEvent) -> Option<i64> {
+        [
+            (&self.config.reschedule_left, -1),
+            (&self.config.reschedule_down, 7),
+            (&self.config.reschedule_up, -7),
+            (&self.config.reschedule_right, 1),
+        ]
+        .into_iter()
+        .find(|(binding, _)| binding.matches(key.code, key.modifiers))
+        .map(|(_, days)| days)
+    }
+
+    /// Keys that would change the task data, disabled while read-only
+    fn is_mutating_key(&self, key: crossterm::event::KeyEvent) -> bool {
+        [
+            &self.config.insert_edit,
+            &self.config.save_task,
+            &self.config.insert_above,
+            &self.config.insert_below,
+            &self.config.delete,
+            &self.config.delete_line,
+            &self.config.toggle_complete,
+            &self.config.paste,
+            &self.config.paste_above,
+            &self.config.repeat,
+            &self.config.undo,
+            &self.config.redo,
+            &self.config.reorder_down,
+            &self.config.reorder_up,
+            &self.config.snooze,
+            &self.config.reschedule_left,
+            &self.config.reschedule_down,
+            &self.config.reschedule_up,
+            &self.config.reschedule_right,
+        ]
+        .iter()
+        .any(|binding| binding.matches(key.code, key.modifiers))
+    }
+
+    /// Reload the data file if it was changed externally and merge it with ours by task id
+    fn merge_external_changes(&mut self) -> bool {
+        if !self.store.changed_on_disk() {
+            return true;
+        }
+
+        let selected_date = self.month_view.get_selected_date(&self.data.events);
+        let base = self.store.persisted().clone();
+        let theirs = match self.store.load() {
+            Ok(theirs) => theirs,
+            Err(e) => {
+                // Caught mid-write or damaged; merging it would drop every task
+                self.status_message = Some(format!("Not saved yet, the data file is unreadable: {}", e));
+                return false;
+            }
+        };
+        let result = merge::merge_by_id(&base, &self.data.events, &theirs.events);
+        let changed = result.tasks != self.data.events;
+        self.data.events = result.tasks;
+        self.loaded = None;
+
+        // Undoing past an external change would clobber it
+        let trimmed = self.undo_tree.retain_consistent(&self.data);
+
+        // Conflicts keep the file's version; record our side so `u` can restore it
+        for conflict in &result.conflicts {
+            let operation = match (&conflict.ours, &conflict.theirs) {
+                (Some(ours), Some(theirs)) => Operation::EditTask {
+                    task_id: ours.id.clone(),
+                    old_task: ours.clone(),
+                    new_task: theirs.clone(),
+                },
+                (Some(ours), None) => Operation::DeleteTask { task: ours.clone() },
+                (None, Some(theirs)) => Operation::CreateTask {
+                    task: theirs.clone(),
+                },
+                (None, None) => continue,
+            };
+            self.undo_tree.push(operation);
+        }
+
+        if !result.conflicts.is_empty() {
+            self.status_message = Some(format!(
+                "{} conflicting edit(s) with external changes; kept the file's version (u restores yours)",
+                result.conflicts.len()
+            ));
+        } else if changed && trimmed {
+            self.status_message = Some(String::from(
+                "Reloaded external changes to the data file; undo history before them was dropped",
+            ));
+        } else if changed {
+            self.status_message = Some(String::from("Reloaded external changes to the data file"));
+        }
+
+        // The selected task may have been deleted by the other writer
+        self.keep_selection_valid(selected_date);
+        true
+    }
+
+    /// Select `date` instead of a task that no longer exists
+    fn keep_selection_valid(&mut self, date: chrono::NaiveDate) {
+        if let Some(task_id) = self.month_view.get_selected_task_id() {
+            if !self.data.events.iter().any(|t| t.id == task_id) {
+                self.month_view.selection = month_view::Selection {
+                    selection_type: SelectionType::Day(date),
+                };
+            }
+        }
+    }
+
+    /// Move through the undo tree chronologically, across branches (`g-`/`g+`,
+    /// `:earlier`/`:later`)
+    fn time_travel(&mut self, distance: UndoDistance, forward: bool) -> Result<(), String> {
+        self.ensure_writable()?;
+        let Some(target) = self.undo_tree.target(distance, forward) else {
+            return Err(String::from(if forward {
+                "Already at the newest change"
+            } else {
+                "Already at the oldest change"
+            }));
+        };
+
+        let selected_date = self.month_view.get_selected_date(&self.data.events);
+        let changed: Vec<String> = self
+            .undo_tree
+            .task_ids_towards(target)
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        let steps = self.undo_tree.travel(target, &mut self.data)?;
+        self.store.mark_dirty(changed.iter().map(String::as_str));
+        self.keep_selection_valid(selected_date);
+        self.pending_change = Some(format!(
+            "Go {} {} change(s) in the undo history",
+            if forward { "forward" } else { "back" },
+            steps
+        ));
+        self.save().map_err(|e| e.to_string())
+    }
+
     fn handle_key_event(&mut self, key: crossterm::event::KeyEvent) -> Result<()> {
+        // Keys that start or stop a recording aren't part of the macro, and
+        // keys played back from a macro are recorded as the '@' that played them
+        let record = self.macro_depth == 0 && self.macros.recording().is_some();
+        if !self.browses_calendar(key) {
+            if let Err(e) = self.load_everything() {
+                self.status_message = Some(format!("Can't read the data file: {}", e));
+                return Ok(());
+            }
+        }
+        self.handle_mode_key(key)?;
+        if record && self.macros.recording().is_some() {
+            self.macros.record(key);
+        }
+        if let Err(e) = self.load_shown_days() {
+            self.status_message = Some(format!("Can't read the data file: {}", e));
+        }
+        Ok(())
+    }
+
+    /// Play the macro in register `name` `times` times through the normal key handling
+    fn play_macro(&mut self, name: char, times: usize) -> Result<()> {
+        let Some(keys) = self.macros.keys(name) else {
+            self.status_message = Some(format!("No macro recorded in register {}", name));
+            return Ok(());
+        };
+        if self.macro_depth >= macros::MAX_DEPTH {
+            self.status_message = Some(String::from("Macro stopped: it plays itself too often"));
+            return Ok(());
+        }
+        self.macro_depth += 1;
+        let mut result = Ok(());
+        'play: for _ in 0..times {
+            for key in &keys {
+                result = self.handle_key_event(*key);
+                if result.is_err() || self.should_exit {
+                    break 'play;
+                }
+            }
+        }
+        self.macro_depth -= 1;
+        result
+    }
+
+    fn handle_mode_key(&mut self, key: crossterm::event::KeyEvent) -> Result<()> {
         match &self.mode {
             AppMode::Normal => self.handle_normal_mode_key(key)?,
             AppMode::Command(state) => {
                 let mut new_state = state.clone();
-                if self.handle_command_mode_key(key, &mut new_state)? {
-                    // Command completed or cancelled
-                    self.mode = AppMode::Normal;
-                } else {
-                    self.mode = AppMode::Command(new_state);
+                let done = self.handle_command_mode_key(key, &mut new_state)?;
+                // Unless the command opened another mode, such as the editor for :edit
+                if let AppMode::Command(_) = self.mode {
+                    self.mode = if done {
+                        // Command completed or cancelled
+                        AppMode::Normal
+                    } else {
+                        AppMode::Command(new_state)
+                    };
+                }
+            }
+            AppMode::CommandWindow(state) => {
+                let mut new_state = state.clone();
+                self.handle_command_window_key(key, &mut new_state);
+                if let AppMode::CommandWindow(_) = self.mode {
+                    self.mode = AppMode::CommandWindow(new_state);
+                }
+            }
+            AppMode::Snooze(state) => {
+                let mut new_state = state.clone();
+                match new_state.handle_key(key.code, Local::now().date_naive()) {
+                    SnoozeAction::None => self.mode = AppMode::Snooze(new_state),
+                    SnoozeAction::Cancel => self.mode = AppMode::Normal,
+                    SnoozeAction::Snooze(date) => {
+                        self.mode = AppMode::Normal;
+                        self.snooze_task(&new_state.task_id, date)?;
+                    }
+                }
+            }
+            AppMode::SmartLists(state) => {
+                let mut new_state = state.clone();
+                let lists = self.filtered_lists(new_state.query.as_deref());
+                let tasks = match lists.get(new_state.list).map(|l| &l.tasks) {
+                    Some(Ok(tasks)) => tasks.clone(),
+                    _ => Vec::new(),
+                };
+                let action = new_state.handle_key(key.code, lists.len(), tasks.len());
+                self.smart_list = new_state.list;
+                match action {
+                    SmartListsAction::None => self.mode = AppMode::SmartLists(new_state),
+                    SmartListsAction::Close => self.mode = AppMode::Normal,
+                    SmartListsAction::Jump(index) => {
+                        let task = &tasks[index];
+                        self.mode = AppMode::Normal;
+                        self.remember_jump();
+                        self.month_view
+                            .jump_to(task.start.date_naive(), Some(task.id.clone()));
+                    }
                 }
             }
             AppMode::TaskEdit(state) => {
@@ -133,47 +1702,44 @@ impl App {
                     // Task edit completed
                     let mut task = new_state.to_task();
                     if new_state.is_new_task {
-                        // Use pending insert order if set (for 'o' and 'O' commands)
-                        if let Some(insert_order) = self.pending_insert_order.take() {
-                            self.data.insert_task_at_order(task.clone(), insert_order);
-
-                            // Select the new task by its order
-                            let task_date = task.start.date_naive();
-                            self.month_view.select_task_by_order(
-                                task_date,
-                                insert_order,
-                                &self.data.events,
-                            );
-                        } else {
-                            // Regular insertion (for 'i' command) - add to end
-                            let task_date = task.start.date_naive();
-                            task.order = self.data.max_order_for_date(task_date) + 1;
-                            self.data.events.push(task.clone());
+                        let (mut position, count) = self
+                            .pending_insert
+                            .take()
+                            .unwrap_or((InsertPosition::End, 1));
+                        if new_state.date != self.month_view.get_selected_date(&self.data.events) {
+                            // A task given another day goes at the end of that day
+                            self.month_view.jump_to(new_state.date, None);
+                            position = InsertPosition::End;
                         }
-
-                        // Track task creation
-                        self.undo_stack
-                            .push(Operation::CreateTask { task: task.clone() });
+                        let change = Change::Insert {
+                            position,
+                            title: new_state.title.clone(),
+                            content: new_state.content.clone(),
+                            list: new_state.list.clone(),
+                        };
+                        self.make_change(change, count)?;
                     } else {
-                        // Track task edit
                         if let Some(existing) = self
                             .data
                             .events
-                            .iter_mut()
+                            .iter()
                             .find(|t| Some(&t.id) == new_state.task_id.as_ref())
+                            .cloned()
                         {
-                            let old_task = existing.clone();
-                            *existing = task.clone();
-
-                            self.undo_stack.push(Operation::EditTask {
-                                task_id: task.id.clone(),
-                                old_task,
-                                new_task: task,
-                            });
+                            // The popup edits title, notes, date and list; the
+                            // time of day stays
+                            task.start = new_state.date.and_time(existing.start.time()).and_utc();
+                            task.end = task.start + (existing.end - existing.start);
+                            task.order = existing.order;
+                            task.completed = existing.completed;
+                            task.tags = existing.tags.clone();
+                            task.priority = existing.priority;
+                            if let Err(e) = self.replace_task(existing, task) {
+                                self.status_message = Some(e);
+                            }
                         }
                     }
                     self.mode = AppMode::Normal;
-                    self.save()?;
                 } else {
                     self.mode = AppMode::TaskEdit(new_state);
                 }
@@ -183,12 +1749,37 @@ impl App {
     }
 
     fn handle_normal_mode_key(&mut self, key: crossterm::event::KeyEvent) -> Result<()> {
+        self.status_message = None;
+
         // Handle keybindings
         if self.config.force_quit.matches(key.code, key.modifiers) {
             self.should_exit = true;
             return Ok(());
         }
 
+        // Collect a count typed before the key, e.g. the 3 in '3x'
+        if let KeyCode::Char(ch) = key.code {
+            if self.pending_key.is_none()
+                && key.modifiers == KeyModifiers::NONE
+                && self.count.push(ch)
+            {
+                return Ok(());
+            }
+        }
+        let count = self.count.take();
+        let times = count.unwrap_or(1);
+        let register = self.register.take();
+
+        if let Some(pid) = self.locked_by {
+            if self.is_mutating_key(key) {
+                self.status_message = Some(format!(
+                    "Read-only: the data file is in use by taskim (pid {})",
+                    pid
+                ));
+                return Ok(());
+            }
+        }
+
         // Handle multi-key sequences first
         if let Some(pending) = self.pending_key {
             if pending == 'g'
@@ -196,373 +1787,314 @@ impl App {
                 && key.modifiers == KeyModifiers::NONE
             {
                 // Handle 'gg' - go to previous year
+                self.remember_jump();
                 self.month_view.prev_year();
                 self.pending_key = None;
                 return Ok(());
+            } else if pending == 'g'
+                && matches!(key.code, KeyCode::Char('-') | KeyCode::Char('+'))
+                && !key.modifiers.contains(KeyModifiers::CONTROL)
+            {
+                // Handle 'g-'/'g+' - move back/forward in time through the undo tree
+                self.pending_key = None;
+                let forward = key.code == KeyCode::Char('+');
+                if let Err(e) = self.time_travel(UndoDistance::Steps(times), forward) {
+                    self.status_message = Some(e);
+                }
+                return Ok(());
             } else if pending == 'd'
                 && key.code == KeyCode::Char('d')
                 && key.modifiers == KeyModifiers::NONE
             {
                 // Handle 'dd' - cut the selected task (vim-style)
-                if let Some(task_id) = self.month_view.get_selected_task_id() {
-                    if let Some(task) = self.data.remove_task_and_reorder(&task_id) {
-                        let task_date = task.start.date_naive();
-
-                        // Store the cut task for pasting
-                        self.yanked_task = Some(task.clone());
-
-                        // Track deletion for undo functionality
-                        self.undo_stack.push(Operation::DeleteTask { task });
-
-                        // Check if there are any remaining tasks on the same date
-                        let remaining_tasks = self.data.get_tasks_for_date(task_date);
-
-                        if remaining_tasks.is_empty() {
-                            // No more tasks on this day, select the day itself
-                            self.month_view.selection = month_view::Selection {
-                                selection_type: month_view::SelectionType::Day(task_date),
-                            };
-                        } else {
-                            // Select the first remaining task
-                            self.month_view.selection = month_view::Selection {
-                                selection_type: month_view::SelectionType::Task(
-                                    remaining_tasks[0].id.clone(),
-                                ),
-                            };
+                self.pending_key = None;
+                self.make_change(Change::Delete { register }, times)?;
+                return Ok(());
+            } else if pending == 'q' {
+                // Handle 'q{reg}' - record a macro (vim-style)
+                self.pending_key = None;
+                match key.code {
+                    KeyCode::Char(name) if Macros::is_valid(name) => self.macros.start(name),
+                    // 'q:' opens the command-history window (vim-style)
+                    KeyCode::Char(':') => self.open_command_window(),
+                    _ => self.status_message = Some(String::from("Invalid register name")),
+                }
+                return Ok(());
+            } else if pending == '@' {
+                // Handle '@{reg}' / '@@' - play a macro
+                self.pending_key = None;
+                if let KeyCode::Char(name) = key.code {
+                    self.play_macro(name, times)?;
+                }
+                return Ok(());
+            } else if pending == 'm' {
+                // Handle 'm{a-z}' - mark the selected task or day
+                self.pending_key = None;
+                match key.code {
+                    KeyCode::Char(name) if Marks::is_valid(name) => {
+                        let position = self.current_position();
+                        self.marks.set(name, position);
+                        self.save_marks();
+                    }
+                    _ => self.status_message = Some(String::from("Invalid mark name")),
+                }
+                return Ok(());
+            } else if pending == '\'' {
+                // Handle ''{a-z}' - jump to a mark
+                self.pending_key = None;
+                if let KeyCode::Char(name) = key.code {
+                    match self.marks.get(name).cloned() {
+                        Some(position) => {
+                            self.remember_jump();
+                            self.go_to_position(position);
                         }
-
-                        self.save()?;
+                        None => self.status_message = Some(format!("Mark {} is not set", name)),
                     }
                 }
+                return Ok(());
+            } else if pending == '"' {
+                // Handle '"{register}' - use a register for the next yank, cut or paste
                 self.pending_key = None;
+                match key.code {
+                    KeyCode::Char(name) if Registers::is_valid(name) => {
+                        self.register = Some(name);
+                        self.count.restore(count);
+                    }
+                    _ => self.status_message = Some(String::from("Invalid register name")),
+                }
                 return Ok(());
             }
             // If we have a pending key but don't match, clear it and continue with normal processing
             self.pending_key = None;
         }
 
-        if self.config.quit.matches(key.code, key.modifiers)
+        if self.config.record_macro.matches(key.code, key.modifiers) {
+            if let Some(name) = self.macros.stop() {
+                // Pressed again while recording, it stops (vim-style)
+                self.status_message = Some(format!("Recorded macro @{}", name));
+            } else if self.macro_depth == 0 {
+                self.pending_key = Some('q');
+            }
+        } else if self.config.quit.matches(key.code, key.modifiers)
             || self.config.quit_alt.matches(key.code, key.modifiers)
         {
             self.should_exit = true;
         } else if self.config.move_left.matches(key.code, key.modifiers) {
-            self.month_view.move_left(&self.data.events);
+            self.navigate(times, MonthView::move_left);
         } else if self.config.move_down.matches(key.code, key.modifiers) {
-            self.month_view.move_down(&self.data.events);
+            self.navigate(times, MonthView::move_down);
         } else if self.config.move_up.matches(key.code, key.modifiers) {
-            self.month_view.move_up(&self.data.events);
+            self.navigate(times, MonthView::move_up);
         } else if self.config.move_right.matches(key.code, key.modifiers) {
-            self.month_view.move_right(&self.data.events);
+            self.navigate(times, MonthView::move_right);
+        } else if self.config.reorder_down.matches(key.code, key.modifiers) {
+            // Swap the selected task with the one below it (J)
+            self.make_change(Change::Reorder { down: true }, times)?;
+        } else if self.config.reorder_up.matches(key.code, key.modifiers) {
+            // Swap the selected task with the one above it (K)
+            self.make_change(Change::Reorder { down: false }, times)?;
+        } else if self.config.snooze.matches(key.code, key.modifiers) {
+            // Push the selected task off to a preset day (z)
+            match self.selected_task() {
+                Ok(task) => {
+                    let today = Local::now().date_naive();
+                    let state = SnoozeState::new(task.id, task.start.date_naive(), today);
+                    self.mode = AppMode::Snooze(state);
+                }
+                Err(e) => self.status_message = Some(e),
+            }
+        } else if self.config.smart_lists.matches(key.code, key.modifiers) {
+            // Open the smart lists and saved filters sidebar (F)
+            self.mode = AppMode::SmartLists(SmartListsState::new(self.smart_list, None));
+        } else if let Some(days) = self.reschedule_days(key) {
+            // Shift the selected task by days or weeks (Alt+h/l, Alt+k/j)
+            self.make_change(Change::Move { days }, times)?;
         } else if self.config.insert_edit.matches(key.code, key.modifiers) {
             match &self.month_view.selection.selection_type {
                 SelectionType::Day(date) => {
-                    // Create new task
+                    // Create new task(s) at the end of the day
                     let edit_state = TaskEditState::new_task(*date);
+                    self.pending_insert = Some((InsertPosition::End, times));
                     self.mode = AppMode::TaskEdit(edit_state);
                 }
                 SelectionType::Task(task_id) => {
-                    // Edit existing task
-                    if let Some(task) = self.data.events.iter().find(|t| &t.id == task_id) {
-                        let edit_state = TaskEditState::edit_task(task);
-                        self.mode = AppMode::TaskEdit(edit_state);
-                    }
-                }
-            }
-        } else if self.config.save_task.matches(key.code, key.modifiers) {
-            match &self.month_view.selection.selection_type {
-                SelectionType::Task(task_id) => {
-                    // Edit existing task (same as insert_edit for task)
+                    // Edit existing task
                     if let Some(task) = self.data.events.iter().find(|t| &t.id == task_id) {
                         let edit_state = TaskEditState::edit_task(task);
                         self.mode = AppMode::TaskEdit(edit_state);
                     }
                 }
-                _ => {}
+            }
+        } else if self.config.save_task.matches(key.code, key.modifiers) {
+            if let SelectionType::Task(task_id) = &self.month_view.selection.selection_type {
+                // Edit existing task (same as insert_edit for task)
+                if let Some(task) = self.data.events.iter().find(|t| &t.id == task_id) {
+                    let edit_state = TaskEditState::edit_task(task);
+                    self.mode = AppMode::TaskEdit(edit_state);
+                }
             }
         } else if self.config.insert_below.matches(key.code, key.modifiers) {
             // Insert task below current position (vim-style: o)
             let selected_date = self.month_view.get_selected_date(&self.data.events);
             let edit_state = TaskEditState::new_task(selected_date);
-
-            // Store the insertion order for when the task is created
-            let insert_order = if let Some(current_order) =
-                self.month_view.get_current_task_order(&self.data.events)
-            {
-                current_order + 1
-            } else {
-                self.data.max_order_for_date(selected_date) + 1
-            };
-
-            // We'll need to track this order for when the task gets created
-            // For now, set up the task edit state
-            self.pending_insert_order = Some(insert_order);
+            self.pending_insert = Some((InsertPosition::Below, times));
             self.mode = AppMode::TaskEdit(edit_state);
         } else if self.config.insert_above.matches(key.code, key.modifiers) {
             // Insert task above current position (vim-style: O)
             let selected_date = self.month_view.get_selected_date(&self.data.events);
             let edit_state = TaskEditState::new_task(selected_date);
-
-            // Store the insertion order for when the task is created
-            let insert_order = if let Some(current_order) =
-                self.month_view.get_current_task_order(&self.data.events)
-            {
-                current_order
-            } else {
-                0
-            };
-
-            // We'll need to track this order for when the task gets created
-            self.pending_insert_order = Some(insert_order);
+            self.pending_insert = Some((InsertPosition::Above, times));
             self.mode = AppMode::TaskEdit(edit_state);
         } else if self.config.delete_line.matches(key.code, key.modifiers) {
-            // Handle first 'd' for 'dd' sequence
+            // Handle first 'd' for 'dd' sequence, keeping the count for the second
             self.pending_key = Some('d');
+            self.count.restore(count);
+            self.register = register;
         } else if self.config.delete.matches(key.code, key.modifiers) {
             // Delete/cut the selected task (vim-style 'x') - same as 'dd'
-            if let Some(task_id) = self.month_view.get_selected_task_id() {
-                if let Some(deleted_task) = self.data.remove_task_and_reorder(&task_id) {
-                    let task_date = deleted_task.start.date_naive();
-
-                    // Store the cut task for pasting (copy functionality)
-                    self.yanked_task = Some(deleted_task.clone());
-
-                    // Track deletion for undo functionality
-                    self.undo_stack
-                        .push(Operation::DeleteTask { task: deleted_task });
-
-                    // Check if there are any remaining tasks on the same date
-                    let remaining_tasks = self.data.get_tasks_for_date(task_date);
-
-                    if remaining_tasks.is_empty() {
-                        // No more tasks on this day, select the day itself
-                        self.month_view.selection = month_view::Selection {
-                            selection_type: month_view::SelectionType::Day(task_date),
-                        };
-                    } else {
-                        // Select the first remaining task (ordered)
-                        self.month_view.selection = month_view::Selection {
-                            selection_type: month_view::SelectionType::Task(
-                                remaining_tasks[0].id.clone(),
-                            ),
-                        };
-                    }
-
-                    self.save()?;
-                }
+            self.make_change(Change::Delete { register }, times)?;
+        } else if self.config.repeat.matches(key.code, key.modifiers) {
+            // Repeat the last change on the selection (vim-style: .), with its
+            // original count unless a new one was typed
+            if let Some(last) = self.last_change.clone() {
+                self.make_change(last.change, count.unwrap_or(last.count))?;
             }
         } else if self.config.undo.matches(key.code, key.modifiers) {
-            // Undo last operation
-            if let Some(operation) = self.undo_stack.undo() {
-                match operation {
-                    Operation::DeleteTask { task } => {
-                        // Restore deleted task
-                        self.data.events.push(task.clone());
-
-                        // Select the restored task
-                        self.month_view.selection = month_view::Selection {
-                            selection_type: month_view::SelectionType::Task(task.id),
-                        };
+            // Undo last operation(s)
+            for _ in 0..times {
+                let operation = match self.undo_tree.undo(&mut self.data) {
+                    Ok(Some(operation)) => operation,
+                    Ok(None) => break,
+                    Err(e) => {
+                        self.status_message = Some(e);
+                        break;
                     }
-                    Operation::EditTask {
-                        task_id,
-                        old_task,
-                        new_task: _,
-                    } => {
-                        // Revert task edit
-                        if let Some(existing) =
-                            self.data.events.iter_mut().find(|t| t.id == task_id)
-                        {
-                            *existing = old_task;
-                        }
-                    }
-                    Operation::CreateTask { task } => {
-                        // Remove created task
-                        self.data.events.retain(|t| t.id != task.id);
-
-                        // Select the day where the task was
-                        let task_date = task.start.date_naive();
-                        self.month_view.selection = month_view::Selection {
-                            selection_type: month_view::SelectionType::Day(task_date),
-                        };
-                    }
-                }
+                };
+                self.pending_change = Some(format!("Undo: {}", operation.describe()));
+                self.store.mark_dirty(operation.task_ids());
+                self.month_view.selection = match operation.primary() {
+                    // Select the restored task
+                    Operation::DeleteTask { task } => month_view::Selection {
+                        selection_type: month_view::SelectionType::Task(task.id.clone()),
+                    },
+                    // Select the day where the removed task was
+                    Operation::CreateTask { task } => month_view::Selection {
+                        selection_type: month_view::SelectionType::Day(task.start.date_naive()),
+                    },
+                    // Follow the task back to where it was
+                    Operation::MoveTask { task_id, .. } => month_view::Selection {
+                        selection_type: month_view::SelectionType::Task(task_id.clone()),
+                    },
+                    _ => self.month_view.selection.clone(),
+                };
                 self.save()?;
             }
         } else if self.config.redo.matches(key.code, key.modifiers) {
-            // Redo last undone operation
-            if let Some(operation) = self.undo_stack.redo() {
-                match operation {
-                    Operation::DeleteTask { task } => {
-                        // Re-delete the task
-                        self.data.events.retain(|t| t.id != task.id);
-
-                        // Select the day where the task was
-                        let task_date = task.start.date_naive();
-                        self.month_view.selection = month_view::Selection {
-                            selection_type: month_view::SelectionType::Day(task_date),
-                        };
-                    }
-                    Operation::EditTask {
-                        task_id,
-                        old_task: _,
-                        new_task,
-                    } => {
-                        // Re-apply task edit
-                        if let Some(existing) =
-                            self.data.events.iter_mut().find(|t| t.id == task_id)
-                        {
-                            *existing = new_task;
-                        }
-                    }
-                    Operation::CreateTask { task } => {
-                        // Re-create task
-                        self.data.events.push(task.clone());
-
-                        // Select the restored task
-                        self.month_view.selection = month_view::Selection {
-                            selection_type: month_view::SelectionType::Task(task.id),
-                            // task_index_in_day: Some(0),
-                        };
+            // Redo last undone operation(s)
+            for _ in 0..times {
+                let operation = match self.undo_tree.redo(&mut self.data) {
+                    Ok(Some(operation)) => operation,
+                    Ok(None) => break,
+                    Err(e) => {
+                        self.status_message = Some(e);
+                        break;
                     }
-                }
+                };
+                self.pending_change = Some(format!("Redo: {}", operation.describe()));
+                self.store.mark_dirty(operation.task_ids());
+                self.month_view.selection = match operation.primary() {
+                    // Select the day where the deleted task was
+                    Operation::DeleteTask { task } => month_view::Selection {
+                        selection_type: month_view::SelectionType::Day(task.start.date_naive()),
+                    },
+                    // Select the restored task
+                    Operation::CreateTask { task } => month_view::Selection {
+                        selection_type: month_view::SelectionType::Task(task.id.clone()),
+                    },
+                    // Follow the task to its new day
+                    Operation::MoveTask { task_id, .. } => month_view::Selection {
+                        selection_type: month_view::SelectionType::Task(task_id.clone()),
+                    },
+                    _ => self.month_view.selection.clone(),
+                };
                 self.save()?;
             }
         } else if self.config.toggle_complete.matches(key.code, key.modifiers) {
             // Toggle task completion
-            if let Some(task_id) = self.month_view.get_selected_task_id() {
-                if let Some(task) = self.data.events.iter_mut().find(|t| t.id == task_id) {
-                    task.completed = !task.completed;
-                    self.save()?;
-                }
-            }
+            self.make_change(Change::ToggleComplete, times)?;
         } else if self.config.yank.matches(key.code, key.modifiers) {
-            // Yank (copy) task
-            if let Some(task_id) = self.month_view.get_selected_task_id() {
-                if let Some(task) = self.data.events.iter().find(|t| t.id == task_id) {
-                    self.yanked_task = Some(task.clone());
-                }
-            }
+            // Yank (copy) the selected task and the ones below it with a count
+            let tasks = self.selected_task_copies(times);
+            self.yank_tasks(tasks, register);
         } else if self.config.paste.matches(key.code, key.modifiers) {
-            // Paste task below current position
-            if let Some(yanked_task) = &self.yanked_task {
-                let selected_date = self.month_view.get_selected_date(&self.data.events);
-                let mut new_task = yanked_task.clone();
-
-                // Generate new ID for the pasted task
-                new_task.id = format!(
-                    "task_{}",
-                    chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
-                );
-
-                // Set new start/end times for the selected date
-                let duration = new_task.end - new_task.start;
-                let new_start = selected_date
-                    .and_hms_opt(
-                        new_task.start.time().hour(),
-                        new_task.start.time().minute(),
-                        new_task.start.time().second(),
-                    )
-                    .unwrap()
-                    .and_utc();
-                new_task.start = new_start;
-                new_task.end = new_start + duration;
-
-                // Insert task with proper ordering
-                let insert_order = if let Some(current_order) =
-                    self.month_view.get_current_task_order(&self.data.events)
-                {
-                    current_order + 1
-                } else {
-                    self.data.max_order_for_date(selected_date) + 1
-                };
-
-                self.data
-                    .insert_task_at_order(new_task.clone(), insert_order);
-
-                // Track the paste operation for undo
-                self.undo_stack.push(Operation::CreateTask {
-                    task: new_task.clone(),
-                });
-
-                // Select the new task
-                self.month_view.select_task_by_order(
-                    selected_date,
-                    insert_order,
-                    &self.data.events,
-                );
-                self.save()?;
-            }
+            // Paste task(s) below current position
+            self.make_change(Change::Paste { above: false, register }, times)?;
         } else if self.config.paste_above.matches(key.code, key.modifiers) {
-            // Paste task above current position
-            if let Some(yanked_task) = &self.yanked_task {
-                let selected_date = self.month_view.get_selected_date(&self.data.events);
-                let mut new_task = yanked_task.clone();
-
-                // Generate new ID for the pasted task
-                new_task.id = format!(
-                    "task_{}",
-                    chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
-                );
-
-                // Set new start/end times for the selected date
-                let duration = new_task.end - new_task.start;
-                let new_start = selected_date
-                    .and_hms_opt(
-                        new_task.start.time().hour(),
-                        new_task.start.time().minute(),
-                        new_task.start.time().second(),
-                    )
-                    .unwrap()
-                    .and_utc();
-                new_task.start = new_start;
-                new_task.end = new_start + duration;
-
-                // Insert task with proper ordering (above current)
-                let insert_order = if let Some(current_order) =
-                    self.month_view.get_current_task_order(&self.data.events)
-                {
-                    current_order
-                } else {
-                    0
-                };
-
-                self.data
-                    .insert_task_at_order(new_task.clone(), insert_order);
-
-                // Track the paste operation for undo
-                self.undo_stack.push(Operation::CreateTask {
-                    task: new_task.clone(),
-                });
-
-                // Select the new task
-                self.month_view.select_task_by_order(
-                    selected_date,
-                    insert_order,
-                    &self.data.events,
-                );
-                self.save()?;
+            // Paste task(s) above current position
+            self.make_change(Change::Paste { above: true, register }, times)?;
+        } else if key.code == KeyCode::Char('@') && key.modifiers == KeyModifiers::NONE {
+            // Play a macro (vim-style: @a, @@), keeping the count for the register key
+            self.pending_key = Some('@');
+            self.count.restore(count);
+        } else if key.code == KeyCode::Char('m') && key.modifiers == KeyModifiers::NONE {
+            // Set a mark (vim-style: ma)
+            self.pending_key = Some('m');
+        } else if matches!(key.code, KeyCode::Char('\'') | KeyCode::Char('`'))
+            && key.modifiers == KeyModifiers::NONE
+        {
+            // Jump to a mark (vim-style: 'a)
+            self.pending_key = Some('\'');
+        } else if key.code == KeyCode::Char('o') && key.modifiers == KeyModifiers::CONTROL {
+            // Back through the jump list (vim-style: Ctrl-o)
+            let current = self.current_position();
+            for _ in 0..times {
+                if let Some(position) = self.jumps.back(current.clone()) {
+                    self.go_to_position(position);
+                }
+            }
+        } else if (key.code == KeyCode::Tab && key.modifiers == KeyModifiers::NONE)
+            || (key.code == KeyCode::Char('i') && key.modifiers == KeyModifiers::CONTROL)
+        {
+            // Forward through the jump list (vim-style: Ctrl-i, which terminals send as Tab)
+            for _ in 0..times {
+                if let Some(position) = self.jumps.forward() {
+                    self.go_to_position(position);
+                }
             }
+        } else if key.code == KeyCode::Char('"') && key.modifiers == KeyModifiers::NONE {
+            // Choose a register for the next yank, cut or paste (vim-style: "a)
+            self.pending_key = Some('"');
+            self.count.restore(count);
         } else if self.config.next_month.matches(key.code, key.modifiers) {
             // Next month (vim-style: L) - preserve day
-            self.month_view.next_month_preserve_day();
+            self.remember_jump();
+            for _ in 0..times {
+                self.month_view.next_month_preserve_day();
+            }
         } else if self.config.prev_month.matches(key.code, key.modifiers) {
             // Previous month (vim-style: H) - preserve day
-            self.month_view.prev_month_preserve_day();
+            self.remember_jump();
+            for _ in 0..times {
+                self.month_view.prev_month_preserve_day();
+            }
         } else if self.config.next_year.matches(key.code, key.modifiers) {
             // Next year (vim-style: G)
+            self.remember_jump();
             self.month_view.next_year();
         } else if self.config.prev_year.matches(key.code, key.modifiers) {
-            // Handle first 'g' for 'gg' sequence
+            // Handle first 'g' for 'gg' sequence, keeping a count for 'g-'/'g+'
             self.pending_key = Some('g');
+            self.count.restore(count);
         } else if self.config.go_to_today.matches(key.code, key.modifiers) {
             // Go to today (vim-style: t)
+            self.remember_jump();
             self.month_view.go_to_today();
         } else if self.config.next_week.matches(key.code, key.modifiers) {
             // Next week (vim-style: w)
-            self.month_view.next_week(&self.data.events);
+            self.navigate(times, MonthView::next_week);
         } else if self.config.prev_week.matches(key.code, key.modifiers) {
             // Previous week (vim-style: b)
-            self.month_view.prev_week(&self.data.events);
+            self.navigate(times, MonthView::prev_week);
         } else if self
             .config
             .first_day_of_month
@@ -599,13 +2131,17 @@ impl App {
                 return Ok(true);
             } else if self.config.save_task.matches(key.code, key.modifiers) {
                 // Save task
-                if !state.title.trim().is_empty() {
+                if !state.title.trim().is_empty() && state.apply_date(Local::now().date_naive()) {
                     return Ok(true);
                 }
             } else if self.config.switch_field.matches(key.code, key.modifiers) {
                 state.switch_field();
             } else if self.config.backspace.matches(key.code, key.modifiers) {
                 state.remove_char();
+            } else if state.editing_field == EditingField::List
+                && matches!(key.code, KeyCode::Left | KeyCode::Right)
+            {
+                state.cycle_list(&self.list_names(), key.code == KeyCode::Right);
             } else if let KeyCode::Char(ch) = key.code {
                 state.add_char(ch);
             }
@@ -618,42 +2154,41 @@ impl App {
         key: crossterm::event::KeyEvent,
         state: &mut CommandState,
     ) -> Result<bool> {
+        if !matches!(key.code, KeyCode::Tab | KeyCode::BackTab) {
+            state.completion = None;
+        }
+        if state.search.is_some() {
+            return Ok(self.handle_history_search_key(key, state));
+        }
+        if key.code == KeyCode::Char('r') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            // Start a reverse search of the history (readline-style: Ctrl-r)
+            state.search = Some(String::new());
+            state.history_prefix = state.input.clone();
+            state.history_index = None;
+            return Ok(false);
+        }
         match key.code {
             KeyCode::Esc => {
                 // Cancel command mode
                 return Ok(true);
             }
             KeyCode::Enter => {
-                // Execute command
-                let command = state.input.trim();
-
-                if command == "help" {
-                    // Toggle help display
-                    state.show_help = !state.show_help;
-                    state.input.clear();
-                    state.cursor_position = 0;
-                    return Ok(false); // Stay in command mode to show help
-                } else if !command.is_empty() {
-                    match self.execute_command(&state.input) {
-                        Ok(_) => {
-                            state.last_error = None;
-                        }
-                        Err(e) => {
-                            state.last_error = Some(e);
-                        }
-                    }
-                    state.input.clear();
-                    state.cursor_position = 0;
-                    return Ok(state.last_error.is_none());
-                } else {
-                    // Empty command, just exit
-                    return Ok(true);
-                }
+                return Ok(self.submit_command(state));
+            }
+            KeyCode::Up | KeyCode::Down => {
+                self.recall_history(state, key.code == KeyCode::Up);
+            }
+            KeyCode::Tab | KeyCode::BackTab => {
+                self.complete_command(state, key.code == KeyCode::Tab);
+                state.show_help = false;
+                state.last_error = None;
             }
             KeyCode::Backspace => {
                 state.remove_char();
-                // Hide help when user starts typing
+                // Hide help and errors when user starts typing
                 state.show_help = false;
+                state.last_error = None;
+                state.history_index = None;
             }
             KeyCode::Left => {
                 state.move_cursor_left();
@@ -663,108 +2198,347 @@ impl App {
             }
             KeyCode::Char(ch) => {
                 state.add_char(ch);
-                // Hide help when user starts typing
+                // Hide help and errors when user starts typing
                 state.show_help = false;
+                state.last_error = None;
+                state.history_index = None;
             }
             _ => {}
         }
         Ok(false)
     }
 
-    fn execute_command(&mut self, command: &str) -> Result<(), String> {
-        let trimmed = command.trim();
-        let registry = get_command_registry();
-        // Special handling for help command
-        if trimmed.starts_with("help") {
-            let parts: Vec<&str> = trimmed.split_whitespace().collect();
-            if parts.len() == 1 {
-                let mut help_text = String::from("Available commands:\n");
-                for (cmd, info) in &registry {
-                    help_text.push_str(&format!(":{:<15} - {}\n", cmd, info.description));
-                }
-                return Err(help_text);
-            } else if parts.len() == 2 {
-                let query = parts[1].trim_start_matches(':');
-                if let Some(info) = registry.get(query) {
-                    return Err(format!(":{} - {}", query, info.description));
-                } else {
-                    return Err(format!("No help found for :{}", query));
-                }
-            }
+    /// Run the typed command and remember it; true when command mode is done
+    fn submit_command(&mut self, state: &mut CommandState) -> bool {
+        let command = state.input.trim().to_string();
+        if command.is_empty() {
+            // Empty command, just exit
+            return true;
         }
-        if trimmed.is_empty() {
-            return Ok(());
+        self.command_history.push(&command);
+        self.save_command_history();
+        state.history_index = None;
+        state.input.clear();
+        state.cursor_position = 0;
+
+        if self.toggles_help(&command) {
+            // Toggle help display, staying in command mode to show it
+            state.show_help = !state.show_help;
+            return false;
         }
-        // Try registry
-        if let Some(cmd) = registry.get(trimmed) {
-            (cmd.exec)(self, trimmed)?;
-            return Ok(());
+        state.last_error = self.execute_command(&command).err();
+        state.last_error.is_none()
+    }
+
+    /// Up/Down: the previous or next command starting with what was typed
+    fn recall_history(&self, state: &mut CommandState, older: bool) {
+        if state.history_index.is_none() {
+            state.history_prefix = state.input.clone();
         }
-        // Try to parse as a date in various formats
-        if let Some(date) = self.parse_date_command(trimmed) {
-            if date.month() != self.month_view.current_date.month()
-                || date.year() != self.month_view.current_date.year()
-            {
-                self.month_view.current_date = date.with_day(1).unwrap();
-                self.month_view.weeks =
-                    MonthView::build_weeks_for_date(self.month_view.current_date);
+        let prefix = state.history_prefix.as_str();
+        let found = match (state.history_index, older) {
+            (None, true) => self.command_history.previous(self.command_history.len(), prefix),
+            (Some(index), true) => self.command_history.previous(index, prefix),
+            (Some(index), false) => self.command_history.next(index, prefix),
+            (None, false) => return,
+        };
+        match found.and_then(|index| Some((index, self.command_history.get(index)?))) {
+            Some((index, command)) => {
+                state.history_index = Some(index);
+                state.recall(command);
             }
-            self.month_view.selection = month_view::Selection {
-                selection_type: month_view::SelectionType::Day(date),
-            };
-            return Ok(());
+            None if !older => {
+                // Past the newest match: back to what was typed
+                state.history_index = None;
+                let typed = state.history_prefix.clone();
+                state.recall(&typed);
+            }
+            None => {}
         }
-        Err(format!(
-            "Unknown command: {}. Type ':help' for available commands.",
-            trimmed
-        ))
     }
 
-    fn parse_date_command(&self, input: &str) -> Option<chrono::NaiveDate> {
-        use chrono::NaiveDate;
-
-        // Try parsing as YYYY (year only)
-        if let Ok(year) = input.parse::<i32>() {
-            if year >= 1900 && year <= 2050 {
-                let current_month = self.month_view.current_date.month();
-                let current_day = self.month_view.get_selected_date(&self.data.events).day();
+    /// Keys while searching the history with Ctrl-r: typing narrows the
+    /// search, Ctrl-r finds an older match, Enter runs the match and Esc or
+    /// Ctrl-g gives up; other keys keep the match for editing
+    fn handle_history_search_key(&mut self, key: crossterm::event::KeyEvent, state: &mut CommandState) -> bool {
+        let Some(mut pattern) = state.search.take() else {
+            return false;
+        };
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let from = match key.code {
+            KeyCode::Char('r') if ctrl => state.history_index,
+            KeyCode::Char('g') if ctrl => {
+                let typed = state.history_prefix.clone();
+                state.recall(&typed);
+                state.history_index = None;
+                return false;
+            }
+            KeyCode::Esc => {
+                let typed = state.history_prefix.clone();
+                state.recall(&typed);
+                state.history_index = None;
+                return false;
+            }
+            KeyCode::Enter => return self.submit_command(state),
+            KeyCode::Char(ch) if !ctrl => {
+                pattern.push(ch);
+                // The current match may still match the longer pattern
+                state.history_index.map(|index| index + 1)
+            }
+            KeyCode::Backspace => {
+                pattern.pop();
+                None
+            }
+            _ => {
+                state.history_index = None;
+                return false;
+            }
+        };
+        let from = from.unwrap_or(self.command_history.len());
+        if let Some(index) = self.command_history.search(from, &pattern) {
+            state.history_index = Some(index);
+            if let Some(command) = self.command_history.get(index) {
+                state.recall(command);
+            }
+        }
+        state.search = Some(pattern);
+        false
+    }
 
-                // Calculate days in the target month for the specified year
-                let days_in_month = days_in_month(year, current_month);
+    fn open_command_window(&mut self) {
+        if self.command_history.is_empty() {
+            self.status_message = Some(String::from("The command history is empty"));
+            return;
+        }
+        self.mode = AppMode::CommandWindow(CommandWindowState {
+            selected: self.command_history.len() - 1,
+        });
+    }
 
-                let safe_day = std::cmp::min(current_day, days_in_month);
-                return NaiveDate::from_ymd_opt(year, current_month, safe_day);
+    /// Keys in the q: window: j/k move, Enter runs the command, i edits it on
+    /// the command line, x forgets it and Esc or q closes the window
+    fn handle_command_window_key(&mut self, key: crossterm::event::KeyEvent, state: &mut CommandWindowState) {
+        let last = self.command_history.len().saturating_sub(1);
+        let command = self
+            .command_history
+            .get(state.selected)
+            .unwrap_or_default()
+            .to_string();
+        if self.config.move_down.matches(key.code, key.modifiers) || key.code == KeyCode::Down {
+            state.selected = (state.selected + 1).min(last);
+        } else if self.config.move_up.matches(key.code, key.modifiers) || key.code == KeyCode::Up {
+            state.selected = state.selected.saturating_sub(1);
+        } else if key.code == KeyCode::Char('g') {
+            state.selected = 0;
+        } else if key.code == KeyCode::Char('G') {
+            state.selected = last;
+        } else if key.code == KeyCode::Enter {
+            let mut command_state = CommandState::new();
+            command_state.recall(&command);
+            self.mode = match self.submit_command(&mut command_state) {
+                true => AppMode::Normal,
+                false => AppMode::Command(command_state),
+            };
+        } else if matches!(key.code, KeyCode::Char('i') | KeyCode::Char('a') | KeyCode::Char('e')) {
+            let mut command_state = CommandState::new();
+            command_state.recall(&command);
+            self.mode = AppMode::Command(command_state);
+        } else if key.code == KeyCode::Char('x') {
+            self.command_history.remove(state.selected);
+            self.save_command_history();
+            if self.command_history.is_empty() {
+                self.mode = AppMode::Normal;
             }
+            state.selected = state.selected.min(self.command_history.len().saturating_sub(1));
+        } else if matches!(key.code, KeyCode::Esc | KeyCode::Char('q')) {
+            self.mode = AppMode::Normal;
         }
+    }
+
+    /// Whether `command` is a bare :help, which toggles the help panel
+    fn toggles_help(&self, command: &str) -> bool {
+        let Ok(line) = cmdline::parse(command) else {
+            return false;
+        };
+        line.range.is_none()
+            && line.args.is_empty()
+            && commands::find_command(&get_command_registry(), &line.name)
+                .is_ok_and(|info| info.name == "help")
+    }
 
-        // Try parsing as MM/DD/YYYY (simple manual parsing)
-        let parts: Vec<&str> = input.split('/').collect();
-        if parts.len() == 3 {
-            if let (Ok(month), Ok(day), Ok(year)) = (
-                parts[0].parse::<u32>(),
-                parts[1].parse::<u32>(),
-                parts[2].parse::<i32>(),
-            ) {
-                return NaiveDate::from_ymd_opt(year, month, day);
+    /// Complete the word at the end of the command line, or cycle through
+    /// the candidates of the last completion
+    fn complete_command(&self, state: &mut CommandState, forward: bool) {
+        match &mut state.completion {
+            Some(completion) => completion.cycle(forward),
+            None => {
+                let (start, candidates) = self.completions(&state.input);
+                if candidates.is_empty() {
+                    return;
+                }
+                let index = if forward { 0 } else { candidates.len() - 1 };
+                state.completion = Some(Completion {
+                    prefix: state.input[..start].to_string(),
+                    candidates,
+                    index,
+                });
             }
         }
+        if let Some(completion) = &state.completion {
+            state.input = completion.current();
+            state.cursor_position = state.input.len();
+            // A single match is done; the next Tab completes the next word
+            if completion.candidates.len() == 1 {
+                state.completion = None;
+            }
+        }
+    }
+
+    /// Where the word being typed starts and what it could be completed to:
+    /// command names, then the arguments the command takes, dates and #tags
+    fn completions(&self, input: &str) -> (usize, Vec<String>) {
+        let (start, word, command) = cmdline::word_at_end(input);
+        let registry = get_command_registry();
+        let mut candidates = if word.starts_with('#') {
+            self.tags()
+        } else if word.starts_with(|c: char| c.is_ascii_digit())
+            || (command.is_none() && input[..start].ends_with(','))
+        {
+            self.date_candidates()
+        } else if let Some(name) = command {
+            match commands::find_command(&registry, &name).map(|info| info.complete) {
+                Ok(Complete::Commands) => registry.iter().map(|c| c.name.to_string()).collect(),
+                Ok(Complete::Options) => commands::option_names(),
+                Ok(Complete::Lists) => self.list_names(),
+                _ => Vec::new(),
+            }
+        } else {
+            registry.iter().map(|c| c.name.to_string()).collect()
+        };
+        candidates.retain(|c| c.starts_with(word));
+        (start, candidates)
+    }
 
-        // Try parsing as DD (day only)
-        if let Ok(day) = input.parse::<u32>() {
-            if day >= 1 && day <= 31 {
-                let current_year = self.month_view.current_date.year();
-                let current_month = self.month_view.current_date.month();
+    /// The #tags of visible tasks, stored or still written in the title
+    fn tags(&self) -> Vec<String> {
+        let tags: std::collections::BTreeSet<String> = self
+            .visible_tasks()
+            .iter()
+            .flat_map(|t| {
+                t.title
+                    .split_whitespace()
+                    .filter(|w| w.len() > 1 && w.starts_with('#'))
+                    .map(str::to_string)
+                    .chain(t.tags.iter().map(|tag| format!("#{}", tag)))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        tags.into_iter().collect()
+    }
 
-                // Check if the day is valid for the current month
-                let days_in_month = days_in_month(current_year, current_month);
+    /// Today, the selected day and the days with visible tasks
+    fn date_candidates(&self) -> Vec<String> {
+        let mut dates: std::collections::BTreeSet<chrono::NaiveDate> = self
+            .visible_tasks()
+            .iter()
+            .map(|t| t.start.date_naive())
+            .collect();
+        dates.insert(Local::now().date_naive());
+        dates.insert(self.month_view.get_selected_date(&self.data.events));
+        dates.iter().map(|d| d.format("%Y-%m-%d").to_string()).collect()
+    }
 
-                if day <= days_in_month {
-                    return NaiveDate::from_ymd_opt(current_year, current_month, day);
-                }
+    fn execute_command(&mut self, command: &str) -> Result<(), String> {
+        let registry = get_command_registry();
+        let parsed = cmdline::parse(command).and_then(|line| match line.name.as_str() {
+            "" => Ok((line, None)),
+            name => commands::find_command(&registry, name).map(|info| (line, Some(info))),
+        });
+        let (line, info) = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                // Not a command: a date expression jumps there, e.g. :next friday or :+3d
+                let date = dates::parse_date(command, Local::now().date_naive()).ok_or(e)?;
+                self.remember_jump();
+                self.month_view.jump_to(date, None);
+                return Ok(());
+            }
+        };
+        let range = match &line.range {
+            Some(spec) => Some(self.resolve_range(spec)?),
+            None => None,
+        };
+        let Some(info) = info else {
+            // A range alone jumps to its first day, e.g. :2025-06-15
+            if let Some(range) = range {
+                self.remember_jump();
+                self.month_view.jump_to(range.start, None);
+            }
+            return Ok(());
+        };
+        if range.is_some() && !info.range {
+            return Err(format!(":{} doesn't take a range", info.name));
+        }
+        let args = commands::Args {
+            range,
+            bang: line.bang,
+            text: &line.args,
+        };
+        (info.exec)(self, &args)
+    }
+
+    fn resolve_range(&self, spec: &RangeSpec) -> Result<DateRange, String> {
+        match spec {
+            RangeSpec::Month => {
+                let first = self.month_view.current_date.with_day(1).unwrap();
+                let days = days_in_month(first.year(), first.month());
+                Ok(DateRange::new(first, first.with_day(days).unwrap()))
+            }
+            RangeSpec::Span(start, end) => {
+                let start = self.resolve_address(start)?;
+                let end = match end {
+                    Some(end) => self.resolve_address(end)?,
+                    None => start,
+                };
+                Ok(DateRange::new(start, end))
             }
         }
+    }
 
-        None
+    fn resolve_address(&self, address: &Address) -> Result<chrono::NaiveDate, String> {
+        use chrono::NaiveDate;
+
+        let selected = self.month_view.get_selected_date(&self.data.events);
+        let shown = self.month_view.current_date;
+        match *address {
+            Address::Selected(offset) => chrono::Duration::try_days(offset)
+                .and_then(|days| selected.checked_add_signed(days))
+                .ok_or_else(|| format!("Invalid day offset: .{:+}", offset)),
+            Address::Date(date) => Ok(date),
+            Address::Day(day) => NaiveDate::from_ymd_opt(shown.year(), shown.month(), day)
+                .ok_or_else(|| format!("{} has no day {}", shown.format("%B"), day)),
+            Address::Year(year) => {
+                // Same month, and the same day where the month is long enough
+                let day = selected.day().min(days_in_month(year, shown.month()));
+                NaiveDate::from_ymd_opt(year, shown.month(), day)
+                    .ok_or_else(|| format!("Invalid year: {}", year))
+            }
+            Address::Mark(name) => {
+                let position = self
+                    .marks
+                    .get(name)
+                    .ok_or_else(|| format!("Mark not set: {}", name))?;
+                // Follow a marked task to its current day
+                let task_date = position.task_id.as_ref().and_then(|id| {
+                    self.data
+                        .events
+                        .iter()
+                        .find(|t| &t.id == id)
+                        .map(|t| t.start.date_naive())
+                });
+                Ok(task_date.unwrap_or(position.date))
+            }
+        }
     }
 
     fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
@@ -775,10 +2549,26 @@ impl App {
                 break;
             }
 
-            if let Ok(event) = event::read() {
-                if let Event::Key(key_event) = event {
+            if self.change_passphrase {
+                ratatui::restore();
+                let result = self.change_passphrase();
+                terminal = ratatui::init();
+                if let Err(e) = result {
+                    self.status_message = Some(format!("Passphrase not changed: {}", e));
+                }
+                continue;
+            }
+
+            // Wake up periodically to pick up changes other processes made to the data file
+            if event::poll(FILE_POLL_INTERVAL)? {
+                if let Ok(Event::Key(key_event)) = event::read() {
                     self.handle_key_event(key_event)?;
                 }
+            } else {
+                self.retry_lock()?;
+                if self.store.changed_on_disk() {
+                    self.save()?;
+                }
             }
         }
         Ok(())
@@ -799,12 +2589,32 @@ impl App {
         ])
         .split(area);
 
-        // Render main content
+        // Render main content, with the smart lists sidebar on the left and
+        // the undo tree panel on the right if shown
+        let main_area = if let AppMode::SmartLists(state) = &self.mode {
+            let columns =
+                Layout::horizontal([Constraint::Length(SIDEBAR_WIDTH), Constraint::Min(0)])
+                    .split(layout[0]);
+            let lists = self.filtered_lists(state.query.as_deref());
+            render_smart_lists(frame, columns[0], state, &lists, &self.config);
+            columns[1]
+        } else {
+            layout[0]
+        };
+        let main_area = if self.show_undo_tree {
+            let columns =
+                Layout::horizontal([Constraint::Min(0), Constraint::Length(UNDO_TREE_WIDTH)])
+                    .split(main_area);
+            self.render_undo_tree(frame, columns[1]);
+            columns[0]
+        } else {
+            main_area
+        };
         render_month_view(
             frame,
-            layout[0],
+            main_area,
             &self.month_view,
-            &self.data.events,
+            &self.visible_tasks(),
             self.scramble_mode,
             &self.config,
         );
@@ -820,10 +2630,98 @@ impl App {
             AppMode::Command(_) => {
                 // Command mode is handled in the footer
             }
+            AppMode::CommandWindow(state) => {
+                self.render_command_window(frame, layout[0], state);
+            }
+            AppMode::Snooze(state) => {
+                render_snooze_popup(frame, area, state, &self.config);
+            }
+            AppMode::SmartLists(_) => {
+                // The sidebar is drawn beside the calendar
+            }
             AppMode::Normal => {}
         }
     }
 
+    /// The q: window over the bottom of the calendar, newest command last
+    fn render_command_window(&self, frame: &mut Frame, area: Rect, state: &CommandWindowState) {
+        let colors = &self.config.ui_colors;
+        let entries = self.command_history.entries();
+        let height = (entries.len() as u16 + 2).min(area.height / 2).max(3);
+        let area = Rect {
+            y: area.y + area.height - height,
+            height,
+            ..area
+        };
+        // Scroll so the selected command stays in view
+        let rows = (height - 2) as usize;
+        let first = (state.selected + 1).saturating_sub(rows);
+        let lines: Vec<Line> = entries
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(rows)
+            .map(|(i, command)| {
+                let style = if i == state.selected {
+                    Style::default()
+                        .bg(colors.selected_task_bg)
+                        .fg(colors.selected_task_fg)
+                } else {
+                    Style::default().fg(colors.default_fg)
+                };
+                Line::styled(format!(":{}", command), style)
+            })
+            .collect();
+
+        let window = Paragraph::new(lines).block(
+            Block::default()
+                .title("Command history")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(colors.selected_completed_task_bg)),
+        );
+        frame.render_widget(Clear, area);
+        frame.render_widget(window, area);
+    }
+
+    fn render_undo_tree(&self, frame: &mut Frame, area: Rect) {
+        let colors = &self.config.ui_colors;
+        let lines: Vec<Line> = self
+            .undo_tree
+            .lines()
+            .into_iter()
+            .map(|line| {
+                let time = line
+                    .time
+                    .map(|t| t.with_timezone(&Local).format("%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_default();
+                let text = format!(
+                    "{}{}* {:>3} {:<14} {}",
+                    if line.current { ">" } else { " " },
+                    "  ".repeat(line.column),
+                    line.seq,
+                    time,
+                    line.summary
+                );
+                let style = if line.current {
+                    Style::default()
+                        .bg(colors.selected_task_bg)
+                        .fg(colors.selected_task_fg)
+                } else {
+                    Style::default().fg(colors.default_fg)
+                };
+                Line::styled(text, style)
+            })
+            .collect();
+
+        let panel = Paragraph::new(lines).block(
+            Block::default()
+                .title("Undo tree (g- / g+)")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(colors.selected_completed_task_bg)),
+        );
+        frame.render_widget(panel, area);
+    }
+
     fn render_footer(&self, frame: &mut Frame, area: Rect) {
         match &self.mode {
             AppMode::Command(state) => {
@@ -838,7 +2736,7 @@ impl App {
                 if state.show_help {
                     let help_lines = vec![
                         Line::from(vec![Span::styled(
-                            "Date Navigation Commands:",
+                            "Dates and Ranges:",
                             Style::default().fg(self.config.ui_colors.selected_task_fg),
                         )]),
                         Line::from(vec![
@@ -851,14 +2749,41 @@ impl App {
                                 "DD",
                                 Style::default().fg(self.config.ui_colors.selected_task_bg),
                             ),
-                            Span::raw(" - Go to day in current month (e.g., 15)"),
+                            Span::raw(" - Go to day in current month (e.g., 15) | "),
+                            Span::styled(
+                                "MM/DD/YYYY",
+                                Style::default().fg(self.config.ui_colors.selected_task_bg),
+                            ),
+                            Span::raw(" - Go to specific date"),
                         ]),
                         Line::from(vec![
                             Span::styled(
-                                "MM/DD/YYYY",
+                                "tomorrow, next fri, +3d, -2w, eom, first mon of july",
+                                Style::default().fg(self.config.ui_colors.selected_task_bg),
+                            ),
+                            Span::raw(" - Date expressions, also in :add, :edit and the editor"),
+                        ]),
+                        Line::from(vec![
+                            Span::styled(
+                                "a,b",
+                                Style::default().fg(self.config.ui_colors.selected_task_bg),
+                            ),
+                            Span::raw(" - Range before a command (e.g., .,.+6delete) | "),
+                            Span::styled(
+                                ".",
+                                Style::default().fg(self.config.ui_colors.selected_task_bg),
+                            ),
+                            Span::raw(" - Selected day | "),
+                            Span::styled(
+                                "%",
+                                Style::default().fg(self.config.ui_colors.selected_task_bg),
+                            ),
+                            Span::raw(" - Shown month | "),
+                            Span::styled(
+                                "'a",
                                 Style::default().fg(self.config.ui_colors.selected_task_bg),
                             ),
-                            Span::raw(" - Go to specific date (e.g., 06/15/2024)"),
+                            Span::raw(" - Day of mark a"),
                         ]),
                         Line::from(vec![
                             Span::styled(
@@ -896,6 +2821,12 @@ impl App {
                                     .fg(self.config.ui_colors.selected_completed_task_fg),
                             ),
                             Span::raw(" - Toggle this help | "),
+                            Span::styled(
+                                "Tab",
+                                Style::default()
+                                    .fg(self.config.ui_colors.selected_completed_task_fg),
+                            ),
+                            Span::raw(" - Complete | "),
                             Span::styled(
                                 "Esc",
                                 Style::default()
@@ -906,7 +2837,26 @@ impl App {
                     ];
                     lines.extend(help_lines)
                 }
-                if !has_error_or_help {
+                if let Some(completion) = state.completion.as_ref().filter(|c| c.candidates.len() > 1) {
+                    // Candidates Tab cycles through, the current one highlighted
+                    let mut spans = Vec::new();
+                    for (i, candidate) in completion.candidates.iter().enumerate() {
+                        let style = if i == completion.index {
+                            Style::default()
+                                .fg(self.config.ui_colors.selected_task_fg)
+                                .bg(self.config.ui_colors.selected_task_bg)
+                        } else {
+                            Style::default()
+                        };
+                        spans.push(Span::styled(candidate.clone(), style));
+                        spans.push(Span::raw("  "));
+                    }
+                    lines.push(Line::from(spans));
+                }
+                if let Some(pattern) = &state.search {
+                    let search_line = format!("(reverse-i-search)'{}': {}", pattern, state.input);
+                    lines.push(Line::from(vec![Span::raw(search_line)]));
+                } else if !has_error_or_help {
                     let command_line = format!(":{}", state.input);
                     lines.push(Line::from(vec![Span::raw(command_line)]));
                 }
@@ -918,20 +2868,57 @@ impl App {
                 frame.render_widget(help_paragraph, area);
             }
             AppMode::Normal => {
+                let mut lines = vec![];
+                if let Some(message) = &self.status_message {
+                    lines.push(Line::from(vec![Span::styled(
+                        message.clone(),
+                        Style::default().fg(self.config.ui_colors.selected_completed_task_fg),
+                    )]));
+                } else if let Some(name) = self.macros.recording() {
+                    lines.push(Line::from(vec![Span::styled(
+                        format!("recording @{}", name),
+                        Style::default().fg(self.config.ui_colors.selected_completed_task_fg),
+                    )]));
+                } else if let Some(pid) = self.locked_by {
+                    lines.push(Line::from(vec![Span::styled(
+                        format!("[read-only] locked by taskim (pid {})", pid),
+                        Style::default().fg(self.config.ui_colors.selected_completed_task_fg),
+                    )]));
+                }
                 if self.show_keybinds {
                     let spans = self.config.get_normal_mode_help_spans(
-                        self.undo_stack.can_undo(),
-                        self.undo_stack.can_redo(),
+                        self.undo_tree.can_undo(),
+                        self.undo_tree.can_redo(),
                     );
-                    let help_text = vec![Line::from(spans)];
-                    let footer = Paragraph::new(help_text)
-                        .style(Style::default().fg(self.config.ui_colors.default_fg));
-                    frame.render_widget(footer, area);
-                } else {
-                    let footer = Paragraph::new("")
-                        .style(Style::default().fg(self.config.ui_colors.default_fg));
-                    frame.render_widget(footer, area);
+                    lines.push(Line::from(spans));
                 }
+                let footer = Paragraph::new(lines)
+                    .style(Style::default().fg(self.config.ui_colors.default_fg));
+                frame.render_widget(footer, area);
+            }
+            AppMode::CommandWindow(_) => {
+                let footer = Paragraph::new(Line::from(
+                    "Enter - Run | i - Edit | x - Forget | j/k - Move | Esc - Close",
+                ))
+                .style(Style::default().fg(self.config.ui_colors.default_fg));
+                frame.render_widget(footer, area);
+            }
+            AppMode::Snooze(state) => {
+                let help = if state.picking.is_some() {
+                    "Type a date (e.g. friday, +3d, 2025-07-01) | Enter - Snooze | Esc - Back"
+                } else {
+                    "t/d/w/m - Snooze | p - Pick date | j/k - Move | Enter - Choose | Esc - Cancel"
+                };
+                let footer = Paragraph::new(Line::from(help))
+                    .style(Style::default().fg(self.config.ui_colors.default_fg));
+                frame.render_widget(footer, area);
+            }
+            AppMode::SmartLists(_) => {
+                let footer = Paragraph::new(Line::from(
+                    "h/l - List | 1-9 - Pick list | j/k - Move | Enter - Go to task | Esc - Close",
+                ))
+                .style(Style::default().fg(self.config.ui_colors.default_fg));
+                frame.render_widget(footer, area);
             }
             AppMode::TaskEdit(_) => {
                 let spans = self.config.get_edit_mode_help_spans();
@@ -944,10 +2931,58 @@ impl App {
     }
 }
 
+/// Tasks of the lists not in `hidden_lists`
+fn shown_tasks<'a>(
+    data: &'a TaskData,
+    hidden_lists: &HashSet<String>,
+    config: &crate::config::Config,
+) -> Vec<&'a Task> {
+    data.events
+        .iter()
+        .filter(|t| !hidden_lists.contains(config.list_name(t)))
+        .collect()
+}
+
+/// The day `:move <date>` sends a task from a given day to; `+N`/`-N`
+/// offsets count from that day, other dates from today
+fn move_date(text: &str) -> Result<impl Fn(chrono::NaiveDate) -> chrono::NaiveDate + '_, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(String::from("Usage: :move <date>, e.g. :move +1d or :move friday"));
+    }
+    let today = Local::now().date_naive();
+    if dates::parse_date(text, today).is_none() {
+        return Err(format!("Unknown date: {}", text));
+    }
+    let relative = text.starts_with(['+', '-']);
+    Ok(move |date| {
+        let base = if relative { date } else { today };
+        dates::parse_date(text, base).unwrap_or(date)
+    })
+}
+
+/// Tag names given to `:tag`, with or without a leading `#`
+fn tag_names(text: &str) -> Result<Vec<String>, String> {
+    let tags: Vec<String> = text
+        .split_whitespace()
+        .map(|tag| tag.trim_start_matches('#'))
+        .filter(|tag| !tag.is_empty())
+        .map(String::from)
+        .collect();
+    if tags.is_empty() {
+        return Err(String::from("Usage: :tag <tag>..., e.g. :tag work urgent"));
+    }
+    Ok(tags)
+}
+
 fn main() -> Result<()> {
     color_eyre::install()?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(result) = cli::run(&args) {
+        return result;
+    }
+    let app = App::new()?;
     let terminal = ratatui::init();
-    let app = App::new();
     let result = app.run(terminal);
     ratatui::restore();
     result
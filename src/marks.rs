@@ -0,0 +1,198 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Most positions the jump list remembers
+const JUMP_LIST_SIZE: usize = 100;
+
+/// A place in the calendar: a task, with its date as a fallback once the task
+/// is gone, or just a day
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Position {
+    pub date: NaiveDate,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
+}
+
+/// Marks set with `m{a-z}` and jumped to with `'{a-z}`, kept between sessions
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Marks {
+    marks: BTreeMap<char, Position>,
+}
+
+impl Marks {
+    pub fn is_valid(name: char) -> bool {
+        name.is_ascii_alphabetic()
+    }
+
+    pub fn set(&mut self, name: char, position: Position) {
+        self.marks.insert(name, position);
+    }
+
+    pub fn get(&self, name: char) -> Option<&Position> {
+        self.marks.get(&name)
+    }
+
+    /// Remove the named marks, returning how many existed
+    pub fn remove(&mut self, names: &str) -> usize {
+        names
+            .chars()
+            .filter(|name| self.marks.remove(name).is_some())
+            .count()
+    }
+
+    /// Listing for `:marks`, with the title of marked tasks from `title`
+    pub fn describe(&self, title: impl Fn(&str) -> Option<String>) -> String {
+        let mut text = String::from("Marks:\n");
+        for (name, position) in &self.marks {
+            let task = position
+                .task_id
+                .as_deref()
+                .and_then(&title)
+                .map(|t| format!("  \"{}\"", t))
+                .unwrap_or_default();
+            text.push_str(&format!("  {}  {}{}\n", name, position.date, task));
+        }
+        text
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<Vec<u8>> {
+        serde_json::to_vec_pretty(self)
+    }
+
+    pub fn from_json(content: &[u8]) -> serde_json::Result<Self> {
+        serde_json::from_slice(content)
+    }
+}
+
+/// Positions left by large moves, walked with Ctrl-o and Ctrl-i like vim's
+#[derive(Debug, Default)]
+pub struct JumpList {
+    positions: Vec<Position>,
+    /// Where Ctrl-o/Ctrl-i are in `positions`; its length when not walking
+    index: usize,
+}
+
+impl JumpList {
+    /// Remember `from` before a jump away from it
+    pub fn push(&mut self, from: Position) {
+        self.positions.retain(|p| *p != from);
+        self.positions.push(from);
+        if self.positions.len() > JUMP_LIST_SIZE {
+            self.positions.remove(0);
+        }
+        self.index = self.positions.len();
+    }
+
+    /// The position before the current one (Ctrl-o)
+    pub fn back(&mut self, current: Position) -> Option<Position> {
+        if self.index >= self.positions.len() {
+            // Leaving the end of the list: remember where we are to come back
+            self.push(current);
+            self.index = self.positions.len() - 1;
+        }
+        if self.index == 0 {
+            return None;
+        }
+        self.index -= 1;
+        self.positions.get(self.index).cloned()
+    }
+
+    /// The position after the current one (Ctrl-i)
+    pub fn forward(&mut self) -> Option<Position> {
+        if self.index + 1 >= self.positions.len() {
+            return None;
+        }
+        self.index += 1;
+        self.positions.get(self.index).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u32) -> Position {
+        Position {
+            date: NaiveDate::from_ymd_opt(2025, 6, d).unwrap(),
+            task_id: None,
+        }
+    }
+
+    fn task(d: u32, id: &str) -> Position {
+        Position {
+            task_id: Some(id.to_string()),
+            ..day(d)
+        }
+    }
+
+    #[test]
+    fn back_and_forward_stop_at_both_ends() {
+        let mut jumps = JumpList::default();
+        assert_eq!(jumps.back(day(9)), None);
+        assert_eq!(jumps.forward(), None);
+
+        let mut jumps = JumpList::default();
+        jumps.push(day(1));
+        jumps.push(day(2));
+        assert_eq!(jumps.forward(), None);
+        assert_eq!(jumps.back(day(3)), Some(day(2)));
+        assert_eq!(jumps.back(day(2)), Some(day(1)));
+        assert_eq!(jumps.back(day(1)), None);
+        assert_eq!(jumps.forward(), Some(day(2)));
+        // Back at the day Ctrl-o left from
+        assert_eq!(jumps.forward(), Some(day(3)));
+        assert_eq!(jumps.forward(), None);
+    }
+
+    #[test]
+    fn a_jump_moves_a_known_position_to_the_end() {
+        let mut jumps = JumpList::default();
+        jumps.push(day(1));
+        jumps.push(task(2, "a"));
+        jumps.push(day(2));
+        jumps.push(day(1));
+        assert_eq!(jumps.back(day(5)), Some(day(1)));
+        assert_eq!(jumps.back(day(1)), Some(day(2)));
+        assert_eq!(jumps.back(day(2)), Some(task(2, "a")));
+        assert_eq!(jumps.back(task(2, "a")), None);
+    }
+
+    #[test]
+    fn the_oldest_jumps_are_forgotten() {
+        let mut jumps = JumpList::default();
+        for n in 0..=JUMP_LIST_SIZE {
+            jumps.push(task(1, &n.to_string()));
+        }
+        // The first Ctrl-o also remembers the current day, pushing out one more
+        let mut oldest = None;
+        while let Some(position) = jumps.back(day(2)) {
+            oldest = Some(position);
+        }
+        assert_eq!(oldest, Some(task(1, "2")));
+    }
+
+    #[test]
+    fn marks_are_set_removed_and_kept_as_json() {
+        let mut marks = Marks::default();
+        marks.set('a', task(3, "x"));
+        marks.set('b', day(4));
+        marks.set('a', task(5, "y"));
+        assert_eq!(marks.get('a'), Some(&task(5, "y")));
+        assert!(Marks::is_valid('Z'));
+        assert!(!Marks::is_valid('1'));
+
+        let title = |id: &str| (id == "y").then(|| String::from("Dentist"));
+        assert_eq!(
+            marks.describe(title),
+            "Marks:\n  a  2025-06-05  \"Dentist\"\n  b  2025-06-04\n"
+        );
+
+        let json = marks.to_json().unwrap();
+        let mut marks = Marks::from_json(&json).unwrap();
+        assert_eq!(marks.get('b'), Some(&day(4)));
+        assert_eq!(marks.remove("bcb"), 1);
+        assert_eq!(marks.get('b'), None);
+        assert_eq!(marks.get('a'), Some(&task(5, "y")));
+    }
+}
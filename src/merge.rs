@@ -0,0 +1,137 @@
+use crate::task::Task;
+use std::collections::{HashMap, HashSet};
+
+/// A task changed differently on both sides since the common base
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub ours: Option<Task>,
+    pub theirs: Option<Task>,
+}
+
+#[derive(Debug, Default)]
+pub struct MergeResult {
+    pub tasks: Vec<Task>,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Three-way merge of task lists by id.
+///
+/// Changes made on only one side are kept. When both sides changed the same
+/// task differently, their version wins and the conflict is reported so the
+/// caller can tell the user instead of silently overwriting either side.
+pub fn merge_by_id(base: &HashMap<String, Task>, ours: &[Task], theirs: &[Task]) -> MergeResult {
+    let ours_by_id: HashMap<&str, &Task> = ours.iter().map(|t| (t.id.as_str(), t)).collect();
+    let theirs_by_id: HashMap<&str, &Task> = theirs.iter().map(|t| (t.id.as_str(), t)).collect();
+
+    // Keep our ordering, followed by tasks only they have
+    let mut ids: Vec<&str> = ours.iter().map(|t| t.id.as_str()).collect();
+    let mut seen: HashSet<&str> = ids.iter().copied().collect();
+    for task in theirs {
+        if seen.insert(task.id.as_str()) {
+            ids.push(task.id.as_str());
+        }
+    }
+    for id in base.keys() {
+        if seen.insert(id.as_str()) {
+            ids.push(id.as_str());
+        }
+    }
+
+    let mut result = MergeResult::default();
+    for id in ids {
+        let base_task = base.get(id);
+        let our_task = ours_by_id.get(id).copied();
+        let their_task = theirs_by_id.get(id).copied();
+
+        let merged = if our_task == their_task || our_task == base_task {
+            their_task
+        } else if their_task == base_task {
+            our_task
+        } else {
+            result.conflicts.push(Conflict {
+                ours: our_task.cloned(),
+                theirs: their_task.cloned(),
+            });
+            their_task
+        };
+
+        if let Some(task) = merged {
+            result.tasks.push(task.clone());
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn task(id: &str, title: &str) -> Task {
+        let mut task = Task::new(title.to_string(), Utc.with_ymd_and_hms(2025, 3, 1, 9, 0, 0).unwrap());
+        task.id = id.to_string();
+        task
+    }
+
+    fn base(tasks: &[Task]) -> HashMap<String, Task> {
+        tasks.iter().map(|t| (t.id.clone(), t.clone())).collect()
+    }
+
+    fn titles(result: &MergeResult) -> Vec<&str> {
+        result.tasks.iter().map(|t| t.title.as_str()).collect()
+    }
+
+    #[test]
+    fn keeps_changes_made_on_one_side() {
+        let original = [task("a", "a"), task("b", "b")];
+        let ours = [task("a", "a ours"), task("b", "b")];
+        let theirs = [task("a", "a"), task("b", "b theirs"), task("c", "c")];
+        let result = merge_by_id(&base(&original), &ours, &theirs);
+        assert_eq!(titles(&result), ["a ours", "b theirs", "c"]);
+        assert!(result.conflicts.is_empty());
+    }
+
+    #[test]
+    fn deletions_on_either_side_are_kept() {
+        let original = [task("a", "a"), task("b", "b")];
+        let ours = [task("b", "b")];
+        let theirs = [task("a", "a")];
+        let result = merge_by_id(&base(&original), &ours, &theirs);
+        assert!(result.tasks.is_empty());
+        assert!(result.conflicts.is_empty());
+    }
+
+    #[test]
+    fn both_sides_editing_keeps_theirs_and_reports_it() {
+        let original = [task("a", "a")];
+        let result = merge_by_id(&base(&original), &[task("a", "ours")], &[task("a", "theirs")]);
+        assert_eq!(titles(&result), ["theirs"]);
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].ours.as_ref().unwrap().title, "ours");
+    }
+
+    #[test]
+    fn our_edit_of_a_task_they_deleted_is_a_conflict() {
+        let original = [task("a", "a"), task("b", "b")];
+        let result = merge_by_id(&base(&original), &[task("a", "edited"), task("b", "b")], &[task("b", "b")]);
+        assert_eq!(titles(&result), ["b"]);
+        assert_eq!(result.conflicts.len(), 1);
+        assert!(result.conflicts[0].theirs.is_none());
+    }
+
+    #[test]
+    fn their_edit_of_a_task_we_deleted_is_a_conflict() {
+        let original = [task("a", "a")];
+        let result = merge_by_id(&base(&original), &[], &[task("a", "edited")]);
+        assert_eq!(titles(&result), ["edited"]);
+        assert_eq!(result.conflicts.len(), 1);
+        assert!(result.conflicts[0].ours.is_none());
+    }
+
+    #[test]
+    fn identical_additions_are_not_conflicts() {
+        let result = merge_by_id(&HashMap::new(), &[task("a", "same")], &[task("a", "same")]);
+        assert_eq!(titles(&result), ["same"]);
+        assert!(result.conflicts.is_empty());
+    }
+}
@@ -87,9 +87,22 @@ impl MonthView {
         self.select_day(target_date);
     }
 
-    // Public method to rebuild weeks for a given date
-    pub fn build_weeks_for_date(date: NaiveDate) -> Vec<Vec<NaiveDate>> {
-        Self::build_weeks(date)
+    /// Show the month of `date` and select `task_id` there, or the day itself
+    pub fn jump_to(&mut self, date: NaiveDate, task_id: Option<String>) {
+        self.navigate_to_date(date);
+        if let Some(task_id) = task_id {
+            self.select_task(task_id);
+        }
+    }
+
+    /// The first and last day in the grid, including the neighbouring months' days
+    pub fn shown_days(&self) -> (NaiveDate, NaiveDate) {
+        let first = self.weeks.first().and_then(|w| w.first());
+        let last = self.weeks.last().and_then(|w| w.last());
+        match (first, last) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => (self.current_date, self.current_date),
+        }
     }
 
     fn build_weeks(date: NaiveDate) -> Vec<Vec<NaiveDate>> {
@@ -149,7 +162,7 @@ impl MonthView {
         self.transition_to_month(new_date);
     }
 
-    pub fn move_up(&mut self, tasks: &[Task]) {
+    pub fn move_up(&mut self, tasks: &[&Task]) {
         match &self.selection.selection_type {
             SelectionType::Day(date) => {
                 let current_date = *date;
@@ -220,7 +233,7 @@ impl MonthView {
         }
     }
 
-    pub fn move_down(&mut self, tasks: &[Task]) {
+    pub fn move_down(&mut self, tasks: &[&Task]) {
         match &self.selection.selection_type {
             SelectionType::Day(date) => {
                 let current_date = *date;
@@ -289,7 +302,7 @@ impl MonthView {
         }
     }
 
-    pub fn move_left(&mut self, _tasks: &[Task]) {
+    pub fn move_left(&mut self, _tasks: &[&Task]) {
         match &self.selection.selection_type {
             SelectionType::Day(date) => {
                 if let Some(new_date) = date.checked_sub_signed(chrono::Duration::days(1)) {
@@ -306,7 +319,7 @@ impl MonthView {
         }
     }
 
-    pub fn move_right(&mut self, _tasks: &[Task]) {
+    pub fn move_right(&mut self, _tasks: &[&Task]) {
         match &self.selection.selection_type {
             SelectionType::Day(date) => {
                 if let Some(new_date) = date.checked_add_signed(chrono::Duration::days(1)) {
@@ -331,12 +344,12 @@ impl MonthView {
     }
 
     // Get the currently selected date
-    pub fn get_selected_date(&self, tasks: &[Task]) -> NaiveDate {
+    pub fn get_selected_date<'a>(&self, tasks: impl IntoIterator<Item = &'a Task>) -> NaiveDate {
         match &self.selection.selection_type {
             SelectionType::Day(date) => *date,
             SelectionType::Task(task_id) => {
                 // Find the actual task and return its date
-                if let Some(task) = tasks.iter().find(|t| &t.id == task_id) {
+                if let Some(task) = tasks.into_iter().find(|t| &t.id == task_id) {
                     task.start.date_naive()
                 } else {
                     // Fallback to current date if task not found
@@ -347,16 +360,16 @@ impl MonthView {
     }
 
     // Move to next week (same day of week)
-    pub fn next_week(&mut self, tasks: &[Task]) {
-        let current_selected = self.get_selected_date(tasks);
+    pub fn next_week(&mut self, tasks: &[&Task]) {
+        let current_selected = self.get_selected_date(tasks.iter().copied());
         if let Some(new_date) = current_selected.checked_add_signed(chrono::Duration::weeks(1)) {
             self.navigate_to_date(new_date);
         }
     }
 
     // Move to previous week (same day of week)
-    pub fn prev_week(&mut self, tasks: &[Task]) {
-        let current_selected = self.get_selected_date(tasks);
+    pub fn prev_week(&mut self, tasks: &[&Task]) {
+        let current_selected = self.get_selected_date(tasks.iter().copied());
         if let Some(new_date) = current_selected.checked_sub_signed(chrono::Duration::weeks(1)) {
             self.navigate_to_date(new_date);
         }
@@ -496,7 +509,7 @@ fn calculate_wrapped_text_height(text: &str, width: usize) -> usize {
         if line.is_empty() {
             total_height += 1;
         } else {
-            total_height += (line.len() + width - 1) / width; // Ceiling division
+            total_height += line.len().div_ceil(width);
         }
     }
 
@@ -507,7 +520,7 @@ pub fn render_month_view(
     frame: &mut Frame,
     area: Rect,
     month_view: &MonthView,
-    tasks: &[Task],
+    tasks: &[&Task],
     scramble_mode: bool,
     config: &crate::config::Config,
 ) {
@@ -553,9 +566,9 @@ pub fn render_month_view(
                                         SelectionType::Task(ref task_id) if task_id == &task.id
                                     );
                                     let title_to_measure = if is_selected_task {
-                                        task.title.clone()
+                                        task.display_title()
                                     } else {
-                                        scramble_text(&task.title, scramble_mode)
+                                        scramble_text(&task.display_title(), scramble_mode)
                                     };
                                     calculate_wrapped_text_height(&title_to_measure, task_width)
                                 })
@@ -618,7 +631,7 @@ fn render_day_cell(
     area: Rect,
     date: NaiveDate,
     month_view: &MonthView,
-    tasks: &[Task],
+    tasks: &[&Task],
     scramble_mode: bool,
     config: &crate::config::Config,
 ) {
@@ -626,7 +639,7 @@ fn render_day_cell(
     let is_selected_day = matches!(month_view.selection.selection_type, SelectionType::Day(selected_date) if selected_date == date);
 
     // Get tasks for this day, sorted by order
-    let mut day_tasks: Vec<_> = tasks.iter().filter(|t| t.is_on_date(date)).collect();
+    let mut day_tasks: Vec<&Task> = tasks.iter().copied().filter(|t| t.is_on_date(date)).collect();
     day_tasks.sort_by_key(|t| t.order);
 
     // Day style
@@ -673,7 +686,7 @@ fn render_day_cell(
         .split(inner_area);
 
         // Render day number in top line
-        if day_layout.len() > 0 && day_layout[0].height > 0 {
+        if !day_layout.is_empty() && day_layout[0].height > 0 {
             frame.render_widget(day_paragraph, day_layout[0]);
         }
 
@@ -700,8 +713,7 @@ fn render_tasks_nowrap(
 ) {
     let task_items: Vec<ListItem> = day_tasks
         .iter()
-        .enumerate()
-        .map(|(_index, task)| {
+        .map(|task| {
             let is_selected_task = matches!(
                 month_view.selection.selection_type,
                 SelectionType::Task(ref task_id) if task_id == &task.id
@@ -722,24 +734,24 @@ fn render_tasks_nowrap(
             } else if task.completed && !is_selected_task {
                 Style::default().fg(config.ui_colors.completed_task_fg)
             } else {
-                Style::default().fg(config.ui_colors.default_task_fg)
+                Style::default().fg(config.task_fg(task))
             };
 
             let max_width = area.width.saturating_sub(2) as usize; // Account for list padding
-            let title = if task.title.len() > max_width && max_width > 3 {
+            let title = if task.display_title().len() > max_width && max_width > 3 {
                 // Show unscrambled text for selected task, scrambled for others
                 let display_title = if is_selected_task {
-                    task.title.clone()
+                    task.display_title()
                 } else {
-                    scramble_text(&task.title, scramble_mode)
+                    scramble_text(&task.display_title(), scramble_mode)
                 };
                 format!("{}...", &display_title[..max_width.saturating_sub(3)])
             } else {
                 // Show unscrambled text for selected task, scrambled for others
                 if is_selected_task {
-                    task.title.clone()
+                    task.display_title()
                 } else {
-                    scramble_text(&task.title, scramble_mode)
+                    scramble_text(&task.display_title(), scramble_mode)
                 }
             };
 
@@ -775,9 +787,9 @@ fn render_tasks_wrapped(
                 SelectionType::Task(ref task_id) if task_id == &task.id
             );
             let title_to_measure = if is_selected_task {
-                task.title.clone()
+                task.display_title()
             } else {
-                scramble_text(&task.title, scramble_mode)
+                scramble_text(&task.display_title(), scramble_mode)
             };
             calculate_wrapped_text_height(&title_to_measure, task_width) as u16
         })
@@ -833,15 +845,15 @@ fn render_tasks_wrapped(
         } else if task.completed && !is_selected_task {
             Style::default().fg(config.ui_colors.completed_task_fg)
         } else {
-            Style::default()
+            Style::default().fg(config.task_fg(task))
         };
 
         let paragraph = Paragraph::new(
             // Show unscrambled text for selected task, scrambled for others
             if is_selected_task {
-                task.title.clone()
+                task.display_title()
             } else {
-                scramble_text(&task.title, scramble_mode)
+                scramble_text(&task.display_title(), scramble_mode)
             },
         )
         .style(style)
@@ -0,0 +1,139 @@
+use crate::dates;
+use crate::task::Priority;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+
+/// Most words tried together as one date, e.g. "first monday of july 2026"
+const MAX_DATE_WORDS: usize = 5;
+
+/// A task described in one line, e.g. `Call dentist tomorrow 3pm #health !high`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuickTask {
+    pub title: String,
+    pub date: Option<NaiveDate>,
+    pub time: Option<NaiveTime>,
+    pub tags: Vec<String>,
+    pub priority: Option<Priority>,
+}
+
+/// Pick the date, time, `#tags` and `!priority` out of `text`; the other
+/// words make the title
+pub fn parse(text: &str, today: NaiveDate) -> QuickTask {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut task = QuickTask::default();
+    let mut title = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        let word = words[i];
+        i += 1;
+        if let Some(tag) = word.strip_prefix('#').filter(|tag| !tag.is_empty()) {
+            if !task.tags.iter().any(|t| t == tag) {
+                task.tags.push(tag.to_string());
+            }
+        } else if let Some(priority) = word.strip_prefix('!').and_then(Priority::from_name) {
+            task.priority = Some(priority);
+        } else if let Some(time) = dates::parse_time(word).filter(|_| task.time.is_none()) {
+            task.time = Some(time);
+        } else if let Some((date, len)) = date_at(&words[i - 1..], today).filter(|_| task.date.is_none()) {
+            task.date = Some(date);
+            i += len - 1;
+        } else {
+            title.push(word);
+        }
+    }
+    task.title = title.join(" ");
+    task
+}
+
+/// The longest run of words at the start of `words` that reads as a date
+fn date_at(words: &[&str], today: NaiveDate) -> Option<(NaiveDate, usize)> {
+    (1..=MAX_DATE_WORDS.min(words.len()))
+        .rev()
+        .find_map(|len| dates::parse_date(&words[..len].join(" "), today).map(|date| (date, len)))
+}
+
+/// When a task on `date` starts: at `time`, or at 9:00 like tasks made in the editor.
+/// Times are stored as typed (wall-clock time tagged UTC), because tasks are
+/// grouped by the date of `start`; converting from local time would move late
+/// or early tasks to another day.
+pub fn start_time(date: NaiveDate, time: Option<NaiveTime>) -> DateTime<Utc> {
+    let time = time.unwrap_or_else(|| NaiveTime::from_hms_opt(9, 0, 0).unwrap());
+    date.and_time(time).and_utc()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn time(h: u32, m: u32) -> Option<NaiveTime> {
+        NaiveTime::from_hms_opt(h, m, 0)
+    }
+
+    // A Wednesday
+    const TODAY: (i32, u32, u32) = (2025, 6, 11);
+
+    fn today() -> NaiveDate {
+        date(TODAY.0, TODAY.1, TODAY.2)
+    }
+
+    #[test]
+    fn picks_out_date_time_tags_and_priority() {
+        let task = parse("Call dentist tomorrow 3pm #health !high", today());
+        assert_eq!(task.title, "Call dentist");
+        assert_eq!(task.date, Some(date(2025, 6, 12)));
+        assert_eq!(task.time, time(15, 0));
+        assert_eq!(task.tags, ["health"]);
+        assert_eq!(task.priority, Some(Priority::High));
+    }
+
+    #[test]
+    fn reads_the_longest_date_expression() {
+        let task = parse("Team sync first monday of july 2026 15:30", today());
+        assert_eq!(task.title, "Team sync");
+        assert_eq!(task.date, Some(date(2026, 7, 6)));
+        assert_eq!(task.time, time(15, 30));
+        assert_eq!(parse("Report next friday", today()).date, Some(date(2025, 6, 13)));
+    }
+
+    #[test]
+    fn only_the_first_date_and_time_count() {
+        let task = parse("Move friday meeting to monday 9am 10am", today());
+        assert_eq!(task.date, Some(date(2025, 6, 13)));
+        assert_eq!(task.time, time(9, 0));
+        assert_eq!(task.title, "Move meeting to monday 10am");
+    }
+
+    #[test]
+    fn names_and_words_that_look_like_abbreviations_stay_in_the_title() {
+        let titles = ["Lunch with Tom", "Sat exam prep", "Sun screen", "Mon ami", "Wed planning", "Tod's party"];
+        for text in titles {
+            let task = parse(text, today());
+            assert_eq!(task.title, text);
+            assert_eq!(task.date, None, "{}", text);
+        }
+        assert_eq!(parse("Lunch with Tom tmr", today()).date, Some(date(2025, 6, 12)));
+    }
+
+    #[test]
+    fn other_words_stay_in_the_title() {
+        let task = parse("Read 3d chapter ! # !urgent 25:00 13pm", today());
+        assert_eq!(task.title, "Read 3d chapter ! # !urgent 25:00 13pm");
+        assert_eq!((task.date, task.time, task.priority), (None, None, None));
+        assert!(task.tags.is_empty());
+        assert_eq!(parse("#a #a #b", today()).tags, ["a", "b"]);
+        assert_eq!(parse("", today()), QuickTask::default());
+    }
+
+    #[test]
+    fn late_and_early_times_stay_on_their_day() {
+        for typed in [time(23, 0), time(1, 0), time(0, 0), None] {
+            let start = start_time(today(), typed);
+            assert_eq!(start.date_naive(), today(), "{:?}", typed);
+            assert_eq!(start.time(), typed.unwrap_or(time(9, 0).unwrap()));
+            assert_eq!(start, today().and_time(start.time()).and_utc());
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use crate::task::Task;
+use std::collections::BTreeMap;
+
+/// Number of deletes kept in registers 1-9
+const DELETE_RING: u32 = 9;
+
+/// Vim-style registers holding yanked and cut tasks.
+///
+/// `"` is the last yank or delete, `0` the last yank, `1`-`9` the most recent
+/// deletes (newest first) and `a`-`z` are named; `A`-`Z` append to `a`-`z`.
+#[derive(Debug, Default)]
+pub struct Registers {
+    contents: BTreeMap<char, Vec<Task>>,
+}
+
+impl Registers {
+    /// Whether `name` can follow `"` in normal mode
+    pub fn is_valid(name: char) -> bool {
+        name == '"' || name.is_ascii_digit() || name.is_ascii_alphabetic()
+    }
+
+    /// Store yanked tasks in `name` or, without a name, in register 0
+    pub fn yank(&mut self, name: Option<char>, tasks: Vec<Task>) {
+        match name.filter(|&n| n != '"') {
+            Some(name) => self.store(name, tasks),
+            None => {
+                self.contents.insert('0', tasks.clone());
+                self.contents.insert('"', tasks);
+            }
+        }
+    }
+
+    /// Store cut tasks in `name` or, without a name, at the front of the
+    /// numbered delete ring
+    pub fn delete(&mut self, name: Option<char>, tasks: Vec<Task>) {
+        match name.filter(|&n| n != '"') {
+            Some(name) => self.store(name, tasks),
+            None => {
+                for n in (1..DELETE_RING).rev() {
+                    let from = char::from_digit(n, 10).unwrap();
+                    let to = char::from_digit(n + 1, 10).unwrap();
+                    match self.contents.remove(&from) {
+                        Some(tasks) => self.contents.insert(to, tasks),
+                        None => self.contents.remove(&to),
+                    };
+                }
+                self.contents.insert('1', tasks.clone());
+                self.contents.insert('"', tasks);
+            }
+        }
+    }
+
+    /// Tasks in `name`, or in the unnamed register without a name
+    pub fn get(&self, name: Option<char>) -> Option<&[Task]> {
+        let name = name.unwrap_or('"').to_ascii_lowercase();
+        self.contents
+            .get(&name)
+            .map(Vec::as_slice)
+            .filter(|tasks| !tasks.is_empty())
+    }
+
+    /// Listing for `:registers`, the unnamed register first
+    pub fn describe(&self) -> String {
+        let mut text = String::from("Registers:\n");
+        let unnamed = self.contents.get_key_value(&'"');
+        let others = self.contents.iter().filter(|(&name, _)| name != '"');
+        for (name, tasks) in unnamed.into_iter().chain(others) {
+            let titles: Vec<&str> = tasks.iter().map(|t| t.title.as_str()).collect();
+            text.push_str(&format!("  \"{}  {}\n", name, titles.join(" | ")));
+        }
+        text
+    }
+
+    /// Named registers replace their contents; uppercase names append
+    fn store(&mut self, name: char, tasks: Vec<Task>) {
+        let register = name.to_ascii_lowercase();
+        let contents = self.contents.entry(register).or_default();
+        if !name.is_ascii_uppercase() {
+            contents.clear();
+        }
+        contents.extend(tasks);
+        let contents = contents.clone();
+        self.contents.insert('"', contents);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn tasks(titles: &[&str]) -> Vec<Task> {
+        titles
+            .iter()
+            .map(|title| Task::new(title.to_string(), Utc::now()))
+            .collect()
+    }
+
+    fn titles(tasks: Option<&[Task]>) -> Vec<&str> {
+        tasks
+            .unwrap_or_default()
+            .iter()
+            .map(|t| t.title.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn yanks_fill_register_0_and_deletes_the_ring() {
+        let mut registers = Registers::default();
+        registers.yank(None, tasks(&["kept"]));
+        registers.delete(None, tasks(&["cut"]));
+        assert_eq!(titles(registers.get(Some('0'))), ["kept"]);
+        assert_eq!(titles(registers.get(Some('1'))), ["cut"]);
+        assert_eq!(titles(registers.get(None)), ["cut"]);
+        assert_eq!(titles(registers.get(Some('"'))), ["cut"]);
+    }
+
+    #[test]
+    fn the_delete_ring_keeps_the_nine_newest() {
+        let mut registers = Registers::default();
+        for n in 1..=10 {
+            registers.delete(None, tasks(&[&format!("cut {}", n)]));
+        }
+        assert_eq!(titles(registers.get(Some('1'))), ["cut 10"]);
+        assert_eq!(titles(registers.get(Some('2'))), ["cut 9"]);
+        assert_eq!(titles(registers.get(Some('9'))), ["cut 2"]);
+        assert!(!registers.describe().contains("cut 1\n"));
+        assert_eq!(registers.get(Some('0')), None);
+    }
+
+    #[test]
+    fn uppercase_names_append_to_the_lowercase_register() {
+        let mut registers = Registers::default();
+        registers.yank(Some('a'), tasks(&["one"]));
+        registers.yank(Some('A'), tasks(&["two"]));
+        registers.delete(Some('A'), tasks(&["three"]));
+        assert_eq!(titles(registers.get(Some('a'))), ["one", "two", "three"]);
+        assert_eq!(titles(registers.get(Some('A'))), ["one", "two", "three"]);
+        assert_eq!(titles(registers.get(None)), ["one", "two", "three"]);
+
+        // A lowercase name replaces, and named cuts leave the ring alone
+        registers.delete(Some('a'), tasks(&["four"]));
+        assert_eq!(titles(registers.get(Some('a'))), ["four"]);
+        assert_eq!(registers.get(Some('1')), None);
+        assert_eq!(registers.get(Some('0')), None);
+    }
+
+    #[test]
+    fn empty_and_invalid_registers() {
+        let mut registers = Registers::default();
+        assert_eq!(registers.get(None), None);
+        registers.yank(Some('b'), Vec::new());
+        assert_eq!(registers.get(Some('b')), None);
+        for name in ['"', '0', '9', 'a', 'Z'] {
+            assert!(Registers::is_valid(name), "{}", name);
+        }
+        for name in ['-', '@', ' ', 'é'] {
+            assert!(!Registers::is_valid(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn describe_lists_the_unnamed_register_first() {
+        let mut registers = Registers::default();
+        registers.yank(Some('a'), tasks(&["x", "y"]));
+        registers.yank(None, tasks(&["z"]));
+        assert_eq!(
+            registers.describe(),
+            "Registers:\n  \"\"  z\n  \"0  z\n  \"a  x | y\n"
+        );
+    }
+}
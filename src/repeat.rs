@@ -0,0 +1,78 @@
+/// Where a new task goes relative to the selection
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InsertPosition {
+    /// After the last task of the selected day (`i` on a day)
+    End,
+    /// Below the selected task (`o`)
+    Below,
+    /// Above the selected task (`O`)
+    Above,
+}
+
+/// A normal-mode change that `.` replays on the current selection
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    ToggleComplete,
+    Delete {
+        register: Option<char>,
+    },
+    Paste {
+        above: bool,
+        register: Option<char>,
+    },
+    Insert {
+        position: InsertPosition,
+        title: String,
+        content: String,
+        list: Option<String>,
+    },
+    /// Reschedule the selected task by a number of days
+    Move {
+        days: i64,
+    },
+    /// Swap the selected task with the one below or above it
+    Reorder {
+        down: bool,
+    },
+}
+
+/// The last change and the count it was made with, e.g. `3x`
+#[derive(Debug, Clone)]
+pub struct LastChange {
+    pub change: Change,
+    pub count: usize,
+}
+
+/// Count typed before a normal-mode key, e.g. the `12` in `12j`
+#[derive(Debug, Default)]
+pub struct Count {
+    digits: Option<usize>,
+}
+
+/// Upper bound so a mistyped count can't lock the UI in a huge loop
+const MAX_COUNT: usize = 999;
+
+impl Count {
+    /// Add a typed digit; `0` only counts after another digit since it is a motion
+    pub fn push(&mut self, ch: char) -> bool {
+        let Some(digit) = ch.to_digit(10) else {
+            return false;
+        };
+        if digit == 0 && self.digits.is_none() {
+            return false;
+        }
+        let value = self.digits.unwrap_or(0) * 10 + digit as usize;
+        self.digits = Some(value.min(MAX_COUNT));
+        true
+    }
+
+    /// The typed count, if any, clearing it for the next key
+    pub fn take(&mut self) -> Option<usize> {
+        self.digits.take()
+    }
+
+    /// Keep a count for the second key of a sequence such as `3dd`
+    pub fn restore(&mut self, count: Option<usize>) {
+        self.digits = count;
+    }
+}
@@ -0,0 +1,177 @@
+use crate::task::Task;
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+/// Width of the sidebar left of the calendar
+pub const SIDEBAR_WIDTH: u16 = 44;
+
+/// A smart list or saved filter, with the tasks it matches or why its
+/// expression does not parse
+pub struct FilteredList {
+    pub name: String,
+    pub tasks: Result<Vec<Task>, String>,
+}
+
+/// The sidebar listing the smart lists and saved filters
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmartListsState {
+    /// The list whose tasks are shown
+    pub list: usize,
+    /// The selected task of that list
+    pub task: usize,
+    /// An expression typed with `:filter`, shown after the saved filters
+    pub query: Option<String>,
+}
+
+/// What a key in the sidebar asks for
+pub enum SmartListsAction {
+    None,
+    Close,
+    /// Select the task at this index of the shown list in the calendar
+    Jump(usize),
+}
+
+impl SmartListsState {
+    pub fn new(list: usize, query: Option<String>) -> Self {
+        Self {
+            list,
+            task: 0,
+            query,
+        }
+    }
+
+    /// h/l or Tab/Shift-Tab switch lists (1-9 pick one), j/k choose a task
+    /// and Enter selects it in the calendar
+    pub fn handle_key(
+        &mut self,
+        code: crossterm::event::KeyCode,
+        lists: usize,
+        tasks: usize,
+    ) -> SmartListsAction {
+        use crossterm::event::KeyCode;
+        match code {
+            KeyCode::Esc | KeyCode::Char('q') => return SmartListsAction::Close,
+            KeyCode::Char('l') | KeyCode::Right | KeyCode::Tab => {
+                self.select_list((self.list + 1) % lists.max(1));
+            }
+            KeyCode::Char('h') | KeyCode::Left | KeyCode::BackTab => {
+                self.select_list((self.list + lists.max(1) - 1) % lists.max(1));
+            }
+            KeyCode::Char(ch @ '1'..='9') => {
+                let index = ch as usize - '1' as usize;
+                if index < lists {
+                    self.select_list(index);
+                }
+            }
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.task = (self.task + 1).min(tasks.saturating_sub(1));
+            }
+            KeyCode::Char('k') | KeyCode::Up => self.task = self.task.saturating_sub(1),
+            KeyCode::Char('g') | KeyCode::Home => self.task = 0,
+            KeyCode::Char('G') | KeyCode::End => self.task = tasks.saturating_sub(1),
+            KeyCode::Enter if self.task < tasks => return SmartListsAction::Jump(self.task),
+            _ => {}
+        }
+        SmartListsAction::None
+    }
+
+    fn select_list(&mut self, list: usize) {
+        self.list = list;
+        self.task = 0;
+    }
+}
+
+pub fn render_smart_lists(
+    frame: &mut Frame,
+    area: Rect,
+    state: &SmartListsState,
+    lists: &[FilteredList],
+    config: &crate::config::Config,
+) {
+    let colors = &config.ui_colors;
+    let selected = Style::default()
+        .bg(colors.selected_task_bg)
+        .fg(colors.selected_task_fg);
+    let border = Style::default().fg(colors.selected_completed_task_bg);
+
+    let names: Vec<Line> = lists
+        .iter()
+        .enumerate()
+        .map(|(i, list)| {
+            let count = match &list.tasks {
+                Ok(tasks) => tasks.len().to_string(),
+                Err(_) => String::from("!"),
+            };
+            let text = format!("{} {:<34}{:>4}", i + 1, list.name, count);
+            if i == state.list {
+                Line::styled(text, selected.add_modifier(Modifier::BOLD))
+            } else {
+                Line::styled(text, Style::default().fg(colors.default_fg))
+            }
+        })
+        .collect();
+    let rows = Layout::vertical([Constraint::Length(names.len() as u16 + 2), Constraint::Min(0)])
+        .split(area);
+
+    let tasks: Vec<Line> = match lists.get(state.list).map(|l| &l.tasks) {
+        Some(Ok(tasks)) if tasks.is_empty() => vec![Line::from(" No tasks")],
+        Some(Ok(tasks)) => {
+            // Scroll so the selected task stays in view
+            let height = rows[1].height.saturating_sub(2) as usize;
+            let first = (state.task + 1).saturating_sub(height);
+            tasks
+                .iter()
+                .enumerate()
+                .skip(first)
+                .take(height)
+                .map(|(i, task)| {
+                    let text = format!(
+                        "{} [{}] {}",
+                        task.start.format("%a %m-%d"),
+                        if task.completed { "x" } else { " " },
+                        task.display_title()
+                    );
+                    let style = if i == state.task {
+                        selected
+                    } else if task.completed {
+                        Style::default().fg(colors.completed_task_fg)
+                    } else {
+                        Style::default().fg(config.task_fg(task))
+                    };
+                    Line::styled(text, style)
+                })
+                .collect()
+        }
+        Some(Err(error)) => vec![Line::styled(
+            format!(" {}", error),
+            Style::default().fg(ratatui::style::Color::Red),
+        )],
+        None => Vec::new(),
+    };
+    let title = lists.get(state.list).map_or("", |l| l.name.as_str());
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(names).block(
+            Block::default()
+                .title("Lists (h/l)")
+                .borders(Borders::ALL)
+                .border_style(border),
+        ),
+        rows[0],
+    );
+    frame.render_widget(
+        Paragraph::new(tasks).block(
+            Block::default()
+                .title(title.to_string())
+                .borders(Borders::ALL)
+                .border_style(border),
+        ),
+        rows[1],
+    );
+}
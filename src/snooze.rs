@@ -0,0 +1,244 @@
+use crate::dates;
+use chrono::NaiveDate;
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+/// Snooze presets as (key, label, date expression); the last one asks for a date
+const PRESETS: [(char, &str, &str); 5] = [
+    ('t', "Tomorrow", "tomorrow"),
+    ('d', "Next weekday", "next weekday"),
+    ('w', "Next week", "next week"),
+    ('m', "Next month", "next month"),
+    ('p', "Pick date", ""),
+];
+
+const PICK_DATE: usize = PRESETS.len() - 1;
+
+/// The snooze popup for one task
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnoozeState {
+    pub task_id: String,
+    /// The day presets count from: the task's, or today for an overdue task
+    pub from: NaiveDate,
+    pub selected: usize,
+    /// The date typed for "Pick date", while it is being typed
+    pub picking: Option<String>,
+    pub error: Option<String>,
+}
+
+/// What a key in the popup asks for
+pub enum SnoozeAction {
+    None,
+    Cancel,
+    Snooze(NaiveDate),
+}
+
+impl SnoozeState {
+    pub fn new(task_id: String, task_date: NaiveDate, today: NaiveDate) -> Self {
+        Self {
+            task_id,
+            from: task_date.max(today),
+            selected: 0,
+            picking: None,
+            error: None,
+        }
+    }
+
+    /// The day preset `index` moves the task to
+    fn preset_date(&self, index: usize) -> Option<NaiveDate> {
+        let (_, _, expression) = PRESETS[index];
+        dates::parse_date(expression, self.from)
+    }
+
+    /// j/k or Up/Down choose a preset, Enter or its letter applies it; a
+    /// picked date is typed as any date expression, counted from `today`
+    pub fn handle_key(&mut self, code: crossterm::event::KeyCode, today: NaiveDate) -> SnoozeAction {
+        use crossterm::event::KeyCode;
+        if let Some(text) = &mut self.picking {
+            match code {
+                KeyCode::Esc => {
+                    self.picking = None;
+                    self.error = None;
+                }
+                KeyCode::Enter => match dates::parse_date(text, today) {
+                    Some(date) => return SnoozeAction::Snooze(date),
+                    None => self.error = Some(format!("Unknown date: {}", text.trim())),
+                },
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                KeyCode::Char(ch) => text.push(ch),
+                _ => {}
+            }
+            return SnoozeAction::None;
+        }
+        let chosen = match code {
+            KeyCode::Esc | KeyCode::Char('q') => return SnoozeAction::Cancel,
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.selected = (self.selected + 1).min(PRESETS.len() - 1);
+                None
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.selected = self.selected.saturating_sub(1);
+                None
+            }
+            KeyCode::Enter => Some(self.selected),
+            KeyCode::Char(ch) => PRESETS.iter().position(|(key, _, _)| *key == ch),
+            _ => None,
+        };
+        match chosen {
+            Some(PICK_DATE) => {
+                self.selected = PICK_DATE;
+                self.picking = Some(String::new());
+                SnoozeAction::None
+            }
+            Some(index) => self
+                .preset_date(index)
+                .map_or(SnoozeAction::None, SnoozeAction::Snooze),
+            None => SnoozeAction::None,
+        }
+    }
+}
+
+pub fn render_snooze_popup(
+    frame: &mut Frame,
+    area: Rect,
+    state: &SnoozeState,
+    config: &crate::config::Config,
+) {
+    let colors = &config.task_edit_colors;
+    let width = 44.min(area.width);
+    let height = (PRESETS.len() as u16 + 3).min(area.height);
+    let popup_area = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+
+    let mut lines: Vec<Line> = PRESETS
+        .iter()
+        .enumerate()
+        .map(|(i, (key, label, _))| {
+            let detail = match (&state.picking, state.preset_date(i)) {
+                (Some(text), _) if i == PICK_DATE => format!("{}_", text),
+                (_, Some(date)) if i != PICK_DATE => date.format("%a %Y-%m-%d").to_string(),
+                _ => String::new(),
+            };
+            let style = if i == state.selected {
+                Style::default()
+                    .fg(colors.title_selected_fg)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(colors.popup_fg)
+            };
+            Line::from(vec![
+                Span::styled(format!(" {} ", key), Style::default().fg(colors.instructions_key_fg)),
+                Span::styled(format!("{:<14}{}", label, detail), style),
+            ])
+        })
+        .collect();
+    if let Some(error) = &state.error {
+        lines.push(Line::from(Span::styled(
+            format!(" {}", error),
+            Style::default().fg(ratatui::style::Color::Red),
+        )));
+    }
+
+    let block = Block::default()
+        .title("Snooze")
+        .borders(Borders::ALL)
+        .style(Style::default().fg(colors.popup_fg).bg(colors.popup_bg))
+        .border_style(Style::default().fg(colors.border_fg));
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(Paragraph::new(lines).block(block), popup_area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyCode;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn snoozed(action: SnoozeAction) -> Option<NaiveDate> {
+        match action {
+            SnoozeAction::Snooze(date) => Some(date),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn presets_count_from_the_task_or_today_if_it_is_overdue() {
+        let today = date("2025-06-11");
+        // (task day, key, snoozed to)
+        let cases = [
+            ("2025-06-11", 't', "2025-06-12"),
+            ("2025-06-13", 't', "2025-06-14"),
+            ("2025-06-02", 't', "2025-06-12"),
+            ("2025-06-13", 'd', "2025-06-16"),
+            ("2025-06-11", 'd', "2025-06-12"),
+            ("2025-06-11", 'w', "2025-06-16"),
+            ("2025-06-11", 'm', "2025-07-01"),
+        ];
+        for (task_day, key, expected) in cases {
+            let mut state = SnoozeState::new(String::from("id"), date(task_day), today);
+            let action = state.handle_key(KeyCode::Char(key), today);
+            assert_eq!(snoozed(action), Some(date(expected)), "{} {}", task_day, key);
+        }
+    }
+
+    #[test]
+    fn enter_applies_the_selected_preset() {
+        let today = date("2025-06-11");
+        let mut state = SnoozeState::new(String::from("id"), today, today);
+        assert!(snoozed(state.handle_key(KeyCode::Char('k'), today)).is_none());
+        assert_eq!(state.selected, 0);
+        state.handle_key(KeyCode::Char('j'), today);
+        state.handle_key(KeyCode::Down, today);
+        state.handle_key(KeyCode::Up, today);
+        assert_eq!(
+            snoozed(state.handle_key(KeyCode::Enter, today)),
+            Some(date("2025-06-12"))
+        );
+        assert!(matches!(
+            state.handle_key(KeyCode::Char('q'), today),
+            SnoozeAction::Cancel
+        ));
+    }
+
+    #[test]
+    fn a_picked_date_counts_from_today() {
+        let today = date("2025-06-11");
+        let mut state = SnoozeState::new(String::from("id"), date("2025-08-01"), today);
+        assert!(snoozed(state.handle_key(KeyCode::Char('p'), today)).is_none());
+        assert_eq!(state.selected, PICK_DATE);
+        for ch in "+3x".chars() {
+            state.handle_key(KeyCode::Char(ch), today);
+        }
+        assert!(snoozed(state.handle_key(KeyCode::Enter, today)).is_none());
+        assert_eq!(state.error.as_deref(), Some("Unknown date: +3x"));
+        state.handle_key(KeyCode::Backspace, today);
+        state.handle_key(KeyCode::Char('d'), today);
+        assert_eq!(
+            snoozed(state.handle_key(KeyCode::Enter, today)),
+            Some(date("2025-06-14"))
+        );
+
+        // Esc leaves the typed date, then the popup
+        state.handle_key(KeyCode::Esc, today);
+        assert_eq!(state.picking, None);
+        assert_eq!(state.error, None);
+        assert!(matches!(
+            state.handle_key(KeyCode::Esc, today),
+            SnoozeAction::Cancel
+        ));
+    }
+}
@@ -0,0 +1,223 @@
+use crate::task::Task;
+use regex::{Regex, RegexBuilder};
+
+/// A parsed `:s/pattern/replacement/flags`
+#[derive(Debug, Clone)]
+pub struct Substitution {
+    regex: Regex,
+    /// The replacement in the regex crate's syntax (`${1}`, `$$`)
+    replacement: String,
+    /// Replace every match instead of the first (the `g` flag)
+    all: bool,
+}
+
+impl Substitution {
+    /// Parse `/pattern/replacement/flags`; any punctuation can stand in for
+    /// `/`. The replacement may use `&` for the match and `\1` for a group,
+    /// and the flags are `g` (every match) and `i` (ignore case).
+    pub fn parse(args: &str) -> Result<Self, String> {
+        let mut parts = split_delimited(args, 3)?.into_iter();
+        let pattern = parts.next().unwrap_or_default();
+        let replacement = parts.next().unwrap_or_default();
+        let flags = parts.next().unwrap_or_default();
+        let mut all = false;
+        let mut ignore_case = false;
+        for flag in flags.trim().chars() {
+            match flag {
+                'g' => all = true,
+                'i' => ignore_case = true,
+                'I' => ignore_case = false,
+                other => return Err(format!("Unknown flag: {}", other)),
+            }
+        }
+        Ok(Self {
+            regex: build_regex(&pattern, ignore_case)?,
+            replacement: vim_replacement(&replacement),
+            all,
+        })
+    }
+
+    pub fn pattern(&self) -> &str {
+        self.regex.as_str()
+    }
+
+    /// `text` with the pattern replaced, or None if it doesn't match
+    pub fn apply(&self, text: &str) -> Option<String> {
+        if !self.regex.is_match(text) {
+            return None;
+        }
+        let replaced = if self.all {
+            self.regex.replace_all(text, self.replacement.as_str())
+        } else {
+            self.regex.replace(text, self.replacement.as_str())
+        };
+        Some(replaced.into_owned())
+    }
+}
+
+/// A parsed `:g/pattern/command`
+#[derive(Debug, Clone)]
+pub struct Global {
+    pub regex: Regex,
+    pub command: String,
+}
+
+impl Global {
+    pub fn parse(args: &str) -> Result<Self, String> {
+        let mut parts = split_delimited(args, 2)?.into_iter();
+        let pattern = parts.next().unwrap_or_default();
+        let command = parts.next().unwrap_or_default();
+        if command.trim().is_empty() {
+            return Err(String::from("Usage: :g/pattern/command, e.g. :g/report/done"));
+        }
+        Ok(Self {
+            regex: build_regex(&pattern, false)?,
+            command: command.trim().to_string(),
+        })
+    }
+
+    /// Whether the pattern matches the task's title or one of its `#tags`
+    pub fn matches(&self, task: &Task) -> bool {
+        self.regex.is_match(&task.title)
+            || task
+                .tags
+                .iter()
+                .any(|tag| self.regex.is_match(&format!("#{}", tag)))
+    }
+}
+
+fn build_regex(pattern: &str, ignore_case: bool) -> Result<Regex, String> {
+    if pattern.is_empty() {
+        return Err(String::from("Empty pattern"));
+    }
+    RegexBuilder::new(pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|e| format!("Invalid pattern: {}", e))
+}
+
+/// Split `args` at its first character into at most `count` parts, the last
+/// one taking the rest; `\` before the delimiter makes it literal
+fn split_delimited(args: &str, count: usize) -> Result<Vec<String>, String> {
+    let args = args.trim_start();
+    let mut chars = args.chars();
+    let delimiter = match chars.next() {
+        Some(c) if !c.is_alphanumeric() && !c.is_whitespace() && c != '\\' && c != '"' => c,
+        _ => return Err(String::from("Expected a pattern like /pattern/")),
+    };
+    let mut parts = vec![String::new()];
+    while let Some(c) = chars.next() {
+        let full = parts.len() == count;
+        let last = parts.last_mut().unwrap();
+        if full {
+            last.push(c);
+        } else if c == '\\' {
+            match chars.next() {
+                Some(next) if next == delimiter => last.push(next),
+                Some(next) => {
+                    last.push('\\');
+                    last.push(next);
+                }
+                None => last.push('\\'),
+            }
+        } else if c == delimiter {
+            parts.push(String::new());
+        } else {
+            last.push(c);
+        }
+    }
+    Ok(parts)
+}
+
+/// Translate vim's `&`, `\&`, `\0`-`\9` and `\\` into the regex crate's syntax
+fn vim_replacement(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => out.push_str("${0}"),
+            '$' => out.push_str("$$"),
+            '\\' => match chars.next() {
+                Some(d) if d.is_ascii_digit() => out.push_str(&format!("${{{}}}", d)),
+                Some('$') => out.push_str("$$"),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            },
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn substitute(args: &str, text: &str) -> Option<String> {
+        Substitution::parse(args).unwrap().apply(text)
+    }
+
+    #[test]
+    fn replaces_the_first_match_or_all_with_g() {
+        assert_eq!(substitute("/a/o/", "banana"), Some(String::from("bonana")));
+        assert_eq!(substitute("/a/o/g", "banana"), Some(String::from("bonono")));
+        assert_eq!(substitute("/x/o/", "banana"), None);
+        // The flags and the last delimiter are optional
+        assert_eq!(substitute("/a/o", "banana"), Some(String::from("bonana")));
+        assert_eq!(substitute("/a", "banana"), Some(String::from("bnana")));
+        assert_eq!(substitute("/A/o/gi", "bAnana"), Some(String::from("bonono")));
+        assert_eq!(substitute("/A/o/iI", "banana"), None);
+    }
+
+    #[test]
+    fn translates_vim_replacements() {
+        assert_eq!(substitute("/an/[&]/", "banana"), Some(String::from("b[an]ana")));
+        assert_eq!(substitute(r"/(\w+) (\w+)/\2 \1/", "call bob"), Some(String::from("bob call")));
+        assert_eq!(substitute(r"/call/\&/", "call bob"), Some(String::from("& bob")));
+        assert_eq!(substitute("/bob/$5/", "pay bob"), Some(String::from("pay $5")));
+        assert_eq!(substitute(r"/bob/a\\b/", "bob"), Some(String::from(r"a\b")));
+    }
+
+    #[test]
+    fn any_punctuation_delimits_and_a_backslash_escapes_it() {
+        assert_eq!(substitute("#a#o#g", "banana"), Some(String::from("bonono")));
+        assert_eq!(substitute(r"/1\/2/half/", "add 1/2 cup"), Some(String::from("add half cup")));
+        assert_eq!(substitute(r"/\d/N/g", "1 of 23"), Some(String::from("N of NN")));
+        assert_eq!(substitute("|/|-|g", "a/b/c"), Some(String::from("a-b-c")));
+    }
+
+    #[test]
+    fn works_on_multibyte_text() {
+        assert_eq!(substitute("/é/e/g", "café crème"), Some(String::from("cafe crème")));
+        assert_eq!(substitute("/è/e/", "café crème"), Some(String::from("café creme")));
+        assert_eq!(substitute("§日§本§", "日本"), Some(String::from("本本")));
+        assert_eq!(substitute("/./x/g", "日本"), Some(String::from("xx")));
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        for args in ["", "   ", "abc", "é日é本é", "a/b/", r"\a\b\", "\"a\"b\"", "//x/", "/(/x/", "/a/b/x", "/a/b/gz"] {
+            assert!(Substitution::parse(args).is_err(), "{}", args);
+        }
+        for args in ["", "/report/", "/report/  ", "/[/done", "/report"] {
+            assert!(Global::parse(args).is_err(), "{}", args);
+        }
+    }
+
+    #[test]
+    fn global_matches_titles_and_tags() {
+        let global = Global::parse("/^rep/ done ").unwrap();
+        assert_eq!(global.command, "done");
+        let mut task = Task::new(String::from("Report"), Utc::now());
+        assert!(!global.matches(&task));
+        task.title = String::from("report");
+        assert!(global.matches(&task));
+
+        let global = Global::parse("/#work$/delete").unwrap();
+        task.tags = vec![String::from("home"), String::from("work")];
+        assert!(global.matches(&task));
+        task.tags.pop();
+        assert!(!global.matches(&task));
+    }
+}
@@ -2,7 +2,10 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Tag prefix counting how often `:rollover` moved a task, e.g. `rolled:2`
+const ROLLED_TAG: &str = "rolled:";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Task {
     pub id: String,
     pub title: String,
@@ -11,15 +14,58 @@ pub struct Task {
     pub comments: Vec<TaskComment>,
     pub completed: bool,
     pub order: u32, // Task ordering within a day (0-based)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub list: Option<String>, // Named list the task belongs to, None for the default list
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>, // Tags without the leading '#'
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+}
+
+/// How urgent a task is, e.g. `!high` in `:add`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    Medium,
+    High,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Priority {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "low" | "l" | "1" => Some(Self::Low),
+            "medium" | "med" | "m" | "2" => Some(Self::Medium),
+            "high" | "h" | "3" => Some(Self::High),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+        }
+    }
+
+    /// Marker shown before the title in the calendar
+    fn marker(self) -> &'static str {
+        match self {
+            Self::Low => "!",
+            Self::Medium => "!!",
+            Self::High => "!!!",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TaskComment {
     pub id: String,
     pub text: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TaskData {
     pub events: Vec<Task>,
 }
@@ -37,6 +83,9 @@ impl Task {
             comments: vec![],
             completed: false,
             order: 0, // Default order, will be set when inserting
+            list: None,
+            tags: Vec::new(),
+            priority: None,
         }
     }
     
@@ -48,6 +97,36 @@ impl Task {
         self.comments.push(comment);
     }
     
+    /// Title as shown in the calendar, with the priority marker and tags
+    pub fn display_title(&self) -> String {
+        let mut title = match self.priority {
+            Some(priority) => format!("{} {}", priority.marker(), self.title),
+            None => self.title.clone(),
+        };
+        for tag in &self.tags {
+            title.push_str(&format!(" #{}", tag));
+        }
+        title
+    }
+
+    /// Add a tag unless the task already has it
+    pub fn add_tag(&mut self, tag: &str) {
+        if !self.tags.iter().any(|t| t == tag) {
+            self.tags.push(tag.to_string());
+        }
+    }
+
+    /// Count one more `:rollover` in the task's `rolled:N` tag
+    pub fn mark_rolled_over(&mut self) {
+        let count = self
+            .tags
+            .iter()
+            .find_map(|t| t.strip_prefix(ROLLED_TAG)?.parse::<u32>().ok())
+            .unwrap_or(0);
+        self.tags.retain(|t| !t.starts_with(ROLLED_TAG));
+        self.tags.push(format!("{}{}", ROLLED_TAG, count + 1));
+    }
+
     pub fn is_on_date(&self, date: chrono::NaiveDate) -> bool {
         let task_date = self.start.date_naive();
         task_date == date
@@ -64,15 +143,15 @@ impl TaskData {
         tasks
     }
     
-    /// Get the maximum order for tasks on a specific date
-    pub fn max_order_for_date(&self, date: chrono::NaiveDate) -> u32 {
+    /// The order that puts a task at the end of a day, 0 for an empty day
+    pub fn next_order_for_date(&self, date: chrono::NaiveDate) -> u32 {
         self.events.iter()
             .filter(|t| t.is_on_date(date))
-            .map(|t| t.order)
+            .map(|t| t.order + 1)
             .max()
             .unwrap_or(0)
     }
-    
+
     /// Insert a task at a specific order, shifting other tasks down
     pub fn insert_task_at_order(&mut self, mut task: Task, target_order: u32) {
         let date = task.start.date_naive();
@@ -88,6 +167,51 @@ impl TaskData {
         self.events.push(task);
     }
     
+    /// Renumber orders within each day as 0, 1, 2, ... keeping their relative order
+    pub fn normalize_orders(&mut self) {
+        let mut indices: Vec<usize> = (0..self.events.len()).collect();
+        indices.sort_by(|&a, &b| {
+            let (a, b) = (&self.events[a], &self.events[b]);
+            (a.start.date_naive(), a.order, a.start, &a.id)
+                .cmp(&(b.start.date_naive(), b.order, b.start, &b.id))
+        });
+
+        let mut current_date = None;
+        let mut next_order = 0;
+        for index in indices {
+            let date = self.events[index].start.date_naive();
+            if current_date != Some(date) {
+                current_date = Some(date);
+                next_order = 0;
+            }
+            self.events[index].order = next_order;
+            next_order += 1;
+        }
+    }
+
+    /// Incomplete tasks of days before `today`, oldest first
+    pub fn overdue_tasks(&self, today: chrono::NaiveDate) -> Vec<Task> {
+        let mut tasks: Vec<Task> = self.events.iter()
+            .filter(|t| !t.completed && t.start.date_naive() < today)
+            .cloned()
+            .collect();
+        tasks.sort_by_key(|t| (t.start.date_naive(), t.order));
+        tasks
+    }
+
+    /// Move a task to the end of `today` at the same time of day, counting the
+    /// move in its `rolled:N` tag, and return it as moved
+    pub fn roll_over(&mut self, task_id: &str, today: chrono::NaiveDate) -> Option<Task> {
+        let mut rolled = self.remove_task_and_reorder(task_id)?;
+        let offset = today - rolled.start.date_naive();
+        rolled.start += offset;
+        rolled.end += offset;
+        rolled.mark_rolled_over();
+        rolled.order = self.next_order_for_date(today);
+        self.insert_task_at_order(rolled.clone(), rolled.order);
+        Some(rolled)
+    }
+
     /// Remove a task and close the gap in ordering
     pub fn remove_task_and_reorder(&mut self, task_id: &str) -> Option<Task> {
         if let Some(pos) = self.events.iter().position(|t| t.id == task_id) {
@@ -108,10 +232,113 @@ impl TaskData {
     }
 }
 
-impl Default for TaskData {
-    fn default() -> Self {
-        Self {
-            events: vec![],
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, TimeZone};
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 6, day).unwrap()
+    }
+
+    /// A task on June `day` at `hour`, with its title as id
+    fn task(title: &str, day: u32, hour: u32, order: u32) -> Task {
+        let start = Utc.with_ymd_and_hms(2025, 6, day, hour, 0, 0).unwrap();
+        let mut task = Task::new(title.to_string(), start);
+        task.id = title.to_string();
+        task.order = order;
+        task
+    }
+
+    fn day(data: &TaskData, day: u32) -> Vec<(&str, u32)> {
+        data.get_tasks_for_date(date(day))
+            .into_iter()
+            .map(|t| (t.title.as_str(), t.order))
+            .collect()
+    }
+
+    #[test]
+    fn new_tasks_go_to_the_end_of_their_day() {
+        let mut data = TaskData::default();
+        assert_eq!(data.next_order_for_date(date(11)), 0);
+        data.events = vec![task("a", 11, 9, 0), task("b", 11, 8, 4), task("c", 12, 9, 7)];
+        assert_eq!(data.next_order_for_date(date(11)), 5);
+        assert_eq!(data.next_order_for_date(date(12)), 8);
+        assert_eq!(data.next_order_for_date(date(13)), 0);
+    }
+
+    #[test]
+    fn inserting_and_removing_keep_the_day_in_order() {
+        let mut data = TaskData {
+            events: vec![task("a", 11, 9, 0), task("b", 11, 9, 1), task("c", 12, 9, 0)],
+        };
+        data.insert_task_at_order(task("new", 11, 9, 0), 1);
+        assert_eq!(day(&data, 11), [("a", 0), ("new", 1), ("b", 2)]);
+        assert_eq!(day(&data, 12), [("c", 0)]);
+
+        let removed = data.remove_task_and_reorder("a").unwrap();
+        assert_eq!(removed.title, "a");
+        assert_eq!(day(&data, 11), [("new", 0), ("b", 1)]);
+        assert_eq!(data.remove_task_and_reorder("a"), None);
+    }
+
+    #[test]
+    fn normalizing_numbers_each_day_from_zero() {
+        // Shared orders fall back to the time, then the id
+        let mut data = TaskData {
+            events: vec![
+                task("late", 11, 18, 3),
+                task("early", 11, 8, 3),
+                task("first", 11, 20, 1),
+                task("y", 12, 9, 5),
+                task("x", 12, 9, 5),
+                task("gap", 12, 9, 9),
+            ],
+        };
+        data.normalize_orders();
+        assert_eq!(day(&data, 11), [("first", 0), ("early", 1), ("late", 2)]);
+        assert_eq!(day(&data, 12), [("x", 0), ("y", 1), ("gap", 2)]);
+    }
+
+    #[test]
+    fn only_incomplete_tasks_of_past_days_roll_over() {
+        let mut done = task("done", 9, 9, 0);
+        done.completed = true;
+        let data = TaskData {
+            events: vec![
+                task("tuesday", 10, 9, 1),
+                done,
+                task("today", 11, 9, 0),
+                task("monday", 9, 9, 1),
+                task("tomorrow", 12, 9, 0),
+                task("tuesday first", 10, 9, 0),
+            ],
+        };
+        let titles: Vec<String> = data
+            .overdue_tasks(date(11))
+            .into_iter()
+            .map(|t| t.title)
+            .collect();
+        assert_eq!(titles, ["monday", "tuesday first", "tuesday"]);
+        assert!(data.overdue_tasks(date(9)).is_empty());
+    }
+
+    #[test]
+    fn rolled_over_tasks_keep_their_time_and_count_the_moves() {
+        let mut data = TaskData {
+            events: vec![task("old", 9, 14, 0), task("today", 11, 9, 0)],
+        };
+        let rolled = data.roll_over("old", date(11)).unwrap();
+        assert_eq!(rolled.start, Utc.with_ymd_and_hms(2025, 6, 11, 14, 0, 0).unwrap());
+        assert_eq!(rolled.end - rolled.start, chrono::Duration::hours(1));
+        assert_eq!(rolled.tags, ["rolled:1"]);
+        assert_eq!(day(&data, 11), [("today", 0), ("old", 1)]);
+        assert!(day(&data, 9).is_empty());
+
+        data.roll_over("old", date(12));
+        let task = data.events.iter().find(|t| t.id == "old").unwrap();
+        assert_eq!(task.tags, ["rolled:2"]);
+        assert_eq!(day(&data, 12), [("old", 0)]);
+        assert_eq!(data.roll_over("missing", date(12)), None);
     }
 }
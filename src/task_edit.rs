@@ -1,3 +1,4 @@
+use crate::dates;
 use crate::task::Task;
 use chrono::NaiveDate;
 use ratatui::{
@@ -16,12 +17,18 @@ pub struct TaskEditState {
     pub editing_field: EditingField,
     pub is_new_task: bool,
     pub date: NaiveDate,
+    /// The date as typed, any expression `dates::parse_date` reads
+    pub date_text: String,
+    pub list: Option<String>,
+    pub error: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum EditingField {
     Title,
     Content,
+    Date,
+    List,
 }
 
 impl TaskEditState {
@@ -33,6 +40,9 @@ impl TaskEditState {
             editing_field: EditingField::Title,
             is_new_task: true,
             date,
+            date_text: date.format("%Y-%m-%d").to_string(),
+            list: None,
+            error: None,
         }
     }
     
@@ -48,6 +58,9 @@ impl TaskEditState {
             editing_field: EditingField::Title,
             is_new_task: false,
             date: task.start.date_naive(),
+            date_text: task.start.date_naive().format("%Y-%m-%d").to_string(),
+            list: task.list.clone(),
+            error: None,
         }
     }
     
@@ -55,6 +68,8 @@ impl TaskEditState {
         match self.editing_field {
             EditingField::Title => self.title.push(ch),
             EditingField::Content => self.content.push(ch),
+            EditingField::Date => self.date_text.push(ch),
+            EditingField::List => {}
         }
     }
     
@@ -62,22 +77,60 @@ impl TaskEditState {
         match self.editing_field {
             EditingField::Title => { self.title.pop(); },
             EditingField::Content => { self.content.pop(); },
+            EditingField::Date => { self.date_text.pop(); },
+            EditingField::List => {}
         }
     }
     
     pub fn switch_field(&mut self) {
         self.editing_field = match self.editing_field {
             EditingField::Title => EditingField::Content,
-            EditingField::Content => EditingField::Title,
+            EditingField::Content => EditingField::Date,
+            EditingField::Date => EditingField::List,
+            EditingField::List => EditingField::Title,
+        };
+    }
+
+    /// Move the task to the next or previous of `names`; the first one is the
+    /// default list, stored as no list at all
+    pub fn cycle_list(&mut self, names: &[String], forward: bool) {
+        if names.is_empty() {
+            return;
+        }
+        let current = self
+            .list
+            .as_ref()
+            .and_then(|list| names.iter().position(|n| n == list))
+            .unwrap_or(0);
+        let next = if forward {
+            (current + 1) % names.len()
+        } else {
+            (current + names.len() - 1) % names.len()
+        };
+        self.list = if next == 0 {
+            None
+        } else {
+            Some(names[next].clone())
         };
     }
     
+    /// Read the typed date, keeping it for the task if it is one
+    pub fn apply_date(&mut self, today: NaiveDate) -> bool {
+        match dates::parse_date(&self.date_text, today) {
+            Some(date) => {
+                self.date = date;
+                self.error = None;
+                true
+            }
+            None => {
+                self.error = Some(format!("Unknown date: {}", self.date_text.trim()));
+                false
+            }
+        }
+    }
+
     pub fn to_task(&self) -> Task {
-        let start = self.date.and_hms_opt(9, 0, 0).unwrap()
-            .and_local_timezone(chrono::Local)
-            .single()
-            .unwrap()
-            .to_utc();
+        let start = crate::quick_add::start_time(self.date, None);
             
         let mut task = Task::new(self.title.clone(), start);
         
@@ -88,6 +141,7 @@ impl TaskEditState {
         if let Some(ref task_id) = self.task_id {
             task.id = task_id.clone();
         }
+        task.list = self.list.clone();
         
         task
     }
@@ -117,10 +171,12 @@ pub fn render_task_edit_popup(
     let inner_area = block.inner(popup_area);
     frame.render_widget(block, popup_area);
     
-    // Split the inner area for title, content, and instructions
+    // Split the inner area for title, content, date, list, and instructions
     let layout = Layout::vertical([
         Constraint::Length(3), // Title field
         Constraint::Min(3),    // Content field
+        Constraint::Length(3), // Date field
+        Constraint::Length(3), // List field
         Constraint::Length(2), // Instructions
     ]).split(inner_area);
     
@@ -168,22 +224,83 @@ pub fn render_task_edit_popup(
 
     frame.render_widget(content_paragraph, layout[1]);
 
+    // Render date field, with the day it reads as once it differs from the text
+    let date_selected = state.editing_field == EditingField::Date;
+    let date_style = if date_selected {
+        Style::default().fg(colors.content_selected_fg).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().fg(colors.content_fg)
+    };
+    let date_border_style = if date_selected {
+        Style::default().fg(colors.border_selected_fg)
+    } else {
+        Style::default().fg(colors.border_fg)
+    };
+    let date_block = Block::default()
+        .title("Date")
+        .borders(Borders::ALL)
+        .border_style(date_border_style);
+    let today = chrono::Local::now().date_naive();
+    let date_line = match dates::parse_date(&state.date_text, today) {
+        Some(date) if date.format("%Y-%m-%d").to_string() != state.date_text.trim() => {
+            format!("{}  ({})", state.date_text, date.format("%a %Y-%m-%d"))
+        }
+        _ => state.date_text.clone(),
+    };
+    let date_paragraph = Paragraph::new(date_line)
+        .block(date_block)
+        .style(date_style);
+    frame.render_widget(date_paragraph, layout[2]);
+
+    // Render list field
+    let list_selected = state.editing_field == EditingField::List;
+    let list_style = if list_selected {
+        Style::default().fg(colors.content_selected_fg).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().fg(colors.content_fg)
+    };
+    let list_border_style = if list_selected {
+        Style::default().fg(colors.border_selected_fg)
+    } else {
+        Style::default().fg(colors.border_fg)
+    };
+    let list_block = Block::default()
+        .title("List")
+        .borders(Borders::ALL)
+        .border_style(list_border_style);
+    let list_name = state
+        .list
+        .as_deref()
+        .unwrap_or_else(|| config.default_list_name());
+    let list_paragraph = Paragraph::new(format!("< {} >", list_name))
+        .block(list_block)
+        .style(list_style);
+    frame.render_widget(list_paragraph, layout[3]);
+
     // Render instructions
-    let instructions = vec![
+    let mut instructions = vec![
         Line::from(vec![
             Span::styled("Tab", Style::default().fg(colors.instructions_key_fg)),
             Span::raw(": Switch field | "),
+            Span::styled("Left/Right", Style::default().fg(colors.instructions_key_fg)),
+            Span::raw(": Change list | "),
             Span::styled("Enter", Style::default().fg(colors.instructions_key_fg)),
             Span::raw(": Save | "),
             Span::styled("Esc", Style::default().fg(colors.instructions_key_fg)),
             Span::raw(": Cancel"),
         ])
     ];
+    if let Some(error) = &state.error {
+        instructions.push(Line::from(Span::styled(
+            error.as_str(),
+            Style::default().fg(ratatui::style::Color::Red),
+        )));
+    }
 
     let instructions_paragraph = Paragraph::new(instructions)
         .style(Style::default().fg(colors.instructions_fg));
 
-    frame.render_widget(instructions_paragraph, layout[2]);
+    frame.render_widget(instructions_paragraph, layout[4]);
 }
 
 // Helper function to create a centered rectangle
@@ -1,6 +1,9 @@
-use crate::task::Task;
+use crate::task::{Task, TaskData};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Operation {
     DeleteTask {
         task: Task,
@@ -13,59 +16,1073 @@ pub enum Operation {
     CreateTask {
         task: Task,
     },
+    ToggleComplete {
+        task_id: String,
+        title: String,
+        /// Completion after the toggle
+        completed: bool,
+    },
+    /// A task moved to another day, keeping its id
+    MoveTask {
+        task_id: String,
+        title: String,
+        old_start: DateTime<Utc>,
+        old_end: DateTime<Utc>,
+        new_start: DateTime<Utc>,
+        new_end: DateTime<Utc>,
+    },
+    /// Order changes within days, as (task id, old order, new order)
+    Reorder {
+        changes: Vec<(String, u32, u32)>,
+    },
+    /// Several operations undone and redone as one, applied in sequence
+    Batch {
+        description: String,
+        operations: Vec<Operation>,
+    },
 }
 
-#[derive(Debug, Clone)]
-pub struct UndoStack {
-    undo_operations: Vec<Operation>,
-    redo_operations: Vec<Operation>,
+/// A state in the undo tree, reached by applying `operation` to its parent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Node {
+    operation: Operation,
+    time: DateTime<Utc>,
+    parent: Option<usize>,
+    /// Child that redo moves to: the one created or visited last
+    redo_child: Option<usize>,
+}
+
+/// Branching undo history like vim's: making a change after undoing starts a new
+/// branch instead of discarding the undone changes. Nodes are keyed by sequence
+/// number, so iterating them is chronological. A state of `None` is the oldest
+/// remembered state, before any of the nodes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UndoTree {
+    nodes: BTreeMap<usize, Node>,
+    current: Option<usize>,
+    root_redo: Option<usize>,
+    next_seq: usize,
+    #[serde(skip)]
     max_size: usize,
 }
 
-impl UndoStack {
+/// One line of the `:undotree` panel
+pub struct UndoTreeLine {
+    pub seq: usize,
+    /// Branch column, 0 for the first branch
+    pub column: usize,
+    pub time: Option<DateTime<Utc>>,
+    pub summary: String,
+    pub current: bool,
+}
+
+/// How far `:earlier`/`:later` move: a number of changes or a span of time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UndoDistance {
+    Steps(usize),
+    Time(Duration),
+}
+
+impl UndoDistance {
+    /// Parse `3` (changes), `30s`, `10m`, `2h` or `1d`; no argument means one change
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(UndoDistance::Steps(1));
+        }
+        let usage = || format!("Expected a count or {{N}}s/m/h/d, e.g. 3 or 10m, not {}", input);
+        let (number, unit) = match input.char_indices().last() {
+            Some((i, unit)) if unit.is_ascii_alphabetic() => (&input[..i], Some(unit)),
+            _ => (input, None),
+        };
+        let number: i64 = number.parse().map_err(|_| usage())?;
+        if number <= 0 {
+            return Err(format!("The count must be positive, not {}", number));
+        }
+        let duration = match unit {
+            None => return Ok(UndoDistance::Steps(number.try_into().map_err(|_| usage())?)),
+            Some('s') => Duration::try_seconds(number),
+            Some('m') => Duration::try_minutes(number),
+            Some('h') => Duration::try_hours(number),
+            Some('d') => Duration::try_days(number),
+            Some(_) => return Err(usage()),
+        };
+        duration
+            .map(UndoDistance::Time)
+            .ok_or_else(|| format!("Too long a time span: {}", input))
+    }
+}
+
+impl UndoTree {
     pub fn new(max_size: usize) -> Self {
         Self {
-            undo_operations: Vec::new(),
-            redo_operations: Vec::new(),
+            nodes: BTreeMap::new(),
+            current: None,
+            root_redo: None,
+            next_seq: 1,
             max_size,
         }
     }
-    
+
     pub fn push(&mut self, operation: Operation) {
-        self.undo_operations.push(operation);
-        
-        // Clear redo stack when new operation is added
-        self.redo_operations.clear();
-        
-        // Keep stack size under control
-        if self.undo_operations.len() > self.max_size {
-            self.undo_operations.remove(0);
-        }
-    }
-    
-    pub fn undo(&mut self) -> Option<Operation> {
-        if let Some(operation) = self.undo_operations.pop() {
-            self.redo_operations.push(operation.clone());
-            Some(operation)
-        } else {
-            None
+        let seq = self.insert(self.current, operation, Utc::now());
+        self.current = Some(seq);
+
+        // Keep the tree size under control
+        while self.nodes.len() > self.max_size {
+            self.forget_oldest();
         }
     }
-    
-    pub fn redo(&mut self) -> Option<Operation> {
-        if let Some(operation) = self.redo_operations.pop() {
-            self.undo_operations.push(operation.clone());
-            Some(operation)
-        } else {
-            None
+
+    /// Keep at most `max_size` changes from now on, forgetting the oldest
+    pub fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+        while self.nodes.len() > self.max_size {
+            self.forget_oldest();
         }
     }
-    
+
+    /// Revert the current change on `data` and return it, or `None` if there
+    /// is nothing to undo. Nothing changes if it no longer applies.
+    pub fn undo(&mut self, data: &mut TaskData) -> Result<Option<Operation>, String> {
+        let Some(seq) = self.current else {
+            return Ok(None);
+        };
+        let node = &self.nodes[&seq];
+        let (parent, operation) = (node.parent, node.operation.clone());
+        self.travel(parent, data)?;
+        Ok(Some(operation))
+    }
+
+    /// Apply the change undone last on `data` and return it, or `None` if
+    /// there is nothing to redo. Nothing changes if it no longer applies.
+    pub fn redo(&mut self, data: &mut TaskData) -> Result<Option<Operation>, String> {
+        let Some(seq) = self.redo_target() else {
+            return Ok(None);
+        };
+        let operation = self.nodes[&seq].operation.clone();
+        self.travel(Some(seq), data)?;
+        Ok(Some(operation))
+    }
+
     pub fn can_undo(&self) -> bool {
-        !self.undo_operations.is_empty()
+        self.current.is_some()
     }
-    
+
     pub fn can_redo(&self) -> bool {
-        !self.redo_operations.is_empty()
+        self.redo_target().is_some()
+    }
+
+    /// The state `distance` back (or forward) in time, or `None` if there is nowhere to go
+    pub fn target(&self, distance: UndoDistance, forward: bool) -> Option<Option<usize>> {
+        match distance {
+            UndoDistance::Steps(count) => self.step_target(count, forward),
+            UndoDistance::Time(duration) => self.time_target(duration, forward),
+        }
+    }
+
+    /// The state `count` steps back (or forward) in time, across branches, for `g-`/`g+`
+    fn step_target(&self, count: usize, forward: bool) -> Option<Option<usize>> {
+        let mut target = self.current;
+        for _ in 0..count {
+            target = match (forward, target) {
+                (false, None) => break,
+                (false, Some(seq)) => self.nodes.range(..seq).next_back().map(|(&k, _)| k),
+                (true, None) => match self.nodes.keys().next() {
+                    Some(&k) => Some(k),
+                    None => break,
+                },
+                (true, Some(seq)) => match self.nodes.range(seq + 1..).next() {
+                    Some((&k, _)) => Some(k),
+                    None => break,
+                },
+            };
+        }
+        (target != self.current).then_some(target)
+    }
+
+    /// The state as it was `duration` before (or after) the current one, for `:earlier`/`:later`
+    fn time_target(&self, duration: Duration, forward: bool) -> Option<Option<usize>> {
+        let reference = match self.current {
+            Some(seq) => self.nodes[&seq].time,
+            None => self.nodes.values().next()?.time,
+        };
+        // Past the representable range means the newest or oldest state
+        let time = if forward {
+            reference
+                .checked_add_signed(duration)
+                .unwrap_or(DateTime::<Utc>::MAX_UTC)
+        } else {
+            reference
+                .checked_sub_signed(duration)
+                .unwrap_or(DateTime::<Utc>::MIN_UTC)
+        };
+        // The last change made at or before that time
+        let target = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.time <= time)
+            .map(|(&k, _)| k)
+            .next_back();
+        (target != self.current).then_some(target)
+    }
+
+    /// Move `data` to another state of the tree by undoing up to the common
+    /// ancestor and redoing down the target's branch. Nothing changes if a step
+    /// no longer applies. Returns the number of operations undone and redone.
+    pub fn travel(&mut self, target: Option<usize>, data: &mut TaskData) -> Result<usize, String> {
+        let (up, down) = self.path_between(target);
+        let mut state = data.clone();
+        for seq in &up {
+            let operation = &self.nodes[seq].operation;
+            if !operation.can_revert(&state) {
+                return Err(format!("Can't undo \"{}\": the task changed since", operation.describe()));
+            }
+            operation.revert(&mut state);
+        }
+        for seq in down.iter().rev() {
+            let operation = &self.nodes[seq].operation;
+            if !operation.can_apply(&state) {
+                return Err(format!("Can't redo \"{}\": the task changed since", operation.describe()));
+            }
+            operation.apply(&mut state);
+        }
+
+        // Redo keeps following the branch we came from, or the one we went to
+        for &seq in up.iter().chain(down.iter().rev()) {
+            let parent = self.nodes[&seq].parent;
+            self.set_redo(parent, Some(seq));
+        }
+        *data = state;
+        self.current = target;
+        Ok(up.len() + down.len())
+    }
+
+    /// The tree for the `:undotree` panel, newest state first
+    pub fn lines(&self) -> Vec<UndoTreeLine> {
+        let mut columns: BTreeMap<usize, usize> = BTreeMap::new();
+        let mut next_column = 1;
+        let mut lines = Vec::new();
+        for (&seq, node) in &self.nodes {
+            // The oldest child continues its parent's branch, later ones start new columns
+            let first_child = self.children(node.parent).first() == Some(&seq);
+            let column = match node.parent {
+                Some(parent) if first_child => columns[&parent],
+                None if first_child => 0,
+                _ => {
+                    next_column += 1;
+                    next_column - 1
+                }
+            };
+            columns.insert(seq, column);
+            lines.push(UndoTreeLine {
+                seq,
+                column,
+                time: Some(node.time),
+                summary: node.operation.describe(),
+                current: self.current == Some(seq),
+            });
+        }
+        lines.push(UndoTreeLine {
+            seq: 0,
+            column: 0,
+            time: None,
+            summary: String::from("Oldest remembered state"),
+            current: self.current.is_none(),
+        });
+        lines.reverse();
+        lines
+    }
+
+    /// Serialize the tree for the journal file
+    pub fn to_journal(&self) -> serde_json::Result<Vec<u8>> {
+        serde_json::to_vec(self)
+    }
+
+    /// Restore the tree from a journal, forgetting changes older than `max_age`
+    /// and keeping at most `max_size` of them
+    pub fn from_journal(content: &[u8], max_size: usize, max_age: Duration) -> serde_json::Result<Self> {
+        let mut tree: UndoTree = serde_json::from_slice(content)?;
+        tree.max_size = max_size;
+
+        let cutoff = Utc::now() - max_age;
+        while tree.nodes.len() > max_size
+            || tree.nodes.values().next().is_some_and(|node| node.time < cutoff)
+        {
+            tree.forget_oldest();
+        }
+        Ok(tree)
+    }
+
+    /// Drop history that no longer matches `data`, e.g. after the data file was
+    /// changed outside taskim. The states before the current one are checked by
+    /// undoing back to the oldest one; history from the first mismatch back is
+    /// forgotten. Then every branch is replayed from there and branches that no
+    /// longer apply are dropped. Returns whether anything was dropped.
+    pub fn retain_consistent(&mut self, data: &TaskData) -> bool {
+        let size = self.nodes.len();
+
+        let mut state = data.clone();
+        for seq in self.path_to_root(self.current) {
+            let operation = &self.nodes[&seq].operation;
+            if !operation.can_revert(&state) {
+                self.rebase(seq);
+                break;
+            }
+            operation.revert(&mut state);
+        }
+        self.retain_applicable(None, &state);
+
+        self.nodes.len() != size
+    }
+
+    /// Remove the branches below `parent` whose operations don't apply to `state`
+    fn retain_applicable(&mut self, parent: Option<usize>, state: &TaskData) {
+        for child in self.children(parent) {
+            let operation = &self.nodes[&child].operation;
+            if operation.can_apply(state) {
+                let mut child_state = state.clone();
+                operation.apply(&mut child_state);
+                self.retain_applicable(Some(child), &child_state);
+            } else {
+                self.remove_subtree(child);
+            }
+        }
+    }
+
+    fn insert(&mut self, parent: Option<usize>, operation: Operation, time: DateTime<Utc>) -> usize {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.nodes.insert(
+            seq,
+            Node {
+                operation,
+                time,
+                parent,
+                redo_child: None,
+            },
+        );
+        self.set_redo(parent, Some(seq));
+        seq
+    }
+
+    fn redo_target(&self) -> Option<usize> {
+        match self.current {
+            Some(seq) => self.nodes[&seq].redo_child,
+            None => self.root_redo,
+        }
+    }
+
+    fn set_redo(&mut self, parent: Option<usize>, child: Option<usize>) {
+        match parent {
+            Some(seq) => {
+                if let Some(node) = self.nodes.get_mut(&seq) {
+                    node.redo_child = child;
+                }
+            }
+            None => self.root_redo = child,
+        }
+    }
+
+    fn children(&self, parent: Option<usize>) -> Vec<usize> {
+        self.nodes
+            .iter()
+            .filter(|(_, node)| node.parent == parent)
+            .map(|(&k, _)| k)
+            .collect()
+    }
+
+    /// Ids of the tasks that travelling to `target` changes
+    pub fn task_ids_towards(&self, target: Option<usize>) -> Vec<&str> {
+        let (up, down) = self.path_between(target);
+        up.iter()
+            .chain(&down)
+            .flat_map(|seq| self.nodes[seq].operation.task_ids())
+            .collect()
+    }
+
+    /// The states undone from the current one up to the common ancestor with
+    /// `target`, and those redone from there down to `target` (last first)
+    fn path_between(&self, target: Option<usize>) -> (Vec<usize>, Vec<usize>) {
+        let mut up = self.path_to_root(self.current);
+        let mut down = self.path_to_root(target);
+        while let (Some(a), Some(b)) = (up.last(), down.last()) {
+            if a != b {
+                break;
+            }
+            up.pop();
+            down.pop();
+        }
+        (up, down)
+    }
+
+    /// `state` and its ancestors, nearest first
+    fn path_to_root(&self, state: Option<usize>) -> Vec<usize> {
+        let mut path = Vec::new();
+        let mut next = state;
+        while let Some(seq) = next {
+            path.push(seq);
+            next = self.nodes[&seq].parent;
+        }
+        path
+    }
+
+    /// Forget the oldest change, making the state after it the oldest remembered one
+    fn forget_oldest(&mut self) {
+        let Some(&oldest) = self.nodes.keys().next() else {
+            return;
+        };
+        if self.path_to_root(self.current).contains(&oldest) {
+            self.rebase(oldest);
+        } else {
+            // A branch we are not on, and can't get back to without its root
+            self.remove_subtree(oldest);
+        }
+    }
+
+    /// Make the state after `seq` the oldest remembered one: forget `seq`, its
+    /// ancestors, and every branch that doesn't start from it
+    fn rebase(&mut self, seq: usize) {
+        let redo_child = self.nodes[&seq].redo_child;
+        let keep = self.descendants(seq);
+        self.nodes.retain(|k, _| keep.contains(k));
+        for child in self.nodes.values_mut() {
+            if child.parent == Some(seq) {
+                child.parent = None;
+            }
+        }
+        self.root_redo = redo_child;
+        if self.current.is_some_and(|current| !keep.contains(&current)) {
+            self.current = None;
+        }
+    }
+
+    /// Remove `seq` and everything below it
+    fn remove_subtree(&mut self, seq: usize) {
+        let mut removed = self.descendants(seq);
+        removed.insert(seq);
+        self.nodes.retain(|k, _| !removed.contains(k));
+        for node in self.nodes.values_mut() {
+            if node.redo_child.is_some_and(|child| removed.contains(&child)) {
+                node.redo_child = None;
+            }
+        }
+        if self.root_redo.is_some_and(|child| removed.contains(&child)) {
+            self.root_redo = None;
+        }
+        if self.current.is_some_and(|current| removed.contains(&current)) {
+            self.current = None;
+        }
+    }
+
+    /// Everything below `seq`, not including it
+    fn descendants(&self, seq: usize) -> HashSet<usize> {
+        let mut found = HashSet::new();
+        // Children always have higher sequence numbers than their parents
+        for (&k, node) in self.nodes.range(seq + 1..) {
+            if node.parent.is_some_and(|parent| parent == seq || found.contains(&parent)) {
+                found.insert(k);
+            }
+        }
+        found
+    }
+}
+
+impl Operation {
+    /// Short human readable summary, e.g. for history commit messages
+    pub fn describe(&self) -> String {
+        match self {
+            Operation::DeleteTask { task } => {
+                format!("Delete \"{}\" from {}", task.title, task.start.date_naive())
+            }
+            Operation::EditTask {
+                old_task, new_task, ..
+            } => {
+                if old_task.title == new_task.title {
+                    format!("Edit \"{}\"", new_task.title)
+                } else {
+                    format!("Rename \"{}\" to \"{}\"", old_task.title, new_task.title)
+                }
+            }
+            Operation::CreateTask { task } => {
+                format!("Add \"{}\" on {}", task.title, task.start.date_naive())
+            }
+            Operation::ToggleComplete {
+                title, completed, ..
+            } => format!(
+                "Mark \"{}\" {}",
+                title,
+                if *completed { "complete" } else { "incomplete" }
+            ),
+            Operation::MoveTask {
+                title, new_start, ..
+            } => format!("Move \"{}\" to {}", title, new_start.date_naive()),
+            Operation::Reorder { changes } => format!("Reorder {} task(s)", changes.len()),
+            Operation::Batch { description, .. } => description.clone(),
+        }
+    }
+
+    /// Ids of the tasks the operation changes
+    pub fn task_ids(&self) -> Vec<&str> {
+        match self {
+            Operation::DeleteTask { task } | Operation::CreateTask { task } => vec![&task.id],
+            Operation::EditTask { task_id, .. }
+            | Operation::ToggleComplete { task_id, .. }
+            | Operation::MoveTask { task_id, .. } => vec![task_id],
+            Operation::Reorder { changes } => changes.iter().map(|(id, _, _)| id.as_str()).collect(),
+            Operation::Batch { operations, .. } => {
+                operations.iter().flat_map(Operation::task_ids).collect()
+            }
+        }
+    }
+
+    /// The operation that gives a batch its meaning, e.g. the task creation in a
+    /// creation that also shifted the tasks below it
+    pub fn primary(&self) -> &Operation {
+        match self {
+            Operation::Batch { operations, .. } => operations
+                .iter()
+                .rev()
+                .find(|op| !matches!(op, Operation::Reorder { .. }))
+                .or(operations.last())
+                .map_or(self, Operation::primary),
+            _ => self,
+        }
+    }
+
+    /// Bundle the operation with the order shifts it caused in the tasks that
+    /// were there before, so undo restores their exact ordering
+    pub fn with_reorder(self, before: &TaskData, after: &TaskData) -> Operation {
+        let old_orders: HashMap<&str, u32> = before
+            .events
+            .iter()
+            .map(|task| (task.id.as_str(), task.order))
+            .collect();
+        let changes: Vec<(String, u32, u32)> = after
+            .events
+            .iter()
+            .filter_map(|task| {
+                let old_order = *old_orders.get(task.id.as_str())?;
+                (old_order != task.order).then(|| (task.id.clone(), old_order, task.order))
+            })
+            .collect();
+        if changes.is_empty() {
+            return self;
+        }
+        Operation::Batch {
+            description: self.describe(),
+            operations: vec![self, Operation::Reorder { changes }],
+        }
+    }
+
+    /// Combine operations made in sequence into one undo step
+    pub fn batch(mut operations: Vec<Operation>) -> Option<Operation> {
+        match operations.len() {
+            0 => None,
+            1 => operations.pop(),
+            n => Some(Operation::Batch {
+                description: format!("{} and {} more", operations[0].describe(), n - 1),
+                operations,
+            }),
+        }
+    }
+
+    /// Perform the operation (again) on `data`
+    pub fn apply(&self, data: &mut TaskData) {
+        match self {
+            Operation::DeleteTask { task } => data.events.retain(|t| t.id != task.id),
+            Operation::EditTask {
+                task_id, new_task, ..
+            } => {
+                if let Some(existing) = find_mut(data, task_id) {
+                    *existing = new_task.clone();
+                }
+            }
+            Operation::CreateTask { task } => data.events.push(task.clone()),
+            Operation::ToggleComplete {
+                task_id, completed, ..
+            } => {
+                if let Some(task) = find_mut(data, task_id) {
+                    task.completed = *completed;
+                }
+            }
+            Operation::MoveTask {
+                task_id,
+                new_start,
+                new_end,
+                ..
+            } => {
+                if let Some(task) = find_mut(data, task_id) {
+                    task.start = *new_start;
+                    task.end = *new_end;
+                }
+            }
+            Operation::Reorder { changes } => {
+                for (task_id, _, new_order) in changes {
+                    if let Some(task) = find_mut(data, task_id) {
+                        task.order = *new_order;
+                    }
+                }
+            }
+            Operation::Batch { operations, .. } => {
+                for operation in operations {
+                    operation.apply(data);
+                }
+            }
+        }
+    }
+
+    /// Undo the operation on `data`
+    pub fn revert(&self, data: &mut TaskData) {
+        match self {
+            Operation::DeleteTask { task } => data.events.push(task.clone()),
+            Operation::EditTask {
+                task_id, old_task, ..
+            } => {
+                if let Some(existing) = find_mut(data, task_id) {
+                    *existing = old_task.clone();
+                }
+            }
+            Operation::CreateTask { task } => data.events.retain(|t| t.id != task.id),
+            Operation::ToggleComplete {
+                task_id, completed, ..
+            } => {
+                if let Some(task) = find_mut(data, task_id) {
+                    task.completed = !*completed;
+                }
+            }
+            Operation::MoveTask {
+                task_id,
+                old_start,
+                old_end,
+                ..
+            } => {
+                if let Some(task) = find_mut(data, task_id) {
+                    task.start = *old_start;
+                    task.end = *old_end;
+                }
+            }
+            Operation::Reorder { changes } => {
+                for (task_id, old_order, _) in changes {
+                    if let Some(task) = find_mut(data, task_id) {
+                        task.order = *old_order;
+                    }
+                }
+            }
+            Operation::Batch { operations, .. } => {
+                for operation in operations.iter().rev() {
+                    operation.revert(data);
+                }
+            }
+        }
+    }
+
+    /// Whether `data` still looks the way this operation left it
+    fn can_revert(&self, data: &TaskData) -> bool {
+        match self {
+            Operation::DeleteTask { task } => find(data, &task.id).is_none(),
+            Operation::EditTask {
+                task_id, new_task, ..
+            } => find(data, task_id).is_some_and(|t| same_content(t, new_task)),
+            Operation::CreateTask { task } => {
+                find(data, &task.id).is_some_and(|t| same_content(t, task))
+            }
+            Operation::ToggleComplete {
+                task_id, completed, ..
+            } => find(data, task_id).is_some_and(|t| t.completed == *completed),
+            Operation::MoveTask {
+                task_id, new_start, ..
+            } => find(data, task_id).is_some_and(|t| t.start == *new_start),
+            Operation::Reorder { changes } => changes
+                .iter()
+                .all(|(task_id, _, _)| find(data, task_id).is_some()),
+            Operation::Batch { operations, .. } => {
+                let mut state = data.clone();
+                operations.iter().rev().all(|operation| {
+                    let ok = operation.can_revert(&state);
+                    operation.revert(&mut state);
+                    ok
+                })
+            }
+        }
+    }
+
+    /// Whether `data` still looks the way it was before this operation
+    fn can_apply(&self, data: &TaskData) -> bool {
+        match self {
+            Operation::DeleteTask { task } => {
+                find(data, &task.id).is_some_and(|t| same_content(t, task))
+            }
+            Operation::EditTask {
+                task_id, old_task, ..
+            } => find(data, task_id).is_some_and(|t| same_content(t, old_task)),
+            Operation::CreateTask { task } => find(data, &task.id).is_none(),
+            Operation::ToggleComplete {
+                task_id, completed, ..
+            } => find(data, task_id).is_some_and(|t| t.completed != *completed),
+            Operation::MoveTask {
+                task_id, old_start, ..
+            } => find(data, task_id).is_some_and(|t| t.start == *old_start),
+            Operation::Reorder { changes } => changes
+                .iter()
+                .all(|(task_id, _, _)| find(data, task_id).is_some()),
+            Operation::Batch { operations, .. } => {
+                let mut state = data.clone();
+                operations.iter().all(|operation| {
+                    let ok = operation.can_apply(&state);
+                    operation.apply(&mut state);
+                    ok
+                })
+            }
+        }
+    }
+}
+
+fn find<'a>(data: &'a TaskData, task_id: &str) -> Option<&'a Task> {
+    data.events.iter().find(|t| t.id == task_id)
+}
+
+fn find_mut<'a>(data: &'a mut TaskData, task_id: &str) -> Option<&'a mut Task> {
+    data.events.iter_mut().find(|t| t.id == task_id)
+}
+
+/// Compare tasks ignoring their order, which merges and syncs renumber without
+/// going through the undo history
+fn same_content(a: &Task, b: &Task) -> bool {
+    Task {
+        order: b.order,
+        ..a.clone()
+    } == *b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn day(d: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 3, d, 9, 0, 0).unwrap()
+    }
+
+    /// Three tasks on March 1st and two on March 2nd, ordered 0..
+    fn sample() -> TaskData {
+        let mut data = TaskData::default();
+        for (title, d, order) in [("a", 1, 0), ("b", 1, 1), ("c", 1, 2), ("d", 2, 0), ("e", 2, 1)] {
+            let mut task = Task::new(title.to_string(), day(d));
+            task.id = title.to_string();
+            task.order = order;
+            data.events.push(task);
+        }
+        data
+    }
+
+    /// Tasks sorted by id, so states compare regardless of vector position
+    fn snapshot(data: &TaskData) -> Vec<Task> {
+        let mut tasks = data.events.clone();
+        tasks.sort_by(|a, b| a.id.cmp(&b.id));
+        tasks
+    }
+
+    /// Run `change` on `data` and record it the way the app does
+    fn recorded(data: &mut TaskData, change: impl FnOnce(&mut TaskData) -> Operation) -> Operation {
+        let before = data.clone();
+        change(data).with_reorder(&before, data)
+    }
+
+    fn assert_inverse(before: &TaskData, operation: &Operation, after: &TaskData) {
+        assert!(operation.can_revert(after));
+        let mut state = after.clone();
+        operation.revert(&mut state);
+        assert_eq!(snapshot(&state), snapshot(before));
+        assert!(operation.can_apply(&state));
+        operation.apply(&mut state);
+        assert_eq!(snapshot(&state), snapshot(after));
+    }
+
+    #[test]
+    fn insert_restores_sibling_order() {
+        let before = sample();
+        let mut data = before.clone();
+        let operation = recorded(&mut data, |data| {
+            let mut task = Task::new("new".to_string(), day(1));
+            task.id = "new".to_string();
+            data.insert_task_at_order(task.clone(), 1);
+            let task = find(data, "new").unwrap().clone();
+            Operation::CreateTask { task }
+        });
+        assert!(matches!(operation, Operation::Batch { .. }));
+        assert_inverse(&before, &operation, &data);
+    }
+
+    #[test]
+    fn delete_restores_sibling_order() {
+        let before = sample();
+        let mut data = before.clone();
+        let operation = recorded(&mut data, |data| {
+            let task = data.remove_task_and_reorder("a").unwrap();
+            Operation::DeleteTask { task }
+        });
+        assert_eq!(find(&data, "b").unwrap().order, 0);
+        assert_inverse(&before, &operation, &data);
+    }
+
+    #[test]
+    fn toggle_is_its_own_inverse() {
+        let before = sample();
+        let mut data = before.clone();
+        let operation = recorded(&mut data, |data| {
+            let task = find_mut(data, "b").unwrap();
+            task.completed = true;
+            Operation::ToggleComplete {
+                task_id: task.id.clone(),
+                title: task.title.clone(),
+                completed: true,
+            }
+        });
+        assert_inverse(&before, &operation, &data);
+    }
+
+    #[test]
+    fn move_restores_both_days() {
+        let before = sample();
+        let mut data = before.clone();
+        let operation = recorded(&mut data, |data| {
+            let mut task = data.remove_task_and_reorder("a").unwrap();
+            let old_start = task.start;
+            let old_end = task.end;
+            task.start = day(2);
+            task.end = day(2) + Duration::hours(1);
+            data.insert_task_at_order(task.clone(), 0);
+            Operation::MoveTask {
+                task_id: task.id.clone(),
+                title: task.title.clone(),
+                old_start,
+                old_end,
+                new_start: task.start,
+                new_end: task.end,
+            }
+        });
+        assert_eq!(find(&data, "a").unwrap().order, 0);
+        assert_eq!(find(&data, "d").unwrap().order, 1);
+        assert_inverse(&before, &operation, &data);
+    }
+
+    #[test]
+    fn batch_reverts_in_reverse_order() {
+        let before = sample();
+        let mut data = before.clone();
+        let first = recorded(&mut data, |data| {
+            let task = data.remove_task_and_reorder("b").unwrap();
+            Operation::DeleteTask { task }
+        });
+        let second = recorded(&mut data, |data| {
+            let old_task = find(data, "c").unwrap().clone();
+            let task = find_mut(data, "c").unwrap();
+            task.title = "renamed".to_string();
+            Operation::EditTask {
+                task_id: task.id.clone(),
+                old_task,
+                new_task: task.clone(),
+            }
+        });
+        let batch = Operation::Batch {
+            description: "Two changes".to_string(),
+            operations: vec![first, second],
+        };
+        assert_inverse(&before, &batch, &data);
+        assert_eq!(batch.describe(), "Two changes");
+    }
+
+    #[test]
+    fn undo_and_redo_sequences_are_inverse() {
+        let mut states = vec![sample()];
+        let mut tree = UndoTree::new(100);
+        let mut data = sample();
+        let changes: [fn(&mut TaskData) -> Operation; 4] = [
+            |data| {
+                let task = data.remove_task_and_reorder("a").unwrap();
+                Operation::DeleteTask { task }
+            },
+            |data| {
+                let mut task = Task::new("x".to_string(), day(2));
+                task.id = "x".to_string();
+                data.insert_task_at_order(task, 0);
+                Operation::CreateTask {
+                    task: find(data, "x").unwrap().clone(),
+                }
+            },
+            |data| {
+                find_mut(data, "d").unwrap().completed = true;
+                Operation::ToggleComplete {
+                    task_id: "d".to_string(),
+                    title: "d".to_string(),
+                    completed: true,
+                }
+            },
+            |data| {
+                let task = data.remove_task_and_reorder("c").unwrap();
+                Operation::DeleteTask { task }
+            },
+        ];
+        for change in changes {
+            tree.push(recorded(&mut data, change));
+            states.push(data.clone());
+        }
+
+        for expected in states.iter().rev().skip(1) {
+            tree.undo(&mut data).unwrap().unwrap();
+            assert_eq!(snapshot(&data), snapshot(expected));
+        }
+        assert!(!tree.can_undo());
+        for expected in states.iter().skip(1) {
+            tree.redo(&mut data).unwrap().unwrap();
+            assert_eq!(snapshot(&data), snapshot(expected));
+        }
+        assert!(!tree.can_redo());
+    }
+
+    #[test]
+    fn undo_and_redo_refuse_a_task_that_changed_since() {
+        let mut data = sample();
+        let mut tree = UndoTree::new(10);
+        tree.push(recorded(&mut data, |data| {
+            find_mut(data, "a").unwrap().completed = true;
+            Operation::ToggleComplete {
+                task_id: "a".to_string(),
+                title: "a".to_string(),
+                completed: true,
+            }
+        }));
+
+        // Someone else unchecks the task in the meantime
+        find_mut(&mut data, "a").unwrap().completed = false;
+        let before = snapshot(&data);
+        assert!(tree.undo(&mut data).is_err());
+        assert_eq!(snapshot(&data), before);
+        assert!(tree.can_undo());
+
+        find_mut(&mut data, "a").unwrap().completed = true;
+        assert!(tree.undo(&mut data).unwrap().is_some());
+        find_mut(&mut data, "a").unwrap().completed = true;
+        assert!(tree.redo(&mut data).is_err());
+        assert!(tree.can_redo());
+    }
+
+    fn toggle(data: &mut TaskData, id: &str) -> Operation {
+        recorded(data, |data| {
+            let task = find_mut(data, id).unwrap();
+            task.completed = !task.completed;
+            Operation::ToggleComplete {
+                task_id: id.to_string(),
+                title: id.to_string(),
+                completed: task.completed,
+            }
+        })
+    }
+
+    #[test]
+    fn time_steps_reach_the_branch_an_undo_left_behind() {
+        let mut data = sample();
+        let mut tree = UndoTree::new(10);
+        tree.push(toggle(&mut data, "a"));
+        let first = data.clone();
+        tree.push(toggle(&mut data, "b"));
+        let abandoned = data.clone();
+
+        // Undo, then a new change starts a second branch
+        tree.undo(&mut data).unwrap().unwrap();
+        tree.push(toggle(&mut data, "c"));
+        let newest = data.clone();
+        assert_eq!(tree.undo(&mut data).unwrap().unwrap().task_ids(), ["c"]);
+        assert_eq!(snapshot(&data), snapshot(&first));
+        tree.redo(&mut data).unwrap().unwrap();
+
+        // g- goes back in time, through the abandoned change
+        let back = |tree: &UndoTree| tree.target(UndoDistance::Steps(1), false).unwrap();
+        let forward = |tree: &UndoTree| tree.target(UndoDistance::Steps(1), true).unwrap();
+        tree.travel(back(&tree), &mut data).unwrap();
+        assert_eq!(snapshot(&data), snapshot(&abandoned));
+        tree.travel(back(&tree), &mut data).unwrap();
+        assert_eq!(snapshot(&data), snapshot(&first));
+        tree.travel(back(&tree), &mut data).unwrap();
+        assert_eq!(snapshot(&data), snapshot(&sample()));
+        assert_eq!(tree.target(UndoDistance::Steps(1), false), None);
+
+        // g+ comes forward the same way; 2g+ skips a state
+        let two = tree.target(UndoDistance::Steps(2), true).unwrap();
+        tree.travel(two, &mut data).unwrap();
+        assert_eq!(snapshot(&data), snapshot(&abandoned));
+        // Crossing branches undoes one change and redoes the other
+        assert_eq!(tree.travel(forward(&tree), &mut data), Ok(2));
+        assert_eq!(snapshot(&data), snapshot(&newest));
+        assert_eq!(tree.target(UndoDistance::Steps(1), true), None);
+    }
+
+    #[test]
+    fn earlier_and_later_go_by_the_time_of_each_change() {
+        let mut data = sample();
+        let mut tree = UndoTree::new(10);
+        let mut states = vec![data.clone()];
+        for id in ["a", "b", "c"] {
+            tree.push(toggle(&mut data, id));
+            states.push(data.clone());
+        }
+        // Made at 9:00, 9:10 and 9:30
+        let start = day(1);
+        for (seq, minutes) in [(1, 0), (2, 10), (3, 30)] {
+            tree.nodes.get_mut(&seq).unwrap().time = start + Duration::minutes(minutes);
+        }
+
+        let earlier = |tree: &UndoTree, text: &str| {
+            tree.target(UndoDistance::parse(text).unwrap(), false)
+        };
+        let later = |tree: &UndoTree, text: &str| {
+            tree.target(UndoDistance::parse(text).unwrap(), true)
+        };
+        // 15 minutes before 9:30 the last change was the one at 9:10
+        assert_eq!(earlier(&tree, "15m"), Some(Some(2)));
+        assert_eq!(earlier(&tree, "20m"), Some(Some(2)));
+        assert_eq!(earlier(&tree, "30m"), Some(Some(1)));
+        assert_eq!(earlier(&tree, "1h"), Some(None));
+        assert_eq!(later(&tree, "1d"), None);
+
+        tree.travel(earlier(&tree, "1h").unwrap(), &mut data).unwrap();
+        assert_eq!(snapshot(&data), snapshot(&states[0]));
+        // Later counts from the oldest change when before all of them
+        tree.travel(later(&tree, "10m").unwrap(), &mut data).unwrap();
+        assert_eq!(snapshot(&data), snapshot(&states[2]));
+        assert_eq!(later(&tree, "19m"), None);
+        tree.travel(later(&tree, "20m").unwrap(), &mut data).unwrap();
+        assert_eq!(snapshot(&data), snapshot(&states[3]));
+    }
+
+    #[test]
+    fn undo_distances_parse() {
+        assert_eq!(UndoDistance::parse(""), Ok(UndoDistance::Steps(1)));
+        assert_eq!(UndoDistance::parse(" 3 "), Ok(UndoDistance::Steps(3)));
+        assert_eq!(UndoDistance::parse("30s"), Ok(UndoDistance::Time(Duration::seconds(30))));
+        assert_eq!(UndoDistance::parse("10m"), Ok(UndoDistance::Time(Duration::minutes(10))));
+        assert_eq!(UndoDistance::parse("2h"), Ok(UndoDistance::Time(Duration::hours(2))));
+        assert_eq!(UndoDistance::parse("1d"), Ok(UndoDistance::Time(Duration::days(1))));
+    }
+
+    #[test]
+    fn undo_distances_reject_bad_input_without_panicking() {
+        for input in ["5é", "é", "m", "3x", "ten", "0", "-5m", "-2", "0s", "99999999999999d"] {
+            assert!(UndoDistance::parse(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn time_travel_past_the_representable_range_stops_at_the_ends() {
+        let mut data = sample();
+        let mut tree = UndoTree::new(10);
+        tree.push(recorded(&mut data, |data| {
+            let task = data.remove_task_and_reorder("a").unwrap();
+            Operation::DeleteTask { task }
+        }));
+        let far = UndoDistance::parse("99999999999d").unwrap();
+        assert_eq!(tree.target(far, false), Some(None));
+        assert_eq!(tree.target(far, true), None);
     }
 }